@@ -154,7 +154,8 @@ impl<B, I, C> BlockImport<B> for MathchainBlockImport<B, I, C> where
 	}
 }
 
-fn find_mathchain_log<B: BlockT>(
+/// Find the Ethereum block the runtime committed to in a Substrate header.
+pub fn find_mathchain_log<B: BlockT>(
 	header: &B::Header,
 ) -> Result<ConsensusLog, Error> {
 	let mut mathchain_log: Option<_> = None;
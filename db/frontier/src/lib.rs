@@ -0,0 +1,267 @@
+// This file is part of Mathchain.
+
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Frontier mapping DB.
+//!
+//! Patched over the `fc-db` of Frontier, whose API and RocksDB layout it keeps,
+//! so that `fc-rpc` and `fc-mapping-sync` run on it unchanged and existing
+//! databases open as they are. On top of upstream, the database can be opened on
+//! ParityDB, and with extension columns after the Frontier ones, which other
+//! crates reach through `Backend::database`.
+
+pub mod parity_db_adapter;
+
+pub use sp_database::Database;
+
+use std::{sync::Arc, path::{Path, PathBuf}, marker::PhantomData};
+use codec::{Encode, Decode};
+use parking_lot::Mutex;
+use sp_core::H256;
+use sp_runtime::traits::Block as BlockT;
+
+const DB_HASH_LEN: usize = 32;
+/// Hash type that this backend uses for the database.
+pub type DbHash = [u8; DB_HASH_LEN];
+
+/// Database settings.
+pub struct DatabaseSettings {
+	/// Where to find the database.
+	pub source: DatabaseSettingsSrc,
+	/// Number of extension columns opened after the Frontier ones.
+	pub extension_columns: u32,
+}
+
+/// Where to find the database.
+#[derive(Debug, Clone)]
+pub enum DatabaseSettingsSrc {
+	/// Load a RocksDB database from a given path. Recommended for most uses.
+	RocksDb {
+		/// Path to the database.
+		path: PathBuf,
+		/// Cache size in MiB.
+		cache_size: usize,
+	},
+	/// Load a ParityDb database from a given path.
+	ParityDb {
+		/// Path to the database.
+		path: PathBuf,
+	},
+}
+
+impl DatabaseSettingsSrc {
+	/// Return dabase path for databases that are on the disk.
+	pub fn path(&self) -> Option<&Path> {
+		match self {
+			DatabaseSettingsSrc::RocksDb { path, .. } => Some(path.as_path()),
+			DatabaseSettingsSrc::ParityDb { path } => Some(path.as_path()),
+		}
+	}
+}
+
+pub mod columns {
+	/// Number of the Frontier columns, extension columns start there.
+	pub const NUM_COLUMNS: u32 = 4;
+
+	pub const META: u32 = 0;
+	pub const BLOCK_MAPPING: u32 = 1;
+	pub const TRANSACTION_MAPPING: u32 = 2;
+	pub const SYNCED_MAPPING: u32 = 3;
+}
+
+pub(crate) mod static_keys {
+	pub const CURRENT_SYNCING_TIPS: &[u8] = b"CURRENT_SYNCING_TIPS";
+}
+
+pub struct Backend<Block: BlockT> {
+	db: Arc<dyn Database<DbHash>>,
+	meta: Arc<MetaDb<Block>>,
+	mapping: Arc<MappingDb<Block>>,
+}
+
+impl<Block: BlockT> Backend<Block> {
+	pub fn new(config: &DatabaseSettings) -> Result<Self, String> {
+		let db = open_database(config)?;
+
+		Ok(Self {
+			db: db.clone(),
+			mapping: Arc::new(MappingDb {
+				db: db.clone(),
+				write_lock: Arc::new(Mutex::new(())),
+				_marker: PhantomData,
+			}),
+			meta: Arc::new(MetaDb {
+				db: db.clone(),
+				_marker: PhantomData,
+			}),
+		})
+	}
+
+	pub fn mapping(&self) -> &Arc<MappingDb<Block>> {
+		&self.mapping
+	}
+
+	pub fn meta(&self) -> &Arc<MetaDb<Block>> {
+		&self.meta
+	}
+
+	/// The whole database, to reach the extension columns.
+	pub fn database(&self) -> &Arc<dyn Database<DbHash>> {
+		&self.db
+	}
+}
+
+/// Number of columns of a database opened with `extension_columns`.
+pub fn total_columns(extension_columns: u32) -> u32 {
+	columns::NUM_COLUMNS + extension_columns
+}
+
+fn open_database(config: &DatabaseSettings) -> Result<Arc<dyn Database<DbHash>>, String> {
+	let num_columns = total_columns(config.extension_columns);
+	let db: Arc<dyn Database<DbHash>> = match &config.source {
+		DatabaseSettingsSrc::RocksDb { path, cache_size } => {
+			let mut db_config = kvdb_rocksdb::DatabaseConfig::with_columns(num_columns);
+			// One cache budget for the whole database, shared by all its columns.
+			db_config.memory_budget = (0..num_columns)
+				.map(|column| (column, *cache_size / num_columns as usize))
+				.collect();
+			let path = path.to_str().ok_or_else(|| "Invalid database path".to_string())?;
+
+			let db = kvdb_rocksdb::Database::open(&db_config, &path)
+				.map_err(|err| format!("{}", err))?;
+			sp_database::as_database(db)
+		},
+		DatabaseSettingsSrc::ParityDb { path } => parity_db_adapter::open(path, num_columns)?,
+	};
+
+	Ok(db)
+}
+
+pub struct MetaDb<Block: BlockT> {
+	db: Arc<dyn Database<DbHash>>,
+	_marker: PhantomData<Block>,
+}
+
+impl<Block: BlockT> MetaDb<Block> {
+	pub fn current_syncing_tips(&self) -> Result<Vec<Block::Hash>, String> {
+		match self.db.get(columns::META, static_keys::CURRENT_SYNCING_TIPS) {
+			Some(raw) => Ok(Vec::<Block::Hash>::decode(&mut &raw[..]).map_err(|e| format!("{:?}", e))?),
+			None => Ok(Vec::new()),
+		}
+	}
+
+	pub fn write_current_syncing_tips(&self, tips: Vec<Block::Hash>) -> Result<(), String> {
+		let mut transaction = sp_database::Transaction::new();
+
+		transaction.set(columns::META, static_keys::CURRENT_SYNCING_TIPS, &tips.encode());
+
+		self.db.commit(transaction).map_err(|e| format!("{:?}", e))?;
+
+		Ok(())
+	}
+}
+
+#[derive(Debug)]
+pub struct MappingCommitment<Block: BlockT> {
+	pub block_hash: Block::Hash,
+	pub ethereum_block_hash: H256,
+	pub ethereum_transaction_hashes: Vec<H256>,
+}
+
+#[derive(Clone, Encode, Decode)]
+pub struct TransactionMetadata<Block: BlockT> {
+	pub block_hash: Block::Hash,
+	pub ethereum_block_hash: H256,
+	pub ethereum_index: u32,
+}
+
+pub struct MappingDb<Block: BlockT> {
+	db: Arc<dyn Database<DbHash>>,
+	write_lock: Arc<Mutex<()>>,
+	_marker: PhantomData<Block>,
+}
+
+impl<Block: BlockT> MappingDb<Block> {
+	pub fn is_synced(&self, block_hash: &Block::Hash) -> Result<bool, String> {
+		match self.db.get(columns::SYNCED_MAPPING, &block_hash.encode()) {
+			Some(raw) => Ok(bool::decode(&mut &raw[..]).map_err(|e| format!("{:?}", e))?),
+			None => Ok(false),
+		}
+	}
+
+	pub fn block_hash(&self, ethereum_block_hash: &H256) -> Result<Option<Block::Hash>, String> {
+		match self.db.get(columns::BLOCK_MAPPING, &ethereum_block_hash.encode()) {
+			Some(raw) => Ok(Some(Block::Hash::decode(&mut &raw[..]).map_err(|e| format!("{:?}", e))?)),
+			None => Ok(None),
+		}
+	}
+
+	pub fn transaction_metadata(
+		&self,
+		ethereum_transaction_hash: &H256,
+	) -> Result<Vec<TransactionMetadata<Block>>, String> {
+		match self.db.get(columns::TRANSACTION_MAPPING, &ethereum_transaction_hash.encode()) {
+			Some(raw) => Ok(
+				Vec::<TransactionMetadata<Block>>::decode(&mut &raw[..]).map_err(|e| format!("{:?}", e))?
+			),
+			None => Ok(Vec::new()),
+		}
+	}
+
+	pub fn write_none(&self, block_hash: Block::Hash) -> Result<(), String> {
+		let _lock = self.write_lock.lock();
+
+		let mut transaction = sp_database::Transaction::new();
+
+		transaction.set(columns::SYNCED_MAPPING, &block_hash.encode(), &true.encode());
+
+		self.db.commit(transaction).map_err(|e| format!("{:?}", e))?;
+
+		Ok(())
+	}
+
+	pub fn write_hashes(&self, commitment: MappingCommitment<Block>) -> Result<(), String> {
+		let _lock = self.write_lock.lock();
+
+		let mut transaction = sp_database::Transaction::new();
+
+		transaction.set(
+			columns::BLOCK_MAPPING,
+			&commitment.ethereum_block_hash.encode(),
+			&commitment.block_hash.encode(),
+		);
+
+		for (i, ethereum_transaction_hash) in commitment.ethereum_transaction_hashes.into_iter().enumerate() {
+			let mut metadata = self.transaction_metadata(&ethereum_transaction_hash)?;
+			metadata.push(TransactionMetadata::<Block> {
+				block_hash: commitment.block_hash,
+				ethereum_block_hash: commitment.ethereum_block_hash,
+				ethereum_index: i as u32,
+			});
+			transaction.set(
+				columns::TRANSACTION_MAPPING,
+				&ethereum_transaction_hash.encode(),
+				&metadata.encode(),
+			);
+		}
+
+		transaction.set(columns::SYNCED_MAPPING, &commitment.block_hash.encode(), &true.encode());
+
+		self.db.commit(transaction).map_err(|e| format!("{:?}", e))?;
+
+		Ok(())
+	}
+}
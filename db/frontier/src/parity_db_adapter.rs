@@ -0,0 +1,107 @@
+// This file is part of Mathchain.
+
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! ParityDB backed `sp_database::Database`, and migration from RocksDB.
+
+use std::path::Path;
+use kvdb::KeyValueDB;
+use sp_database::{Database, Change, ColumnId, Transaction, error::DatabaseError};
+
+fn handle_err<T>(result: parity_db::Result<T>) -> Result<T, DatabaseError> {
+	result.map_err(|e| DatabaseError(Box::new(e)))
+}
+
+/// Reads of `sp_database::Database` cannot fail, a failed one is logged and read as missing.
+fn handle_read_err<T>(result: parity_db::Result<Option<T>>) -> Option<T> {
+	result.unwrap_or_else(|e| {
+		log::error!(target: "frontier-db", "ParityDB read error: {:?}", e);
+		None
+	})
+}
+
+/// Wrap ParityDb database into a trait object that implements `sp_database::Database`
+struct DbAdapter(parity_db::Db);
+
+impl<H: Clone + AsRef<[u8]>> Database<H> for DbAdapter {
+	fn commit(&self, transaction: Transaction<H>) -> Result<(), DatabaseError> {
+		let changes = transaction.0.into_iter().map(|change|
+			match change {
+				Change::Set(col, key, value) => Ok((col as u8, key, Some(value))),
+				Change::Remove(col, key) => Ok((col as u8, key, None)),
+				_ => Err(DatabaseError(Box::new(std::io::Error::new(
+					std::io::ErrorKind::Other,
+					"Hashed changes are not supported by the ParityDB adapter",
+				)))),
+			}
+		).collect::<Result<Vec<_>, _>>()?;
+
+		handle_err(self.0.commit(changes))
+	}
+
+	fn get(&self, col: ColumnId, key: &[u8]) -> Option<Vec<u8>> {
+		handle_read_err(self.0.get(col as u8, key))
+	}
+
+	fn contains(&self, col: ColumnId, key: &[u8]) -> bool {
+		handle_read_err(self.0.get_size(col as u8, key)).is_some()
+	}
+
+	fn value_size(&self, col: ColumnId, key: &[u8]) -> Option<usize> {
+		handle_read_err(self.0.get_size(col as u8, key)).map(|s| s as usize)
+	}
+}
+
+fn open_db(path: &Path, num_columns: u32) -> Result<parity_db::Db, String> {
+	let mut config = parity_db::Options::with_columns(path, num_columns as u8);
+	config.sync = true;
+
+	parity_db::Db::open(&config).map_err(|err| format!("{:?}", err))
+}
+
+/// Open a ParityDB database as a `sp_database::Database`.
+pub fn open<H: Clone + AsRef<[u8]>>(
+	path: &Path,
+	num_columns: u32,
+) -> Result<std::sync::Arc<dyn Database<H>>, String> {
+	Ok(std::sync::Arc::new(DbAdapter(open_db(path, num_columns)?)))
+}
+
+/// Copy every column of the RocksDB database at `from` into a new ParityDB
+/// database at `to`, returning the number of copied entries.
+pub fn migrate_from_rocksdb(from: &Path, to: &Path, num_columns: u32) -> Result<usize, String> {
+	if to.exists() {
+		return Err(format!("{} already exists", to.display()));
+	}
+
+	let source_path = from.to_str().ok_or_else(|| "Invalid database path".to_string())?;
+	let source = kvdb_rocksdb::Database::open(
+		&kvdb_rocksdb::DatabaseConfig::with_columns(num_columns),
+		source_path,
+	).map_err(|err| format!("{}", err))?;
+	let target = open_db(to, num_columns)?;
+
+	let mut copied = 0;
+	for column in 0..num_columns {
+		let entries = source.iter(column)
+			.map(|(key, value)| (column as u8, key.into_vec(), Some(value.into_vec())))
+			.collect::<Vec<_>>();
+		copied += entries.len();
+		target.commit(entries).map_err(|err| format!("{:?}", err))?;
+	}
+
+	Ok(copied)
+}
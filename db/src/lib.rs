@@ -0,0 +1,499 @@
+// This file is part of Mathchain.
+
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! MathChain extension columns of the Frontier DB.
+//!
+//! The indexes MathChain adds on top of the Ethereum mappings are stored in
+//! columns of the Frontier DB following the `fc-db` ones, so they share its
+//! backend, its cache and its lifetime.
+
+use std::{sync::Arc, marker::PhantomData, collections::BTreeSet};
+use codec::{Encode, Decode};
+use sp_core::{H160, H256};
+use sp_database::Database;
+use sp_runtime::traits::Block as BlockT;
+use ethereum::Log;
+use mathchain_rpc_primitives::debug::Call;
+
+pub use fc_db::DbHash;
+
+pub mod columns {
+	use fc_db::columns::NUM_COLUMNS as FRONTIER_COLUMNS;
+
+	/// Number of extension columns to open the Frontier DB with.
+	pub const NUM_COLUMNS: u32 = 4;
+
+	pub const META: u32 = FRONTIER_COLUMNS;
+	pub const TRACES: u32 = FRONTIER_COLUMNS + 1;
+	pub const LOGS: u32 = FRONTIER_COLUMNS + 2;
+	pub const LOG_INDEX: u32 = FRONTIER_COLUMNS + 3;
+}
+
+pub(crate) mod static_keys {
+	pub const TRACES_SYNCED: &[u8] = b"TRACES_SYNCED";
+	pub const LOGS_SYNCED: &[u8] = b"LOGS_SYNCED";
+}
+
+/// Number of consecutive blocks sharing a log index entry.
+pub const LOG_INDEX_BUCKET: u64 = 1024;
+
+pub struct Backend<Block: BlockT> {
+	meta: Arc<MetaDb<Block>>,
+	traces: Arc<TraceDb<Block>>,
+	logs: Arc<LogDb<Block>>,
+}
+
+impl<Block: BlockT> Backend<Block> {
+	/// Extension columns of `frontier`, which must have been opened with
+	/// `columns::NUM_COLUMNS` extension columns.
+	pub fn new(frontier: &fc_db::Backend<Block>) -> Self {
+		Self::with_database(frontier.database().clone())
+	}
+
+	fn with_database(db: Arc<dyn Database<DbHash>>) -> Self {
+		Self {
+			meta: Arc::new(MetaDb {
+				db: db.clone(),
+				_marker: PhantomData,
+			}),
+			traces: Arc::new(TraceDb {
+				db: db.clone(),
+				_marker: PhantomData,
+			}),
+			logs: Arc::new(LogDb {
+				db: db.clone(),
+				_marker: PhantomData,
+			}),
+		}
+	}
+
+	pub fn meta(&self) -> &Arc<MetaDb<Block>> {
+		&self.meta
+	}
+
+	pub fn traces(&self) -> &Arc<TraceDb<Block>> {
+		&self.traces
+	}
+
+	pub fn logs(&self) -> &Arc<LogDb<Block>> {
+		&self.logs
+	}
+}
+
+fn load_decode<T: Decode>(db: &dyn Database<DbHash>, column: u32, key: &[u8]) -> Result<Option<T>, String> {
+	match db.get(column, key) {
+		Some(raw) => T::decode(&mut &raw[..])
+			.map(Some)
+			.map_err(|e| format!("Frontier DB is corrupted. Decode error: {}", e)),
+		None => Ok(None),
+	}
+}
+
+pub struct MetaDb<Block: BlockT> {
+	db: Arc<dyn Database<DbHash>>,
+	_marker: PhantomData<Block>,
+}
+
+impl<Block: BlockT> MetaDb<Block> {
+	/// Highest block number whose traces have been recorded.
+	pub fn traces_synced(&self) -> Result<Option<u64>, String> {
+		load_decode(&*self.db, columns::META, static_keys::TRACES_SYNCED)
+	}
+
+	/// Highest block number whose logs have been indexed.
+	pub fn logs_synced(&self) -> Result<Option<u64>, String> {
+		load_decode(&*self.db, columns::META, static_keys::LOGS_SYNCED)
+	}
+}
+
+/// Call traces of an Ethereum transaction.
+#[derive(Clone, Eq, PartialEq, Debug, Encode, Decode)]
+pub struct TransactionTraces {
+	pub transaction_hash: H256,
+	pub transaction_position: u32,
+	/// Calls in the order they were entered.
+	pub calls: Vec<Call>,
+}
+
+/// Call traces of all the Ethereum transactions of a canonical block.
+#[derive(Clone, Eq, PartialEq, Debug, Encode, Decode)]
+pub struct BlockTraces<Hash> {
+	/// Substrate block the traces were recorded from.
+	pub block_hash: Hash,
+	pub ethereum_block_hash: H256,
+	pub transactions: Vec<TransactionTraces>,
+}
+
+pub struct TraceDb<Block: BlockT> {
+	db: Arc<dyn Database<DbHash>>,
+	_marker: PhantomData<Block>,
+}
+
+impl<Block: BlockT> TraceDb<Block> {
+	/// Traces recorded for the canonical block at `number`.
+	pub fn block_traces(&self, number: u64) -> Result<Option<BlockTraces<Block::Hash>>, String> {
+		load_decode(&*self.db, columns::TRACES, &number.to_be_bytes())
+	}
+
+	/// Record the traces of the canonical block at `number`, replacing any retracted one.
+	pub fn write_block_traces(
+		&self,
+		number: u64,
+		traces: BlockTraces<Block::Hash>,
+	) -> Result<(), String> {
+		let mut transaction = sp_database::Transaction::new();
+
+		transaction.set(columns::TRACES, &number.to_be_bytes(), &traces.encode());
+
+		let synced = load_decode::<u64>(&*self.db, columns::META, static_keys::TRACES_SYNCED)?;
+		if synced.map_or(true, |synced| synced < number) {
+			transaction.set(columns::META, static_keys::TRACES_SYNCED, &number.encode());
+		}
+
+		self.db.commit(transaction).map_err(|e| format!("{:?}", e))?;
+
+		Ok(())
+	}
+}
+
+/// A log emitted by an Ethereum transaction.
+#[derive(Clone, Eq, PartialEq, Debug, Encode, Decode)]
+pub struct TransactionLog {
+	pub transaction_hash: H256,
+	pub transaction_index: u32,
+	/// Index of the log within its transaction.
+	pub transaction_log_index: u32,
+	/// Index of the log within its block.
+	pub log_index: u32,
+	pub log: Log,
+}
+
+/// Logs of all the Ethereum transactions of a canonical block.
+#[derive(Clone, Eq, PartialEq, Debug, Encode, Decode)]
+pub struct BlockLogs<Hash> {
+	/// Substrate block the logs were indexed from.
+	pub block_hash: Hash,
+	pub ethereum_block_hash: H256,
+	pub logs: Vec<TransactionLog>,
+}
+
+/// Key of the log index entry of all the logs in a bucket.
+fn any_log_key(bucket: u64) -> Vec<u8> {
+	let mut key = b"l".to_vec();
+	key.extend_from_slice(&bucket.to_be_bytes());
+	key
+}
+
+/// Key of the log index entry of the logs emitted by `address` in a bucket.
+fn address_key(address: &H160, bucket: u64) -> Vec<u8> {
+	let mut key = b"a".to_vec();
+	key.extend_from_slice(address.as_bytes());
+	key.extend_from_slice(&bucket.to_be_bytes());
+	key
+}
+
+/// Key of the log index entry of the logs with `topic` at `position` in a bucket.
+fn topic_key(position: u8, topic: &H256, bucket: u64) -> Vec<u8> {
+	let mut key = b"t".to_vec();
+	key.push(position);
+	key.extend_from_slice(topic.as_bytes());
+	key.extend_from_slice(&bucket.to_be_bytes());
+	key
+}
+
+/// Keys of the log index entries listing the block at `number`.
+fn index_keys<Hash>(number: u64, logs: &BlockLogs<Hash>) -> BTreeSet<Vec<u8>> {
+	let bucket = number / LOG_INDEX_BUCKET;
+	let mut keys = BTreeSet::new();
+	if !logs.logs.is_empty() {
+		keys.insert(any_log_key(bucket));
+	}
+	for TransactionLog { log, .. } in logs.logs.iter() {
+		keys.insert(address_key(&log.address, bucket));
+		for (position, topic) in log.topics.iter().enumerate().take(4) {
+			keys.insert(topic_key(position as u8, topic, bucket));
+		}
+	}
+	keys
+}
+
+/// Index of the blocks holding logs, by emitter address and positional topic.
+///
+/// Each index entry lists the numbers of the blocks of one `LOG_INDEX_BUCKET`
+/// holding a matching log. Blocks are indexed in order, so after a re-org the
+/// index is rewound to the last canonical block, and the entries of a retracted
+/// block are replaced when the canonical block at its number is indexed.
+pub struct LogDb<Block: BlockT> {
+	db: Arc<dyn Database<DbHash>>,
+	_marker: PhantomData<Block>,
+}
+
+impl<Block: BlockT> LogDb<Block> {
+	/// Logs indexed for the canonical block at `number`.
+	pub fn block_logs(&self, number: u64) -> Result<Option<BlockLogs<Block::Hash>>, String> {
+		load_decode(&*self.db, columns::LOGS, &number.to_be_bytes())
+	}
+
+	/// Index the logs of the canonical block at `number`, replacing any retracted
+	/// one, and mark the index synced up to `number`.
+	pub fn write_block_logs(
+		&self,
+		number: u64,
+		logs: BlockLogs<Block::Hash>,
+	) -> Result<(), String> {
+		let keys = index_keys(number, &logs);
+		let retracted_keys = match self.block_logs(number)? {
+			Some(retracted) => index_keys(number, &retracted),
+			None => BTreeSet::new(),
+		};
+
+		let mut transaction = sp_database::Transaction::new();
+
+		transaction.set(columns::LOGS, &number.to_be_bytes(), &logs.encode());
+
+		for key in retracted_keys.difference(&keys) {
+			let mut numbers = load_decode::<Vec<u64>>(&*self.db, columns::LOG_INDEX, key)?
+				.unwrap_or_default();
+			if let Ok(position) = numbers.binary_search(&number) {
+				numbers.remove(position);
+				if numbers.is_empty() {
+					transaction.remove(columns::LOG_INDEX, key);
+				} else {
+					transaction.set(columns::LOG_INDEX, key, &numbers.encode());
+				}
+			}
+		}
+
+		for key in keys.difference(&retracted_keys) {
+			let mut numbers = load_decode::<Vec<u64>>(&*self.db, columns::LOG_INDEX, key)?
+				.unwrap_or_default();
+			if let Err(position) = numbers.binary_search(&number) {
+				numbers.insert(position, number);
+				transaction.set(columns::LOG_INDEX, key, &numbers.encode());
+			}
+		}
+
+		transaction.set(columns::META, static_keys::LOGS_SYNCED, &number.encode());
+
+		self.db.commit(transaction).map_err(|e| format!("{:?}", e))?;
+
+		Ok(())
+	}
+
+	/// Highest block up to which the index only holds canonical blocks, going
+	/// back at most `max_depth` blocks from the synced one.
+	///
+	/// `canon_hash` returns the hash of the canonical block at a number. Indexed
+	/// blocks past the returned one were retracted and must not be trusted.
+	pub fn canonical_synced(
+		&self,
+		canon_hash: impl Fn(u64) -> Result<Option<Block::Hash>, String>,
+		max_depth: u64,
+	) -> Result<Option<u64>, String> {
+		let mut synced = match load_decode::<u64>(&*self.db, columns::META, static_keys::LOGS_SYNCED)? {
+			Some(synced) => synced,
+			None => return Ok(None),
+		};
+
+		for _ in 0..max_depth {
+			match self.block_logs(synced)? {
+				Some(logs) if Some(logs.block_hash) != canon_hash(synced)? => {
+					if synced == 0 {
+						return Ok(None);
+					}
+					synced -= 1;
+				},
+				_ => break,
+			}
+		}
+
+		Ok(Some(synced))
+	}
+
+	/// Numbers of the blocks in `from..=to` that may hold a log emitted by one
+	/// of `addresses` and carrying, at each position, one of `topics`.
+	///
+	/// An empty set of addresses or topics matches anything.
+	pub fn candidate_blocks(
+		&self,
+		from: u64,
+		to: u64,
+		addresses: &[H160],
+		topics: &[Vec<H256>],
+	) -> Result<Vec<u64>, String> {
+		let mut candidates = Vec::new();
+
+		for bucket in (from / LOG_INDEX_BUCKET)..=(to / LOG_INDEX_BUCKET) {
+			let mut groups = Vec::new();
+			if !addresses.is_empty() {
+				groups.push(addresses.iter().map(|address| address_key(address, bucket)).collect::<Vec<_>>());
+			}
+			for (position, topics) in topics.iter().enumerate().take(4) {
+				if !topics.is_empty() {
+					groups.push(topics.iter().map(|topic| topic_key(position as u8, topic, bucket)).collect());
+				}
+			}
+			if groups.is_empty() {
+				groups.push(vec![any_log_key(bucket)]);
+			}
+
+			let mut matching: Option<BTreeSet<u64>> = None;
+			for keys in groups {
+				let mut numbers = BTreeSet::new();
+				for key in keys {
+					numbers.extend(
+						load_decode::<Vec<u64>>(&*self.db, columns::LOG_INDEX, &key)?.unwrap_or_default()
+					);
+				}
+				matching = Some(match matching {
+					Some(matching) => matching.intersection(&numbers).cloned().collect(),
+					None => numbers,
+				});
+			}
+
+			candidates.extend(
+				matching.unwrap_or_default().into_iter().filter(|number| *number >= from && *number <= to)
+			);
+		}
+
+		Ok(candidates)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_runtime::testing::{Block as RawBlock, ExtrinsicWrapper};
+
+	type Block = RawBlock<ExtrinsicWrapper<u64>>;
+
+	fn backend() -> Backend<Block> {
+		Backend::with_database(Arc::new(sp_database::MemDb::new()))
+	}
+
+	fn hash(seed: u8) -> H256 {
+		H256::repeat_byte(seed)
+	}
+
+	fn block_logs(block_hash: H256, logs: Vec<(H160, Vec<H256>)>) -> BlockLogs<H256> {
+		BlockLogs {
+			block_hash,
+			ethereum_block_hash: H256::zero(),
+			logs: logs.into_iter().enumerate().map(|(index, (address, topics))| TransactionLog {
+				transaction_hash: H256::zero(),
+				transaction_index: 0,
+				transaction_log_index: index as u32,
+				log_index: index as u32,
+				log: Log { address, topics, data: Vec::new() },
+			}).collect(),
+		}
+	}
+
+	#[test]
+	fn candidate_blocks_follow_buckets_and_filters() {
+		let backend = backend();
+		let (a, b) = (H160::repeat_byte(0xa), H160::repeat_byte(0xb));
+		let (x, y) = (hash(0x11), hash(0x22));
+
+		backend.logs().write_block_logs(5, block_logs(hash(5), vec![(a, vec![x])])).unwrap();
+		backend.logs().write_block_logs(6, block_logs(hash(6), vec![])).unwrap();
+		backend.logs().write_block_logs(LOG_INDEX_BUCKET + 1, block_logs(hash(7), vec![(b, vec![y, x])])).unwrap();
+		backend.logs().write_block_logs(2 * LOG_INDEX_BUCKET, block_logs(hash(8), vec![(a, vec![y])])).unwrap();
+
+		let to = 2 * LOG_INDEX_BUCKET;
+		assert_eq!(
+			backend.logs().candidate_blocks(0, to, &[], &[]).unwrap(),
+			vec![5, LOG_INDEX_BUCKET + 1, 2 * LOG_INDEX_BUCKET],
+		);
+		assert_eq!(backend.logs().candidate_blocks(0, to, &[a], &[]).unwrap(), vec![5, 2 * LOG_INDEX_BUCKET]);
+		assert_eq!(backend.logs().candidate_blocks(0, to, &[], &[vec![x]]).unwrap(), vec![5]);
+		assert_eq!(
+			backend.logs().candidate_blocks(0, to, &[], &[vec![], vec![x]]).unwrap(),
+			vec![LOG_INDEX_BUCKET + 1],
+		);
+		assert_eq!(backend.logs().candidate_blocks(0, to, &[a, b], &[vec![y]]).unwrap(), vec![
+			LOG_INDEX_BUCKET + 1,
+			2 * LOG_INDEX_BUCKET,
+		]);
+		assert_eq!(backend.logs().candidate_blocks(0, to, &[b], &[vec![x]]).unwrap(), Vec::<u64>::new());
+		// Blocks of a bucket outside the range are left out.
+		assert_eq!(backend.logs().candidate_blocks(6, LOG_INDEX_BUCKET + 1, &[], &[]).unwrap(), vec![
+			LOG_INDEX_BUCKET + 1,
+		]);
+	}
+
+	#[test]
+	fn rewriting_a_block_replaces_its_index_entries() {
+		let backend = backend();
+		let (a, b) = (H160::repeat_byte(0xa), H160::repeat_byte(0xb));
+
+		backend.logs().write_block_logs(3, block_logs(hash(1), vec![(a, vec![hash(0x11)])])).unwrap();
+		backend.logs().write_block_logs(3, block_logs(hash(2), vec![(b, vec![hash(0x22)])])).unwrap();
+
+		assert_eq!(backend.logs().candidate_blocks(0, 10, &[a], &[]).unwrap(), Vec::<u64>::new());
+		assert_eq!(backend.logs().candidate_blocks(0, 10, &[], &[vec![hash(0x11)]]).unwrap(), Vec::<u64>::new());
+		assert_eq!(backend.logs().candidate_blocks(0, 10, &[b], &[vec![hash(0x22)]]).unwrap(), vec![3]);
+
+		backend.logs().write_block_logs(3, block_logs(hash(3), vec![])).unwrap();
+		assert_eq!(backend.logs().candidate_blocks(0, 10, &[], &[]).unwrap(), Vec::<u64>::new());
+	}
+
+	#[test]
+	fn canonical_synced_rewinds_past_retracted_blocks() {
+		let backend = backend();
+		let retracted = |number: u64| hash(0x80 + number as u8);
+		let canonical = |number: u64| hash(number as u8);
+
+		for number in 0..=3 {
+			backend.logs().write_block_logs(number, block_logs(canonical(number), vec![])).unwrap();
+		}
+		for number in 4..=6 {
+			backend.logs().write_block_logs(number, block_logs(retracted(number), vec![])).unwrap();
+		}
+		assert_eq!(backend.meta().logs_synced().unwrap(), Some(6));
+
+		// The chain switched to a fork from block #3.
+		let canon_hash = |number: u64| Ok(Some(canonical(number)));
+		assert_eq!(backend.logs().canonical_synced(canon_hash, 256).unwrap(), Some(3));
+		assert_eq!(backend.logs().canonical_synced(canon_hash, 2).unwrap(), Some(4));
+
+		// Indexing the new block #4 rewinds the index to it.
+		backend.logs().write_block_logs(4, block_logs(canonical(4), vec![])).unwrap();
+		assert_eq!(backend.meta().logs_synced().unwrap(), Some(4));
+		assert_eq!(backend.logs().canonical_synced(canon_hash, 256).unwrap(), Some(4));
+
+		// A shorter chain ending at #2.
+		let canon_hash = |number: u64| Ok(if number <= 2 { Some(canonical(number)) } else { None });
+		assert_eq!(backend.logs().canonical_synced(canon_hash, 256).unwrap(), Some(2));
+	}
+
+	#[test]
+	fn new_canonical_block_is_a_candidate_after_reorg() {
+		let backend = backend();
+		let a = H160::repeat_byte(0xa);
+
+		backend.logs().write_block_logs(0, block_logs(hash(0), vec![])).unwrap();
+		backend.logs().write_block_logs(1, block_logs(hash(0x81), vec![])).unwrap();
+
+		// The retracted #1 had no log, the new canonical #1 has one.
+		let synced = backend.logs().canonical_synced(|number| Ok(Some(hash(number as u8))), 256).unwrap();
+		assert_eq!(synced, Some(0));
+
+		backend.logs().write_block_logs(1, block_logs(hash(1), vec![(a, vec![])])).unwrap();
+		assert_eq!(backend.logs().candidate_blocks(0, 1, &[a], &[]).unwrap(), vec![1]);
+	}
+}
@@ -1,13 +1,15 @@
-use sp_core::{Pair, Public, sr25519, U256, H160, crypto::UncheckedInto,};
+use sp_core::{Pair, Public, sr25519, U256, H160, crypto::{UncheckedInto, key_types}};
 use mathchain_runtime::{
 	AccountId, AuraConfig, BalancesConfig, EVMConfig, EthereumConfig, GenesisConfig, GrandpaConfig,
-	SudoConfig, SystemConfig, WASM_BINARY, Signature, ValidatorSetConfig, opaque::SessionKeys, SessionConfig,
-	SecretStoreConfig
+	SudoConfig, SystemConfig, WASM_BINARY, Signature, StakingConfig, StakerStatus, opaque::SessionKeys, SessionConfig,
+	SecretStoreConfig, Perbill, ImOnlineConfig, AuthorityDiscoveryConfig, placeholder_session_key,
 };
-use mathchain_runtime::constants::currency::MATHS as MATH;
+use mathchain_runtime::constants::{currency::MATHS as MATH, staking::VALIDATOR_BOND};
 
 use sp_consensus_aura::sr25519::AuthorityId as AuraId;
 use sp_finality_grandpa::AuthorityId as GrandpaId;
+use pallet_im_online::sr25519::AuthorityId as ImOnlineId;
+use sp_authority_discovery::AuthorityId as AuthorityDiscoveryId;
 use sp_runtime::traits::{Verify, IdentifyAccount};
 use sc_service::{ChainType, Properties};
 use std::collections::BTreeMap;
@@ -84,19 +86,25 @@ pub fn get_account_id_from_seed<TPublic: Public>(seed: &str) -> AccountId where
 fn session_keys(
 	aura: AuraId,
 	grandpa: GrandpaId,
+	im_online: ImOnlineId,
+	authority_discovery: AuthorityDiscoveryId,
 ) -> SessionKeys {
-	SessionKeys { aura, grandpa }
+	SessionKeys { aura, grandpa, im_online, authority_discovery }
 }
 
 pub fn get_authority_keys_from_seed(seed: &str) -> (
 	AccountId,
 	AuraId,
-	GrandpaId
+	GrandpaId,
+	ImOnlineId,
+	AuthorityDiscoveryId,
 ) {
 	(
 		get_account_id_from_seed::<sr25519::Public>(seed),
 		get_from_seed::<AuraId>(seed),
-		get_from_seed::<GrandpaId>(seed)
+		get_from_seed::<GrandpaId>(seed),
+		get_from_seed::<ImOnlineId>(seed),
+		get_from_seed::<AuthorityDiscoveryId>(seed),
 	)
 }
 
@@ -121,19 +129,27 @@ pub fn galois_for_genesis() -> Result<ChainSpec, String> {
 	const GENESIS_VALIDATOR_ED3: &'static str =
 		"0xf350c893e43dafe5d0e1c572673666b3d414057c0d117b476fcac5f777e627f2";
 
+	// The validators set their own `im_online` and `authority_discovery` keys with
+	// `session.set_keys`. Until all of them have, no one is reported unresponsive.
 	let genesis_validator1: (
 		AccountId,
 		AuraId,
 		GrandpaId,
+		ImOnlineId,
+		AuthorityDiscoveryId,
 	) = {
 		let stash = array_bytes::hex_str_array_unchecked!(GENESIS_VALIDATOR_SR1, 32);
 		let session = array_bytes::hex_str_array_unchecked!(GENESIS_VALIDATOR_SR1, 32);
 		let grandpa = array_bytes::hex_str_array_unchecked!(GENESIS_VALIDATOR_ED1, 32);
 
+		let stash: AccountId = stash.into();
+
 		(
-			stash.into(),
+			stash.clone(),
 			session.unchecked_into(),
 			grandpa.unchecked_into(),
+			placeholder_session_key(key_types::IM_ONLINE, &stash).into(),
+			placeholder_session_key(key_types::AUTHORITY_DISCOVERY, &stash).into(),
 		)
 	};
 
@@ -141,15 +157,21 @@ pub fn galois_for_genesis() -> Result<ChainSpec, String> {
 		AccountId,
 		AuraId,
 		GrandpaId,
+		ImOnlineId,
+		AuthorityDiscoveryId,
 	) = {
 		let stash = array_bytes::hex_str_array_unchecked!(GENESIS_VALIDATOR_SR2, 32);
 		let session = array_bytes::hex_str_array_unchecked!(GENESIS_VALIDATOR_SR2, 32);
 		let grandpa = array_bytes::hex_str_array_unchecked!(GENESIS_VALIDATOR_ED2, 32);
 
+		let stash: AccountId = stash.into();
+
 		(
-			stash.into(),
+			stash.clone(),
 			session.unchecked_into(),
 			grandpa.unchecked_into(),
+			placeholder_session_key(key_types::IM_ONLINE, &stash).into(),
+			placeholder_session_key(key_types::AUTHORITY_DISCOVERY, &stash).into(),
 		)
 	};
 
@@ -157,15 +179,21 @@ pub fn galois_for_genesis() -> Result<ChainSpec, String> {
 		AccountId,
 		AuraId,
 		GrandpaId,
+		ImOnlineId,
+		AuthorityDiscoveryId,
 	) = {
 		let stash = array_bytes::hex_str_array_unchecked!(GENESIS_VALIDATOR_SR3, 32);
 		let session = array_bytes::hex_str_array_unchecked!(GENESIS_VALIDATOR_SR3, 32);
 		let grandpa = array_bytes::hex_str_array_unchecked!(GENESIS_VALIDATOR_ED3, 32);
 
+		let stash: AccountId = stash.into();
+
 		(
-			stash.into(),
+			stash.clone(),
 			session.unchecked_into(),
 			grandpa.unchecked_into(),
+			placeholder_session_key(key_types::IM_ONLINE, &stash).into(),
+			placeholder_session_key(key_types::AUTHORITY_DISCOVERY, &stash).into(),
 		)
 	};
 
@@ -196,7 +224,7 @@ pub fn galois_for_genesis() -> Result<ChainSpec, String> {
 		ChainType::Live,
 		move || testnet_genesis(
 			wasm_binary,
-			// Initial Poa authorities
+			// Initial validators
 			vec![
 				genesis_validator1.clone(),
 				genesis_validator2.clone(),
@@ -250,7 +278,7 @@ pub fn development_config() -> Result<ChainSpec, String> {
 		ChainType::Development,
 		move || testnet_genesis(
 			wasm_binary,
-			// Initial PoA authorities
+			// Initial validators
 			vec![
 				get_authority_keys_from_seed("Alice"),
 			],
@@ -310,7 +338,7 @@ pub fn local_testnet_config() -> Result<ChainSpec, String> {
 		ChainType::Local,
 		move || testnet_genesis(
 			wasm_binary,
-			// Initial PoA authorities
+			// Initial validators
 			vec![
 				get_authority_keys_from_seed("Alice"),
 				get_authority_keys_from_seed("Bob"),
@@ -351,7 +379,7 @@ pub fn local_testnet_config() -> Result<ChainSpec, String> {
 /// Configure initial storage state for FRAME modules.
 fn testnet_genesis(
 	wasm_binary: &[u8],
-	initial_authorities: Vec<(AccountId, AuraId, GrandpaId)>,
+	initial_authorities: Vec<(AccountId, AuraId, GrandpaId, ImOnlineId, AuthorityDiscoveryId)>,
 	root_key: AccountId,
 	endowed_accounts: Vec<AccountId>,
 	key_servers: Vec<(H160, AccountId, &[u8])>,
@@ -393,14 +421,27 @@ fn testnet_genesis(
 			accounts: evm_accounts,
 		},
 		pallet_ethereum: EthereumConfig {},
-		pallet_validator_set: ValidatorSetConfig {
-			validators: initial_authorities.iter().map(|x| x.0.clone()).collect::<Vec<_>>(),
+		pallet_staking: StakingConfig {
+			validator_count: initial_authorities.len() as u32,
+			minimum_validator_count: initial_authorities.len() as u32,
+			stakers: initial_authorities.iter().map(|x| {
+				(x.0.clone(), x.0.clone(), VALIDATOR_BOND, StakerStatus::Validator)
+			}).collect(),
+			invulnerables: initial_authorities.iter().map(|x| x.0.clone()).collect(),
+			slash_reward_fraction: Perbill::from_percent(10),
+			..Default::default()
 		},
 		pallet_session: SessionConfig {
 			keys: initial_authorities.iter().map(|x| {
-				(x.0.clone(), x.0.clone(), session_keys(x.1.clone(), x.2.clone()))
+				(x.0.clone(), x.0.clone(), session_keys(x.1.clone(), x.2.clone(), x.3.clone(), x.4.clone()))
 			}).collect::<Vec<_>>(),
 		},
+		pallet_im_online: ImOnlineConfig {
+			keys: vec![],
+		},
+		pallet_authority_discovery: AuthorityDiscoveryConfig {
+			keys: vec![],
+		},
 		secretstore_runtime_module: SecretStoreConfig {
 			owner: get_account_id_from_seed::<sr25519::Public>("Alice"),
 			is_initialization_completed: true,
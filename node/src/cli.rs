@@ -1,3 +1,4 @@
+use std::path::PathBuf;
 use structopt::{StructOpt, clap::arg_enum};
 
 arg_enum! {
@@ -12,6 +13,31 @@ arg_enum! {
 	}
 }
 
+arg_enum! {
+	/// Available Frontier DB backends.
+	#[allow(missing_docs)]
+	#[derive(Debug, Copy, Clone, PartialEq, StructOpt)]
+	pub enum FrontierDatabase {
+		RocksDb,
+		ParityDb,
+	}
+}
+
+#[derive(Debug, Clone, StructOpt)]
+pub struct FrontierDbParams {
+	/// Database backend of the Frontier DB, following `--database` by default.
+	#[structopt(
+		long = "frontier-database",
+		possible_values = &FrontierDatabase::variants(),
+		case_insensitive = true,
+	)]
+	pub database: Option<FrontierDatabase>,
+
+	/// Cache size of the Frontier DB, in MiB.
+	#[structopt(long = "frontier-db-cache-size", default_value = "0")]
+	pub cache_size: usize,
+}
+
 #[allow(missing_docs)]
 #[derive(Debug, StructOpt)]
 pub struct RunCmd {
@@ -25,6 +51,53 @@ pub struct RunCmd {
 
 	#[structopt(long = "enable-dev-signer")]
 	pub enable_dev_signer: bool,
+
+	/// Use the `eths` keys of the keystore as Ethereum accounts, on interfaces allowing unsafe RPC calls.
+	#[structopt(long = "enable-keystore-signer")]
+	pub enable_keystore_signer: bool,
+
+	/// Record the call traces of every block to serve `trace_filter` and `trace_block`.
+	#[structopt(long = "enable-trace-filter")]
+	pub enable_trace_filter: bool,
+
+	/// Maximum number of blocks an `eth_getLogs` query may span.
+	#[structopt(long = "max-logs-range", default_value = "10000")]
+	pub max_logs_range: u64,
+
+	/// Maximum number of logs an `eth_getLogs` query may return.
+	#[structopt(long = "max-logs-results", default_value = "10000")]
+	pub max_logs_results: usize,
+
+	/// Maximum number of blocks a `trace_filter` query may span.
+	#[structopt(long = "max-trace-range", default_value = "1000")]
+	pub max_trace_range: u64,
+
+	/// Maximum number of traces a `trace_filter` query may return.
+	#[structopt(long = "max-trace-results", default_value = "1000")]
+	pub max_trace_results: usize,
+
+	/// Maximum size of an HTTP or WebSocket RPC request, batches included, in MiB.
+	#[structopt(long = "rpc-max-request-size", default_value = "15")]
+	pub rpc_max_request_size: usize,
+
+	/// Maximum number of filters `eth_newFilter` may keep at once.
+	#[structopt(long = "max-stored-filters", default_value = "500")]
+	pub max_stored_filters: usize,
+
+	/// Number of blocks an unused filter is kept for.
+	#[structopt(long = "filter-retain-threshold", default_value = "100")]
+	pub filter_retain_threshold: u64,
+
+	/// Number of blocks a pending Ethereum transaction is kept for.
+	#[structopt(long = "transaction-retain-threshold", default_value = "5")]
+	pub transaction_retain_threshold: u64,
+
+	/// Gas limit `eth_call` is capped at.
+	#[structopt(long = "rpc-gas-cap", default_value = "25000000")]
+	pub rpc_gas_cap: u64,
+
+	#[structopt(flatten)]
+	pub frontier_db: FrontierDbParams,
 }
 
 #[derive(Debug, StructOpt)]
@@ -65,4 +138,81 @@ pub enum Subcommand {
 
 	/// Key management cli utilities
 	Key(sc_cli::KeySubcommand),
+
+	/// Maintain the Frontier DB.
+	FrontierDb(FrontierDbCmd),
+
+	/// Try the runtime upgrade and a few blocks on a state snapshot.
+	TryRuntime(TryRuntimeCmd),
+}
+
+#[derive(Debug, StructOpt)]
+pub enum FrontierDbAction {
+	/// Check the Ethereum mappings of canonical blocks against their digests.
+	Check {
+		/// First block to check, the genesis by default.
+		#[structopt(long = "from")]
+		from: Option<u64>,
+		/// Last block to check, the best block by default.
+		#[structopt(long = "to")]
+		to: Option<u64>,
+	},
+
+	/// Rebuild the Ethereum mappings of canonical blocks from their digests.
+	Reindex {
+		/// First block to reindex.
+		#[structopt(long = "from")]
+		from: u64,
+		/// Last block to reindex, the best block by default.
+		#[structopt(long = "to")]
+		to: Option<u64>,
+	},
+
+	/// Remove the Frontier DB, to be rebuilt by the mapping sync on the next start.
+	Purge {
+		/// Skip interactive prompt by answering yes automatically.
+		#[structopt(short = "y")]
+		yes: bool,
+	},
+
+	/// Copy the RocksDB Frontier DB into ParityDB, for `--frontier-database paritydb`.
+	Migrate,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct FrontierDbCmd {
+	#[structopt(subcommand)]
+	pub action: FrontierDbAction,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub shared_params: sc_cli::SharedParams,
+}
+
+impl sc_cli::CliConfiguration for FrontierDbCmd {
+	fn shared_params(&self) -> &sc_cli::SharedParams {
+		&self.shared_params
+	}
+}
+
+#[derive(Debug, StructOpt)]
+pub struct TryRuntimeCmd {
+	/// State snapshot to start from, as written by `remote-externalities`.
+	#[structopt(long = "snapshot", parse(from_os_str))]
+	pub snapshot: PathBuf,
+
+	/// Blocks to execute after the upgrade, from `export-blocks --binary`. They start
+	/// with the block following the snapshot.
+	#[structopt(long = "blocks", parse(from_os_str))]
+	pub blocks: Option<PathBuf>,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub shared_params: sc_cli::SharedParams,
+}
+
+impl sc_cli::CliConfiguration for TryRuntimeCmd {
+	fn shared_params(&self) -> &sc_cli::SharedParams {
+		&self.shared_params
+	}
 }
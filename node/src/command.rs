@@ -15,14 +15,15 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::{chain_spec, service};
-use crate::cli::{Cli, Subcommand};
+use crate::{chain_spec, service, frontier_db};
+use crate::cli::{Cli, Subcommand, FrontierDbAction};
 use sc_cli::{SubstrateCli, RuntimeVersion, Role, ChainSpec};
 use sc_service::PartialComponents;
 use mathchain_runtime::Block;
 use sp_core::crypto::Ss58AddressFormat;
 use service::IdentifyVariant;
 use crate::service::new_partial;
+use crate::rpc::RpcConfig;
 
 use galois_runtime_config::CHAIN_ID as GaoloisChainId;
 use mathchain_runtime_config::CHAIN_ID as MathchainChainId;
@@ -95,7 +96,7 @@ pub fn run() -> sc_cli::Result<()> {
 			let runner = cli.create_runner(cmd)?;
 			runner.async_run(|config| {
 				let PartialComponents { client, task_manager, import_queue, ..}
-					= new_partial(&config, cli.run.sealing)?;
+					= new_partial(&config, cli.run.sealing, &cli.run.frontier_db)?;
 				Ok((cmd.run(client, import_queue), task_manager))
 			})
 		},
@@ -103,7 +104,7 @@ pub fn run() -> sc_cli::Result<()> {
 			let runner = cli.create_runner(cmd)?;
 			runner.async_run(|config| {
 				let PartialComponents { client, task_manager, ..}
-					= new_partial(&config, cli.run.sealing)?;
+					= new_partial(&config, cli.run.sealing, &cli.run.frontier_db)?;
 				Ok((cmd.run(client, config.database), task_manager))
 			})
 		},
@@ -111,7 +112,7 @@ pub fn run() -> sc_cli::Result<()> {
 			let runner = cli.create_runner(cmd)?;
 			runner.async_run(|config| {
 				let PartialComponents { client, task_manager, ..}
-					= new_partial(&config, cli.run.sealing)?;
+					= new_partial(&config, cli.run.sealing, &cli.run.frontier_db)?;
 				Ok((cmd.run(client, config.chain_spec), task_manager))
 			})
 		},
@@ -119,7 +120,7 @@ pub fn run() -> sc_cli::Result<()> {
 			let runner = cli.create_runner(cmd)?;
 			runner.async_run(|config| {
 				let PartialComponents { client, task_manager, import_queue, ..}
-					= new_partial(&config, cli.run.sealing)?;
+					= new_partial(&config, cli.run.sealing, &cli.run.frontier_db)?;
 				Ok((cmd.run(client, import_queue), task_manager))
 			})
 		},
@@ -131,11 +132,36 @@ pub fn run() -> sc_cli::Result<()> {
 			let runner = cli.create_runner(cmd)?;
 			runner.async_run(|config| {
 				let PartialComponents { client, task_manager, backend, ..}
-					= new_partial(&config, cli.run.sealing)?;
+					= new_partial(&config, cli.run.sealing, &cli.run.frontier_db)?;
 				Ok((cmd.run(client, backend), task_manager))
 			})
 		},
 		Some(Subcommand::Key(cmd)) => Ok(cmd.run(&cli)?),
+		Some(Subcommand::FrontierDb(cmd)) => {
+			let runner = cli.create_runner(cmd)?;
+			match cmd.action {
+				FrontierDbAction::Check { from, to } => runner.async_run(|config| {
+					let PartialComponents { client, task_manager, other, ..}
+						= new_partial(&config, cli.run.sealing, &cli.run.frontier_db)?;
+					let frontier_backend = other.4;
+					Ok((async move { frontier_db::check(&*client, &frontier_backend, from, to) }, task_manager))
+				}),
+				FrontierDbAction::Reindex { from, to } => runner.async_run(|config| {
+					let PartialComponents { client, task_manager, other, ..}
+						= new_partial(&config, cli.run.sealing, &cli.run.frontier_db)?;
+					let frontier_backend = other.4;
+					Ok((async move { frontier_db::reindex(&*client, &frontier_backend, from, to) }, task_manager))
+				}),
+				FrontierDbAction::Purge { yes } => runner.sync_run(|config| {
+					frontier_db::purge(&service::frontier_database_dir(&config), yes)
+				}),
+				FrontierDbAction::Migrate => runner.sync_run(|config| {
+					let copied = service::migrate_frontier_db(&config).map_err(sc_cli::Error::Input)?;
+					log::info!("Copied {} entries into the ParityDB Frontier DB", copied);
+					Ok(())
+				}),
+			}
+		},
 		Some(Subcommand::Benchmark(cmd)) => {
 			if cfg!(feature = "runtime-benchmarks") {
 				let runner = cli.create_runner(cmd)?;
@@ -146,14 +172,52 @@ pub fn run() -> sc_cli::Result<()> {
 				You can enable it with `--features runtime-benchmarks`.".into())
 			}
 		},
+		#[cfg(feature = "try-runtime")]
+		Some(Subcommand::TryRuntime(cmd)) => {
+			let runner = cli.create_runner(cmd)?;
+			runner.async_run(|config| {
+				let registry = config.prometheus_config.as_ref().map(|cfg| &cfg.registry);
+				let task_manager = sc_service::TaskManager::new(config.task_executor.clone(), registry)
+					.map_err(|e| sc_cli::Error::Service(sc_service::Error::Prometheus(e)))?;
+				Ok((crate::try_runtime::run(config, cmd.snapshot.clone(), cmd.blocks.clone()), task_manager))
+			})
+		},
+		#[cfg(not(feature = "try-runtime"))]
+		Some(Subcommand::TryRuntime(_)) => {
+			Err("try-runtime wasn't enabled when building the node. \
+			You can enable it with `--features try-runtime`.".into())
+		},
 		None => {
+			if cli.run.enable_trace_filter && !cfg!(feature = "evm-tracing") {
+				return Err("--enable-trace-filter needs the runtime tracing of the node. \
+				You can enable it with `--features evm-tracing`.".into())
+			}
+
 			let runner = cli.create_runner(&cli.run.base)?;
 			set_default_ss58_version(&runner.config().chain_spec);
 
 			runner.run_node_until_exit(|config| async move {
 				match config.role {
 					Role::Light => service::new_light(config),
-					_ => service::new_full(config, cli.run.sealing, cli.run.enable_dev_signer),
+					_ => service::new_full(
+						config,
+						cli.run.sealing,
+						cli.run.enable_dev_signer,
+						cli.run.frontier_db.clone(),
+						RpcConfig {
+							enable_keystore_signer: cli.run.enable_keystore_signer,
+							enable_trace_filter: cli.run.enable_trace_filter,
+							max_logs_range: cli.run.max_logs_range,
+							max_logs_results: cli.run.max_logs_results,
+							max_trace_range: cli.run.max_trace_range,
+							max_trace_results: cli.run.max_trace_results,
+							max_request_size: cli.run.rpc_max_request_size,
+							max_stored_filters: cli.run.max_stored_filters,
+							filter_retain_threshold: cli.run.filter_retain_threshold,
+							transaction_retain_threshold: cli.run.transaction_retain_threshold,
+							rpc_gas_cap: cli.run.rpc_gas_cap,
+						},
+					),
 				}.map_err(sc_cli::Error::Service)
 			})
 		}
@@ -0,0 +1,152 @@
+//! `frontier-db` maintenance subcommand.
+//!
+//! The Ethereum block and transaction mappings of the Frontier DB are derived
+//! from the `ConsensusLog` digest of each Substrate header, so they can be
+//! checked and rebuilt from the Substrate DB alone.
+
+use std::{fs, io::{self, Write}, path::Path};
+use sp_blockchain::HeaderBackend;
+use sp_runtime::{generic::BlockId, traits::{Block as BlockT, Header as HeaderT, UniqueSaturatedInto}};
+use mathchain_consensus::find_mathchain_log;
+use mathchain_consensus_primitives::ConsensusLog;
+use mathchain_runtime::opaque::Block;
+
+/// Blocks in `from..=to`, bounded by the best block. The genesis block carries
+/// no digest, its mapping is written by the mapping sync worker.
+fn block_range<C: HeaderBackend<Block>>(client: &C, from: Option<u64>, to: Option<u64>) -> (u64, u64) {
+	let best = UniqueSaturatedInto::<u64>::unique_saturated_into(client.info().best_number);
+	(from.unwrap_or(1).max(1), to.unwrap_or(best).min(best))
+}
+
+fn canonical_header<C: HeaderBackend<Block>>(
+	client: &C,
+	number: u64,
+) -> sc_cli::Result<<Block as BlockT>::Header> {
+	client.header(BlockId::Number(number.unique_saturated_into()))?
+		.ok_or_else(|| sc_cli::Error::Input(format!("Block #{} not found", number)))
+}
+
+/// Check the mappings of the canonical blocks in `from..=to`.
+pub fn check<C: HeaderBackend<Block>>(
+	client: &C,
+	frontier_backend: &fc_db::Backend<Block>,
+	from: Option<u64>,
+	to: Option<u64>,
+) -> sc_cli::Result<()> {
+	let (from, to) = block_range(client, from, to);
+	let mut problems = 0;
+
+	for number in from..=to {
+		let header = canonical_header(client, number)?;
+		let hash = header.hash();
+		let mut problem = |message: String| {
+			log::warn!("Block #{} ({}): {}", number, hash, message);
+			problems += 1;
+		};
+
+		let (ethereum_block_hash, transaction_hashes) = match find_mathchain_log::<Block>(&header) {
+			Ok(ConsensusLog::EndBlock { block_hash, transaction_hashes }) => (block_hash, transaction_hashes),
+			Err(err) => {
+				problem(format!("{}", err));
+				continue;
+			},
+		};
+
+		match frontier_backend.mapping().block_hash(&ethereum_block_hash) {
+			Ok(Some(mapped)) if mapped == hash => (),
+			Ok(mapped) => problem(format!(
+				"Ethereum block {:?} is mapped to {:?}", ethereum_block_hash, mapped,
+			)),
+			Err(err) => problem(err),
+		}
+
+		for (index, transaction_hash) in transaction_hashes.into_iter().enumerate() {
+			match frontier_backend.mapping().transaction_metadata(&transaction_hash) {
+				Ok(metadata) if metadata.iter().any(|metadata|
+					metadata.block_hash == hash && metadata.ethereum_index == index as u32
+				) => (),
+				Ok(_) => problem(format!("Ethereum transaction {:?} is not mapped", transaction_hash)),
+				Err(err) => problem(err),
+			}
+		}
+
+		match frontier_backend.mapping().is_synced(&hash) {
+			Ok(true) => (),
+			Ok(false) => problem("not marked as synced".into()),
+			Err(err) => problem(err),
+		}
+	}
+
+	if problems > 0 {
+		return Err(sc_cli::Error::Input(format!(
+			"{} problems found in blocks #{}..#{}, run `frontier-db reindex` on them",
+			problems, from, to,
+		)));
+	}
+
+	log::info!("Frontier DB mappings of blocks #{}..#{} are consistent", from, to);
+	Ok(())
+}
+
+/// Rewrite the mappings of the canonical blocks in `from..=to` from their digests.
+pub fn reindex<C: HeaderBackend<Block>>(
+	client: &C,
+	frontier_backend: &fc_db::Backend<Block>,
+	from: u64,
+	to: Option<u64>,
+) -> sc_cli::Result<()> {
+	let (from, to) = block_range(client, Some(from), to);
+
+	for number in from..=to {
+		let header = canonical_header(client, number)?;
+		let ConsensusLog::EndBlock { block_hash, transaction_hashes }
+			= find_mathchain_log::<Block>(&header).map_err(|err| {
+				sc_cli::Error::Input(format!("Block #{}: {}", number, err))
+			})?;
+
+		frontier_backend.mapping().write_hashes(fc_db::MappingCommitment {
+			block_hash: header.hash(),
+			ethereum_block_hash: block_hash,
+			ethereum_transaction_hashes: transaction_hashes,
+		}).map_err(sc_cli::Error::Input)?;
+
+		if number % 10_000 == 0 {
+			log::info!("Reindexed up to block #{}", number);
+		}
+	}
+
+	log::info!("Reindexed blocks #{}..#{}", from, to);
+	Ok(())
+}
+
+/// Remove the Frontier DB in `path`.
+pub fn purge(path: &Path, yes: bool) -> sc_cli::Result<()> {
+	if !yes {
+		print!("Are you sure to remove {:?}? [y/N]: ", path);
+		io::stdout().flush().expect("failed to flush stdout");
+
+		let mut input = String::new();
+		io::stdin().read_line(&mut input)?;
+		let input = input.trim();
+
+		match input.chars().nth(0) {
+			Some('y') | Some('Y') => {},
+			_ => {
+				println!("Aborted");
+				return Ok(())
+			},
+		}
+	}
+
+	match fs::remove_dir_all(path) {
+		Ok(_) => {
+			println!("{:?} removed.", path);
+			Ok(())
+		},
+		Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+			eprintln!("{:?} did not exist.", path);
+			Ok(())
+		},
+		Err(err) => Result::Err(err.into())
+	}
+}
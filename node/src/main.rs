@@ -6,7 +6,11 @@ mod chain_spec;
 mod service;
 mod cli;
 mod command;
+mod frontier_db;
+mod randomness;
 mod rpc;
+#[cfg(feature = "try-runtime")]
+mod try_runtime;
 
 fn main() -> sc_cli::Result<()> {
 	command::run()
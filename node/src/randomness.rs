@@ -0,0 +1,64 @@
+//! VRF outputs of the local Aura keys for `pallet_vrf_randomness`.
+//!
+//! The outputs are made over the randomness of the best block, which Aura authors
+//! the next block on. The runtime requires the output of the author in every block,
+//! so a block authored on another parent has no valid output and is not built.
+
+use std::{sync::Arc, marker::PhantomData};
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_core::crypto::key_types::AURA;
+use sp_inherents::{InherentData, InherentIdentifier, ProvideInherentData};
+use sp_keystore::{SyncCryptoStore, SyncCryptoStorePtr};
+use sp_runtime::{generic::BlockId, traits::{Block as BlockT, One, SaturatedConversion}};
+use sp_consensus_vrf::schnorrkel::{VRFOutput, VRFProof};
+use pallet_vrf_randomness::{INHERENT_IDENTIFIER, InherentType, VrfRandomnessApi, make_transcript_data};
+
+pub struct VrfInherentDataProvider<B, C> {
+	client: Arc<C>,
+	keystore: SyncCryptoStorePtr,
+	_marker: PhantomData<B>,
+}
+
+impl<B, C> VrfInherentDataProvider<B, C> {
+	pub fn new(client: Arc<C>, keystore: SyncCryptoStorePtr) -> Self {
+		Self { client, keystore, _marker: PhantomData }
+	}
+}
+
+impl<B, C> ProvideInherentData for VrfInherentDataProvider<B, C> where
+	B: BlockT,
+	C: ProvideRuntimeApi<B> + HeaderBackend<B> + Send + Sync,
+	C::Api: VrfRandomnessApi<B>,
+{
+	fn inherent_identifier(&self) -> &'static InherentIdentifier {
+		&INHERENT_IDENTIFIER
+	}
+
+	fn provide_inherent_data(
+		&self,
+		inherent_data: &mut InherentData,
+	) -> Result<(), sp_inherents::Error> {
+		let info = self.client.info();
+		let randomness = self.client.runtime_api()
+			.current_randomness(&BlockId::Hash(info.best_hash))
+			.map_err(|err| sp_inherents::Error::from(format!("{:?}", err)))?;
+		let number = (info.best_number + One::one()).saturated_into::<u64>();
+
+		let outputs: InherentType = SyncCryptoStore::sr25519_public_keys(&*self.keystore, AURA)
+			.iter()
+			.filter_map(|public| SyncCryptoStore::sr25519_vrf_sign(
+				&*self.keystore,
+				AURA,
+				public,
+				make_transcript_data(&randomness, number),
+			).ok().flatten())
+			.map(|signature| (VRFOutput(signature.output), VRFProof(signature.proof)))
+			.collect();
+		inherent_data.put_data(INHERENT_IDENTIFIER, &outputs)
+	}
+
+	fn error_to_string(&self, _error: &[u8]) -> Option<String> {
+		None
+	}
+}
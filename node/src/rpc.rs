@@ -1,25 +1,22 @@
 //! A collection of node-specific RPC methods.
 
 use std::sync::Arc;
-use std::collections::BTreeMap;
 use fc_rpc_core::types::{PendingTransactions, FilterPool};
 use sc_consensus_manual_seal::rpc::{ManualSeal, ManualSealApi};
-use mathchain_runtime::{Hash, AccountId, Index, opaque::Block, Balance};
+use mathchain_runtime::{Hash, AccountId, Index, opaque::Block, Balance, BlockNumber, AssetId, AssetBalance};
 use sp_api::ProvideRuntimeApi;
 use sp_transaction_pool::TransactionPool;
 use sp_blockchain::{Error as BlockChainError, HeaderMetadata, HeaderBackend};
 use sc_rpc_api::DenyUnsafe;
 use sc_client_api::{
 	backend::{StorageProvider, Backend, StateBackend, AuxStore},
-	client::BlockchainEvents
+	client::{BlockchainEvents, BlockBackend},
 };
 use sc_rpc::SubscriptionTaskExecutor;
 use sp_runtime::traits::BlakeTwo256;
 use sp_block_builder::BlockBuilder;
 use sc_network::NetworkService;
 use jsonrpc_pubsub::manager::SubscriptionManager;
-use pallet_ethereum::EthereumStorageSchema;
-use fc_rpc::{StorageOverride, SchemaV1Override};
 
 /// Light client extra dependencies.
 pub struct LightDeps<C, F, P> {
@@ -31,6 +28,35 @@ pub struct LightDeps<C, F, P> {
 	pub remote_blockchain: Arc<dyn sc_client_api::light::RemoteBlockchain<Block>>,
 	/// Fetcher instance.
 	pub fetcher: Arc<F>,
+	/// Network service
+	pub network: Arc<NetworkService<Block, Hash>>,
+}
+
+/// Ethereum RPC limits, as set on the command line.
+#[derive(Clone, Debug)]
+pub struct RpcConfig {
+	/// Whether the keystore `eths` keys are used as Ethereum accounts.
+	pub enable_keystore_signer: bool,
+	/// Whether call traces are recorded for `trace_filter`.
+	pub enable_trace_filter: bool,
+	/// Maximum number of blocks an `eth_getLogs` query may span.
+	pub max_logs_range: u64,
+	/// Maximum number of logs an `eth_getLogs` query may return.
+	pub max_logs_results: usize,
+	/// Maximum number of blocks a `trace_filter` query may span.
+	pub max_trace_range: u64,
+	/// Maximum number of traces a `trace_filter` query may return.
+	pub max_trace_results: usize,
+	/// Maximum size of an RPC request, batches included, in MiB.
+	pub max_request_size: usize,
+	/// Maximum number of filters `eth_newFilter` may keep at once.
+	pub max_stored_filters: usize,
+	/// Number of blocks an unused filter is kept for.
+	pub filter_retain_threshold: u64,
+	/// Number of blocks a pending Ethereum transaction is kept for.
+	pub transaction_retain_threshold: u64,
+	/// Gas limit `eth_call` is capped at.
+	pub rpc_gas_cap: u64,
 }
 
 /// Full client dependencies.
@@ -47,12 +73,20 @@ pub struct FullDeps<C, P> {
 	pub enable_dev_signer: bool,
 	/// Network service
 	pub network: Arc<NetworkService<Block, Hash>>,
+	/// Local keystore, holding the keys of the keystore signer.
+	pub keystore: Option<Arc<sc_keystore::LocalKeystore>>,
 	/// Ethereum pending transactions.
 	pub pending_transactions: PendingTransactions,
 	/// EthFilterApi pool.
 	pub filter_pool: Option<FilterPool>,
 	/// Backend.
 	pub backend: Arc<fc_db::Backend<Block>>,
+	/// MathChain columns of the Frontier DB, holding the log index and call traces.
+	pub mathchain_backend: Arc<mathchain_db::Backend<Block>>,
+	/// Ethereum storage overrides, by the storage schema of each block.
+	pub overrides: Arc<mathchain_rpc::OverrideHandle<Block>>,
+	/// Ethereum RPC limits.
+	pub rpc_config: RpcConfig,
 	/// Manual seal command sink
 	pub command_sink: Option<futures::channel::mpsc::Sender<sc_consensus_manual_seal::rpc::EngineCommand<Hash>>>,
 }
@@ -65,16 +99,28 @@ pub fn create_full<C, P, BE>(
 	BE: Backend<Block> + 'static,
 	BE::State: StateBackend<BlakeTwo256>,
 	C: ProvideRuntimeApi<Block> + StorageProvider<Block, BE> + AuxStore,
-	C: BlockchainEvents<Block>,
+	C: BlockchainEvents<Block> + BlockBackend<Block>,
 	C: HeaderBackend<Block> + HeaderMetadata<Block, Error=BlockChainError>,
 	C: Send + Sync + 'static,
 	C::Api: substrate_frame_rpc_system::AccountNonceApi<Block, AccountId, Index>,
 	C::Api: BlockBuilder<Block>,
 	C::Api: pallet_transaction_payment_rpc::TransactionPaymentRuntimeApi<Block, Balance>,
 	C::Api: fp_rpc::EthereumRuntimeRPCApi<Block>,
+	C::Api: mathchain_rpc_primitives::account_service::AccountServiceRuntimeApi<Block, AccountId>,
+	C::Api: mathchain_rpc_primitives::asset_fee_payment::AssetFeePaymentRuntimeApi<Block, AssetId, AssetBalance>,
+	C::Api: mathchain_rpc_primitives::debug::DebugRuntimeApi<Block>,
+	C::Api: mathchain_rpc_primitives::recovery::RecoveryRuntimeApi<Block, AccountId, Balance, BlockNumber>,
+	C::Api: mathchain_rpc_primitives::txpool::TxPoolRuntimeApi<Block>,
+	C::Api: secretstore_runtime_primitives::acl_storage::SecretStoreAclApi<Block>,
+	C::Api: secretstore_runtime_primitives::key_server_set::SecretStoreKeyServerSetApi<Block>,
+	C::Api: secretstore_runtime_primitives::service::SecretStoreServiceApi<Block>,
 	P: TransactionPool<Block=Block> + 'static,
 {
 	use substrate_frame_rpc_system::{FullSystem, SystemApi};
+	use mathchain_rpc::{
+		AccountService, AccountServiceApi, AssetFeePayment, AssetFeePaymentApi, Debug, DebugApi, EthCall, EthCallApi, EthKeystoreSigner,
+		EthSign, EthSignApi, EthLogs, EthLogsApi, Recovery, RecoveryApi, SecretStore, SecretStoreApi, Trace, TraceApi, TxPool, TxPoolApi,
+	};
 	use pallet_transaction_payment_rpc::{TransactionPayment, TransactionPaymentApi};
 	use fc_rpc::{
 		EthApi, EthApiServer, EthFilterApi, EthFilterApiServer, NetApi, NetApiServer,
@@ -89,10 +135,14 @@ pub fn create_full<C, P, BE>(
 		deny_unsafe,
 		is_authority,
 		network,
+		keystore,
 		pending_transactions,
 		filter_pool,
 		command_sink,
 		backend,
+		mathchain_backend,
+		overrides,
+		rpc_config,
 		enable_dev_signer,
 	} = deps;
 
@@ -107,11 +157,22 @@ pub fn create_full<C, P, BE>(
 	if enable_dev_signer {
 		signers.push(Box::new(EthDevSigner::new()) as Box<dyn EthSigner>);
 	}
-	let mut overrides = BTreeMap::new();
-	overrides.insert(
-		EthereumStorageSchema::V1,
-		Box::new(SchemaV1Override::new(client.clone())) as Box<dyn StorageOverride<_> + Send + Sync>
-	);
+	let keystore_signer = match keystore {
+		Some(keystore) if rpc_config.enable_keystore_signer => Some(EthKeystoreSigner::new(keystore)),
+		_ => None,
+	};
+	if let Some(keystore_signer) = &keystore_signer {
+		// Keystore accounts are only exposed where unsafe calls are allowed.
+		if deny_unsafe.check_if_safe().is_ok() {
+			signers.push(Box::new(keystore_signer.clone()) as Box<dyn EthSigner>);
+		}
+	}
+	// `eth_call` and `eth_getLogs` are served by `EthCall` and `EthLogs`, and
+	// `eth_sign` by `EthSign` when the keystore signer is enabled.
+	let mut replaced_methods = vec!["eth_call", "eth_getLogs"];
+	if keystore_signer.is_some() {
+		replaced_methods.push("eth_sign");
+	}
 	io.extend_with(
 		EthApiServer::to_delegate(EthApi::new(
 			client.clone(),
@@ -120,10 +181,84 @@ pub fn create_full<C, P, BE>(
 			network.clone(),
 			pending_transactions.clone(),
 			signers,
-			overrides,
-			backend,
+			mathchain_rpc::schema_overrides(client.clone()),
+			backend.clone(),
 			is_authority,
 		))
+			.into_iter()
+			.filter(|(name, _)| !replaced_methods.contains(&name.as_str()))
+	);
+
+	io.extend_with(
+		EthLogsApi::to_delegate(EthLogs::new(
+			client.clone(),
+			backend.clone(),
+			mathchain_backend.clone(),
+			overrides.clone(),
+			rpc_config.max_logs_range,
+			rpc_config.max_logs_results,
+		))
+	);
+
+	if let Some(keystore_signer) = keystore_signer {
+		io.extend_with(
+			EthSignApi::to_delegate(EthSign::new(keystore_signer, deny_unsafe))
+		);
+	}
+
+	io.extend_with(
+		EthCallApi::to_delegate(EthCall::new(
+			client.clone(),
+			backend.clone(),
+			rpc_config.rpc_gas_cap,
+		))
+	);
+
+	io.extend_with(
+		DebugApi::to_delegate(Debug::new(
+			client.clone(),
+			backend.clone(),
+			overrides,
+			deny_unsafe,
+		))
+	);
+
+	if rpc_config.enable_trace_filter {
+		io.extend_with(
+			TraceApi::to_delegate(Trace::new(
+				client.clone(),
+				backend,
+				mathchain_backend,
+				rpc_config.max_trace_range,
+				rpc_config.max_trace_results,
+			))
+		);
+	}
+
+	io.extend_with(
+		SecretStoreApi::to_delegate(SecretStore::new(client.clone()))
+	);
+
+	io.extend_with(
+		AccountServiceApi::to_delegate(AccountService::new(client.clone()))
+	);
+
+	io.extend_with(
+		AssetFeePaymentApi::to_delegate(AssetFeePayment::new(client.clone()))
+	);
+
+	io.extend_with(
+		RecoveryApi::to_delegate(Recovery::new(
+			client.clone(),
+			SubscriptionManager::new(Arc::new(subscription_task_executor.clone())),
+		))
+	);
+
+	io.extend_with(
+		TxPoolApi::to_delegate(TxPool::new(
+			client.clone(),
+			pool.clone(),
+		))
 	);
 
 	if let Some(filter_pool) = filter_pool {
@@ -131,7 +266,7 @@ pub fn create_full<C, P, BE>(
 			EthFilterApiServer::to_delegate(EthFilterApi::new(
 				client.clone(),
 				filter_pool.clone(),
-				500 as usize, // max stored filters
+				rpc_config.max_stored_filters,
 			))
 		);
 	}
@@ -182,23 +317,36 @@ pub fn create_light<C, P, M, F>(
 	C: sp_blockchain::HeaderBackend<Block>,
 	C: Send + Sync + 'static,
 	F: sc_client_api::light::Fetcher<Block> + 'static,
-	P: TransactionPool + 'static,
+	P: TransactionPool<Block=Block> + 'static,
 	M: jsonrpc_core::Metadata + Default,
 {
 	use substrate_frame_rpc_system::{LightSystem, SystemApi};
+	use mathchain_rpc::{EthLight, EthLightApi};
 
 	let LightDeps {
 		client,
 		pool,
 		remote_blockchain,
-		fetcher
+		fetcher,
+		network,
 	} = deps;
 	let mut io = jsonrpc_core::IoHandler::default();
 	io.extend_with(
 		SystemApi::<Hash, AccountId, Index>::to_delegate(
-			LightSystem::new(client, remote_blockchain, fetcher, pool)
+			LightSystem::new(client.clone(), remote_blockchain.clone(), fetcher.clone(), pool.clone())
 		)
 	);
 
+	io.extend_with(
+		EthLightApi::to_delegate(EthLight::new(
+			client,
+			remote_blockchain,
+			fetcher,
+			pool,
+			network,
+			mathchain_runtime::TransactionConverter,
+		))
+	);
+
 	io
 }
\ No newline at end of file
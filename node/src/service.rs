@@ -1,6 +1,6 @@
 //! Service and ServiceFactory implementation. Specialized wrapper over substrate service.
 
-use std::{sync::{Arc, Mutex}, cell::RefCell, time::Duration, collections::{HashMap, BTreeMap}};
+use std::{sync::{Arc, Mutex}, cell::RefCell, time::Duration, collections::{HashMap, BTreeMap}, path::PathBuf};
 use fc_rpc::EthTask;
 use fc_rpc_core::types::{FilterPool, PendingTransactions};
 use sc_client_api::{ExecutorProvider, RemoteBackend, BlockchainEvents};
@@ -8,7 +8,7 @@ use sc_consensus_manual_seal::{self as manual_seal};
 use fc_consensus::FrontierBlockImport;
 use fc_mapping_sync::MappingSyncWorker;
 use mathchain_runtime::{self, opaque::Block, RuntimeApi, SLOT_DURATION};
-use sc_service::{error::Error as ServiceError, Configuration, TaskManager, BasePath};
+use sc_service::{error::Error as ServiceError, Configuration, TaskManager, BasePath, config::DatabaseConfig};
 use sp_inherents::{InherentDataProviders, ProvideInherentData, InherentIdentifier, InherentData};
 use sc_executor::native_executor_instance;
 pub use sc_executor::NativeExecutor;
@@ -19,13 +19,18 @@ use sp_timestamp::InherentError;
 use sc_telemetry::{Telemetry, TelemetryWorker};
 use sc_cli::SubstrateCli;
 use futures::StreamExt;
-use crate::cli::Sealing;
+use sp_api::{ApiExt, ProvideRuntimeApi};
+use sp_blockchain::HeaderBackend;
+use sp_runtime::generic::BlockId;
+use mathchain_rpc_primitives::debug::DebugRuntimeApi;
+use crate::cli::{Sealing, FrontierDatabase, FrontierDbParams};
 
 // Our native executor instance.
 native_executor_instance!(
 	pub Executor,
 	mathchain_runtime::api::dispatch,
 	mathchain_runtime::native_version,
+	frame_benchmarking::benchmarking::HostFunctions,
 );
 
 type FullClient = sc_service::TFullClient<Block, RuntimeApi, Executor>;
@@ -77,23 +82,63 @@ impl ProvideInherentData for MockTimestampInherentDataProvider {
 	}
 }
 
-pub fn open_frontier_backend(config: &Configuration) -> Result<Arc<fc_db::Backend<Block>>, String> {
+/// Directory holding the Frontier DB.
+pub fn frontier_database_dir(config: &Configuration) -> PathBuf {
 	let config_dir = config.base_path.as_ref()
 		.map(|base_path| base_path.config_dir(config.chain_spec.id()))
 		.unwrap_or_else(|| {
 			BasePath::from_project("", "", &crate::cli::Cli::executable_name())
 				.config_dir(config.chain_spec.id())
 		});
-	let database_dir = config_dir.join("frontier").join("db");
+	config_dir.join("frontier")
+}
+
+/// Database backend of the Frontier DB, following `--database` unless set explicitly.
+pub fn frontier_database(config: &Configuration, params: &FrontierDbParams) -> FrontierDatabase {
+	params.database.unwrap_or_else(|| match config.database {
+		DatabaseConfig::ParityDb { .. } => FrontierDatabase::ParityDb,
+		_ => FrontierDatabase::RocksDb,
+	})
+}
+
+/// Path of the Frontier DB on `database`.
+fn frontier_mapping_path(config: &Configuration, database: FrontierDatabase) -> PathBuf {
+	match database {
+		FrontierDatabase::RocksDb => frontier_database_dir(config).join("db"),
+		FrontierDatabase::ParityDb => frontier_database_dir(config).join("paritydb").join("db"),
+	}
+}
+
+pub fn open_frontier_backend(
+	config: &Configuration,
+	params: &FrontierDbParams,
+) -> Result<Arc<fc_db::Backend<Block>>, String> {
+	let database = frontier_database(config, params);
+	let path = frontier_mapping_path(config, database);
+	let source = match database {
+		FrontierDatabase::RocksDb => fc_db::DatabaseSettingsSrc::RocksDb {
+			path,
+			cache_size: params.cache_size,
+		},
+		FrontierDatabase::ParityDb => fc_db::DatabaseSettingsSrc::ParityDb { path },
+	};
 
 	Ok(Arc::new(fc_db::Backend::<Block>::new(&fc_db::DatabaseSettings {
-		source: fc_db::DatabaseSettingsSrc::RocksDb {
-			path: database_dir,
-			cache_size: 0,
-		}
+		source,
+		extension_columns: mathchain_db::columns::NUM_COLUMNS,
 	})?))
 }
 
+/// Copy the RocksDB Frontier DB into the ParityDB one used with `--frontier-database paritydb`,
+/// returning the number of copied entries.
+pub fn migrate_frontier_db(config: &Configuration) -> Result<usize, String> {
+	fc_db::parity_db_adapter::migrate_from_rocksdb(
+		&frontier_mapping_path(config, FrontierDatabase::RocksDb),
+		&frontier_mapping_path(config, FrontierDatabase::ParityDb),
+		fc_db::total_columns(mathchain_db::columns::NUM_COLUMNS),
+	)
+}
+
 /// Can be called for a `Configuration` to check if it is a configuration for the `Crab` network.
 pub trait IdentifyVariant {
 	/// Returns if this is a configuration for the `Crab` network.
@@ -113,7 +158,11 @@ impl IdentifyVariant for Box<dyn sc_service::ChainSpec> {
 	}
 }
 
-pub fn new_partial(config: &Configuration, sealing: Option<Sealing>) -> Result<
+pub fn new_partial(
+	config: &Configuration,
+	sealing: Option<Sealing>,
+	frontier_db: &FrontierDbParams,
+) -> Result<
 	sc_service::PartialComponents<
 		FullClient, FullBackend, FullSelectChain,
 		sp_consensus::import_queue::BasicQueue<Block, sp_api::TransactionFor<FullClient, Block>>,
@@ -160,7 +209,7 @@ pub fn new_partial(config: &Configuration, sealing: Option<Sealing>) -> Result<
 	let filter_pool: Option<FilterPool>
 		= Some(Arc::new(Mutex::new(BTreeMap::new())));
 
-	let frontier_backend = open_frontier_backend(config)?;
+	let frontier_backend = open_frontier_backend(config, frontier_db)?;
 
 	if let Some(sealing) = sealing {
 		inherent_data_providers
@@ -204,6 +253,14 @@ pub fn new_partial(config: &Configuration, sealing: Option<Sealing>) -> Result<
 		mathchain_block_import, client.clone(),
 	);
 
+	inherent_data_providers
+		.register_provider(crate::randomness::VrfInherentDataProvider::new(
+			client.clone(),
+			keystore_container.sync_keystore(),
+		))
+		.map_err(Into::into)
+		.map_err(sp_consensus::error::Error::InherentData)?;
+
 	let import_queue = sc_consensus_aura::import_queue::<AuraPair, _, _, _, _, _>(
 		ImportQueueParams {
 			block_import: aura_block_import.clone(),
@@ -231,14 +288,21 @@ pub fn new_full(
 	mut config: Configuration,
 	sealing: Option<Sealing>,
 	enable_dev_signer: bool,
+	frontier_db: FrontierDbParams,
+	rpc_config: crate::rpc::RpcConfig,
 ) -> Result<TaskManager, ServiceError> {
 	let sc_service::PartialComponents {
 		client, backend, mut task_manager, import_queue, keystore_container,
 		select_chain, transaction_pool, inherent_data_providers,
 		other: (consensus_result, mut telemetry, pending_transactions, filter_pool, frontier_backend),
-	} = new_partial(&config, sealing)?;
+	} = new_partial(&config, sealing, &frontier_db)?;
+
+	let mathchain_backend = Arc::new(mathchain_db::Backend::new(&frontier_backend));
+	let overrides = Arc::new(mathchain_rpc::OverrideHandle::new(client.clone()));
 
 	config.network.extra_sets.push(sc_finality_grandpa::grandpa_peers_set_config());
+	// Batches are split by the RPC server, so their size is bounded with the request one.
+	config.rpc_max_payload = Some(rpc_config.max_request_size);
 
 	let (network, network_status_sinks, system_rpc_tx, network_starter) =
 		sc_service::build_network(sc_service::BuildNetworkParams {
@@ -276,6 +340,10 @@ pub fn new_full(
 		let pending = pending_transactions.clone();
 		let filter_pool = filter_pool.clone();
 		let frontier_backend = frontier_backend.clone();
+		let mathchain_backend = mathchain_backend.clone();
+		let overrides = overrides.clone();
+		let rpc_config = rpc_config.clone();
+		let keystore = keystore_container.local_keystore();
 
 		Box::new(move |deny_unsafe, _| {
 			let deps = crate::rpc::FullDeps {
@@ -285,9 +353,13 @@ pub fn new_full(
 				is_authority,
 				enable_dev_signer,
 				network: network.clone(),
+				keystore: keystore.clone(),
 				pending_transactions: pending.clone(),
 				filter_pool: filter_pool.clone(),
 				backend: frontier_backend.clone(),
+				mathchain_backend: mathchain_backend.clone(),
+				overrides: overrides.clone(),
+				rpc_config: rpc_config.clone(),
 				command_sink: Some(command_sink.clone())
 			};
 			crate::rpc::create_full(
@@ -308,6 +380,59 @@ pub fn new_full(
 		).for_each(|()| futures::future::ready(()))
 	);
 
+	task_manager.spawn_essential_handle().spawn_blocking(
+		"mathchain-log-index",
+		mathchain_rpc::LogIndexTask::sync_task(
+			client.clone(),
+			mathchain_backend.clone(),
+			overrides.clone(),
+		)
+	);
+
+	// Tracing re-executes blocks, keep it off the async executor. The node keeps
+	// running without it, only `trace_filter` stops following the chain.
+	if rpc_config.enable_trace_filter {
+		// The on-chain runtime is only traceable if it was built with `evm-tracing`
+		// too, otherwise every block would fail to be traced.
+		let best = BlockId::Hash(client.info().best_hash);
+		let traceable = client.runtime_api()
+			.has_api::<dyn DebugRuntimeApi<Block>>(&best)
+			.unwrap_or(false);
+		if !traceable {
+			return Err(ServiceError::Other(
+				"--enable-trace-filter needs a runtime built with the `evm-tracing` feature, \
+				the runtime of the best block has no DebugRuntimeApi".into()
+			));
+		}
+
+		task_manager.spawn_handle().spawn_blocking(
+			"mathchain-trace-sync",
+			mathchain_rpc::TraceTask::sync_task(
+				client.clone(),
+				mathchain_backend.clone(),
+				overrides.clone(),
+			)
+		);
+	}
+
+	// Validators publish their addresses on the DHT and find each other there.
+	if is_authority {
+		let dht_event_stream = network.event_stream("authority-discovery")
+			.filter_map(|e| async move { match e {
+				sc_network::Event::Dht(e) => Some(e),
+				_ => None,
+			}});
+		let (authority_discovery_worker, _service) = sc_authority_discovery::new_worker_and_service(
+			client.clone(),
+			network.clone(),
+			Box::pin(dht_event_stream),
+			sc_authority_discovery::Role::PublishAndDiscover(keystore_container.keystore()),
+			prometheus_registry.clone(),
+		);
+
+		task_manager.spawn_handle().spawn("authority-discovery-worker", authority_discovery_worker.run());
+	}
+
 	let _rpc_handlers = sc_service::spawn_tasks(sc_service::SpawnTasksParams {
 		network: network.clone(),
 		client: client.clone(),
@@ -326,27 +451,25 @@ pub fn new_full(
 
 	// Spawn Frontier EthFilterApi maintenance task.
 	if let Some(filter_pool) = filter_pool {
-		// Each filter is allowed to stay in the pool for 100 blocks.
-		const FILTER_RETAIN_THRESHOLD: u64 = 100;
+		// Each filter is allowed to stay in the pool for `filter_retain_threshold` blocks.
 		task_manager.spawn_essential_handle().spawn(
 			"frontier-filter-pool",
 			EthTask::filter_pool_task(
 					Arc::clone(&client),
 					filter_pool,
-					FILTER_RETAIN_THRESHOLD,
+					rpc_config.filter_retain_threshold,
 			)
 		);
 	}
 
 	// Spawn Frontier pending transactions maintenance task (as essential, otherwise we leak).
 	if let Some(pending_transactions) = pending_transactions {
-		const TRANSACTION_RETAIN_THRESHOLD: u64 = 5;
 		task_manager.spawn_essential_handle().spawn(
 			"frontier-pending-transactions",
 			EthTask::pending_transaction_task(
 				Arc::clone(&client),
 					pending_transactions,
-					TRANSACTION_RETAIN_THRESHOLD,
+					rpc_config.transaction_retain_threshold,
 				)
 		);
 	}
@@ -548,15 +671,6 @@ pub fn new_light(mut config: Configuration) -> Result<TaskManager, ServiceError>
 		},
 	)?;
 
-	let light_deps = crate::rpc::LightDeps {
-		remote_blockchain: backend.remote_blockchain(),
-		fetcher: on_demand.clone(),
-		client: client.clone(),
-		pool: transaction_pool.clone(),
-	};
-
-	let rpc_extensions = crate::rpc::create_light(light_deps);
-
 	let (network, network_status_sinks, system_rpc_tx, network_starter) =
 		sc_service::build_network(sc_service::BuildNetworkParams {
 			config: &config,
@@ -574,6 +688,16 @@ pub fn new_light(mut config: Configuration) -> Result<TaskManager, ServiceError>
 		);
 	}
 
+	let light_deps = crate::rpc::LightDeps {
+		remote_blockchain: backend.remote_blockchain(),
+		fetcher: on_demand.clone(),
+		client: client.clone(),
+		pool: transaction_pool.clone(),
+		network: network.clone(),
+	};
+
+	let rpc_extensions = crate::rpc::create_light(light_deps);
+
 	sc_service::spawn_tasks(sc_service::SpawnTasksParams {
 		remote_blockchain: Some(backend.remote_blockchain()),
 		transaction_pool,
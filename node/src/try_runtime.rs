@@ -0,0 +1,117 @@
+//! `try-runtime` subcommand.
+//!
+//! Runs the migrations of the runtime of this node on a state snapshot of a
+//! chain, then executes blocks on top of the migrated state. The snapshot is a
+//! `remote-externalities` state file and the blocks come from `export-blocks
+//! --binary`, so no node has to be reachable.
+//!
+//! The runtime of this node replaces the code of the snapshot, as after a
+//! `set_code`, and the upgrade is recorded as done, so the blocks do not run the
+//! migrations again. The state roots of the blocks are not checked, the
+//! migrations change the state they were built on.
+
+use std::{fs, path::PathBuf};
+use codec::{Decode, Encode};
+use sc_executor::NativeExecutor;
+use sc_service::Configuration;
+use sp_core::{
+	storage::well_known_keys,
+	testing::TaskExecutor,
+	traits::{RuntimeCode, WrappedRuntimeCode},
+};
+use sp_runtime::{RuntimeString, generic::SignedBlock, traits::{Block as BlockT, Header as HeaderT, NumberFor}};
+use sp_state_machine::{ExecutionStrategy, OverlayedChanges, StateMachine};
+use remote_externalities::{Builder, Mode, OfflineConfig, SnapshotConfig};
+use mathchain_runtime::{Block, Weight, WASM_BINARY, migrations::ExecutedBlock};
+use crate::service::Executor;
+
+pub async fn run(
+	config: Configuration,
+	snapshot: PathBuf,
+	blocks: Option<PathBuf>,
+) -> sc_cli::Result<()> {
+	let code = WASM_BINARY.ok_or_else(|| sc_cli::Error::Input("The runtime wasm is not available".into()))?;
+	let executor = NativeExecutor::<Executor>::new(
+		config.wasm_method,
+		config.default_heap_pages,
+		config.max_runtime_instances,
+	);
+
+	let ext = Builder::new()
+		.mode(Mode::Offline(OfflineConfig { state_snapshot: SnapshotConfig::new(snapshot) }))
+		.build()
+		.await
+		.map_err(|err| sc_cli::Error::Input(format!("Failed to load the snapshot: {:?}", err)))?;
+
+	let mut changes = OverlayedChanges::default();
+	changes.set_storage(well_known_keys::CODE.to_vec(), Some(code.to_vec()));
+	let fetcher = WrappedRuntimeCode(code.into());
+	let runtime_code = RuntimeCode {
+		code_fetcher: &fetcher,
+		heap_pages: None,
+		hash: sp_core::blake2_256(code).to_vec(),
+	};
+
+	let mut call = |method: &str, data: &[u8]| -> sc_cli::Result<Vec<u8>> {
+		StateMachine::<_, _, NumberFor<Block>, _>::new(
+			&ext.backend,
+			None,
+			&mut changes,
+			&executor,
+			method,
+			data,
+			Default::default(),
+			&runtime_code,
+			TaskExecutor::new(),
+		)
+		.execute(ExecutionStrategy::AlwaysWasm)
+		.map_err(|err| sc_cli::Error::Input(format!("Failed to call {}: {:?}", method, err)))
+	};
+
+	let encoded = call("TryRuntime_on_runtime_upgrade", &[])?;
+	let (weight, max_weight) = <Result<(Weight, Weight), RuntimeString>>::decode(&mut &*encoded)
+		.map_err(|err| sc_cli::Error::Input(format!("Failed to decode the upgrade result: {:?}", err)))?
+		.map_err(|err| sc_cli::Error::Input(format!("Migration failed: {:?}", err)))?;
+	log::info!(
+		"Runtime upgrade weighs {} ({}% of a block)",
+		weight,
+		weight.saturating_mul(100) / max_weight.max(1),
+	);
+	if weight > max_weight {
+		log::warn!("The runtime upgrade does not fit in a block");
+	}
+
+	for block in read_blocks(blocks)? {
+		let number = *block.header().number();
+		let encoded = call("TryExecuteBlock_execute_block_no_check", &block.encode())?;
+		let executed = ExecutedBlock::decode(&mut &*encoded)
+			.map_err(|err| sc_cli::Error::Input(format!("Failed to decode the result of #{}: {:?}", number, err)))?;
+		log::info!(
+			"Block #{} weighs {}, {} extrinsics returned a dispatch error",
+			number,
+			executed.weight,
+			executed.dispatch_errors,
+		);
+		if executed.invalid > 0 {
+			log::warn!("Block #{}: {} extrinsics are invalid on the migrated state", number, executed.invalid);
+		}
+	}
+
+	Ok(())
+}
+
+/// Blocks of a file written by `export-blocks --binary`.
+fn read_blocks(path: Option<PathBuf>) -> sc_cli::Result<Vec<Block>> {
+	let path = match path {
+		Some(path) => path,
+		None => return Ok(Vec::new()),
+	};
+	let bytes = fs::read(&path)?;
+	let input = &mut &bytes[..];
+
+	let invalid = |err: codec::Error| sc_cli::Error::Input(format!("Invalid blocks file {:?}: {:?}", path, err));
+	let count = u64::decode(input).map_err(invalid)?;
+	(0..count)
+		.map(|_| SignedBlock::<Block>::decode(input).map(|signed| signed.block).map_err(invalid))
+		.collect()
+}
@@ -0,0 +1,706 @@
+//! # Account Service Pallet
+//!
+//! Binds a nickname, and the addresses its owner holds on other chains, to an
+//! account, so that they resolve to it.
+//!
+//! A nickname is bound by its account. An address of another chain is bound with
+//! a signature of `ownership_message` by its key, in the signed message format of
+//! the wallets of that chain:
+//!
+//! - Ethereum and Tron: secp256k1, signed with the `\x19Ethereum Signed Message:\n`
+//!   or `\x19TRON Signed Message:\n` prefix, then the message length.
+//! - Bitcoin: secp256k1 signed message of a P2PKH address, compressed or not.
+//! - Solana: ed25519 signature of the message.
+//! - Polkadot: SCALE encoded `MultiSignature` of the message wrapped in
+//!   `<Bytes>..</Bytes>`, as signed by `signRaw` of polkadot.js.
+//!
+//! The message names the account and the genesis hash of the chain, so a proof
+//! can neither bind the address to another account nor be replayed on another
+//! chain.
+//!
+//! This crate is patched in place of the `pallet-account-service` 2.0.0 of
+//! MathChain-common, whose nickname and Ethereum storage it keeps.
+//! `migrate_to_v3` removes the bindings 2.0.0 left behind.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use sp_std::{prelude::*, convert::TryFrom};
+use codec::{Encode, Decode};
+use frame_support::{
+	decl_module, decl_storage, decl_event, decl_error, ensure,
+	storage::IterableStorageMap,
+	traits::{EnsureOrigin, Get},
+	weights::Weight,
+};
+use frame_system::ensure_signed;
+use sp_core::{H160, ed25519, crypto::AccountId32};
+use sp_io::hashing::{keccak_256, sha2_256};
+use sp_runtime::{MultiSignature, RuntimeDebug, traits::{Verify, Zero}};
+
+/// Maximum number of addresses of chains other than Ethereum bound to an account.
+pub const MAX_CHAIN_ADDRESSES: usize = 16;
+
+/// A nickname, or an address of another chain.
+///
+/// Variants are only ever appended, the encoding is shared with the clients.
+#[derive(Clone, Eq, PartialEq, Encode, Decode, RuntimeDebug)]
+pub enum AccountServiceEnum {
+	Nickname(Vec<u8>),
+	Ethereum(H160),
+	/// Hash160 of the public key of a P2PKH address.
+	Bitcoin([u8; 20]),
+	/// Address without its `0x41` prefix.
+	Tron(H160),
+	/// Ed25519 public key.
+	Solana([u8; 32]),
+	/// Account id, under any SS58 prefix.
+	Polkadot([u8; 32]),
+}
+
+/// Nickname and Ethereum address of an account, an empty nickname or a zero
+/// address when unset.
+#[derive(Clone, Eq, PartialEq, Encode, Decode, RuntimeDebug)]
+pub struct MultiAddressDetails {
+	pub nickname: AccountServiceEnum,
+	pub ethereum: AccountServiceEnum,
+}
+
+impl Default for MultiAddressDetails {
+	fn default() -> Self {
+		MultiAddressDetails {
+			nickname: AccountServiceEnum::Nickname(Vec::new()),
+			ethereum: AccountServiceEnum::Ethereum(H160::zero()),
+		}
+	}
+}
+
+pub trait Config: frame_system::Config {
+	/// The overarching event type.
+	type Event: From<Event<Self>> + Into<<Self as frame_system::Config>::Event>;
+
+	/// The origin which may bind and clear the addresses of any account.
+	type ForceOrigin: EnsureOrigin<Self::Origin>;
+
+	/// The minimum length of a nickname.
+	type MinLength: Get<usize>;
+
+	/// The maximum length of a nickname.
+	type MaxLength: Get<usize>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Config> as AccountService {
+		/// Nickname and Ethereum address bound to an account.
+		pub MultiAddressOf get(fn multi_address_of):
+			map hasher(blake2_128_concat) T::AccountId => Option<MultiAddressDetails>;
+
+		/// Addresses of the other chains bound to an account.
+		pub ChainAddressesOf get(fn chain_addresses_of):
+			map hasher(blake2_128_concat) T::AccountId => Vec<AccountServiceEnum>;
+
+		/// Account a nickname or an address is bound to.
+		pub FromNickname: map hasher(blake2_128_concat) AccountServiceEnum => T::AccountId;
+	}
+}
+
+decl_event! {
+	pub enum Event<T> where AccountId = <T as frame_system::Config>::AccountId {
+		/// A nickname or an address was bound to an account. \[account, address\]
+		Bound(AccountId, AccountServiceEnum),
+		/// A nickname or an address was unbound from an account. \[account, address\]
+		Unbound(AccountId, AccountServiceEnum),
+		/// All the addresses of an account were unbound. \[account\]
+		Cleared(AccountId),
+	}
+}
+
+decl_error! {
+	pub enum Error for Module<T: Config> {
+		/// The nickname is too short.
+		TooShort,
+		/// The nickname is too long.
+		TooLong,
+		/// The nickname or the address is bound to another account.
+		AlreadyBound,
+		/// The signature was not made by the key of the address.
+		InvalidProof,
+		/// The address is not bound to the account.
+		NotBound,
+		/// The account has `MAX_CHAIN_ADDRESSES` addresses of other chains.
+		TooManyAddresses,
+	}
+}
+
+decl_module! {
+	pub struct Module<T: Config> for enum Call where origin: T::Origin {
+		type Error = Error<T>;
+
+		const MinLength: u32 = T::MinLength::get() as u32;
+
+		const MaxLength: u32 = T::MaxLength::get() as u32;
+
+		fn deposit_event() = default;
+
+		/// Bind a nickname or an address to the caller, replacing its nickname or its
+		/// Ethereum address.
+		///
+		/// `proof` is the signature of `ownership_message` by the key of the address,
+		/// it is ignored for a nickname.
+		///
+		/// The dispatch origin for this call must be _Signed_.
+		#[weight = 50_000_000 + T::DbWeight::get().reads_writes(3, 3)]
+		fn bind(origin, address: AccountServiceEnum, proof: Vec<u8>) {
+			let who = ensure_signed(origin)?;
+
+			if let AccountServiceEnum::Nickname(nickname) = &address {
+				ensure!(nickname.len() >= T::MinLength::get(), Error::<T>::TooShort);
+				ensure!(nickname.len() <= T::MaxLength::get(), Error::<T>::TooLong);
+			} else {
+				ensure!(verify_proof(&address, &proof, &Self::ownership_message(&who)), Error::<T>::InvalidProof);
+			}
+
+			Self::do_bind(who, address)?;
+		}
+
+		/// Unbind a nickname or an address from the caller.
+		///
+		/// The dispatch origin for this call must be _Signed_.
+		#[weight = 30_000_000 + T::DbWeight::get().reads_writes(2, 2)]
+		fn unbind(origin, address: AccountServiceEnum) {
+			let who = ensure_signed(origin)?;
+
+			Self::do_unbind(&who, &address)?;
+			Self::deposit_event(RawEvent::Unbound(who, address));
+		}
+
+		/// Unbind all the nicknames and addresses of the caller.
+		///
+		/// The dispatch origin for this call must be _Signed_.
+		#[weight = 50_000_000 + T::DbWeight::get().reads_writes(2, MAX_CHAIN_ADDRESSES as u64 + 4)]
+		fn clear(origin) {
+			let who = ensure_signed(origin)?;
+
+			Self::do_clear(&who);
+			Self::deposit_event(RawEvent::Cleared(who));
+		}
+
+		/// Bind a nickname or an address to `target`, without proof.
+		///
+		/// The dispatch origin for this call must match `T::ForceOrigin`.
+		#[weight = 50_000_000 + T::DbWeight::get().reads_writes(3, 3)]
+		fn force_bind(origin, target: T::AccountId, address: AccountServiceEnum) {
+			T::ForceOrigin::ensure_origin(origin)?;
+
+			Self::do_bind(target, address)?;
+		}
+
+		/// Unbind a nickname or an address from `target`.
+		///
+		/// The dispatch origin for this call must match `T::ForceOrigin`.
+		#[weight = 30_000_000 + T::DbWeight::get().reads_writes(2, 2)]
+		fn force_unbind(origin, target: T::AccountId, address: AccountServiceEnum) {
+			T::ForceOrigin::ensure_origin(origin)?;
+
+			Self::do_unbind(&target, &address)?;
+			Self::deposit_event(RawEvent::Unbound(target, address));
+		}
+
+		/// Unbind all the nicknames and addresses of `target`.
+		///
+		/// The dispatch origin for this call must match `T::ForceOrigin`.
+		#[weight = 50_000_000 + T::DbWeight::get().reads_writes(2, MAX_CHAIN_ADDRESSES as u64 + 4)]
+		fn force_clear(origin, target: T::AccountId) {
+			T::ForceOrigin::ensure_origin(origin)?;
+
+			Self::do_clear(&target);
+			Self::deposit_event(RawEvent::Cleared(target));
+		}
+	}
+}
+
+impl<T: Config> Module<T> {
+	/// Account `address` is bound to, the default account if none.
+	pub fn from_ethereum(address: &AccountServiceEnum) -> T::AccountId {
+		FromNickname::<T>::get(address)
+	}
+
+	/// Account `address` is bound to.
+	pub fn account_of(address: &AccountServiceEnum) -> Option<T::AccountId> {
+		if FromNickname::<T>::contains_key(address) {
+			Some(FromNickname::<T>::get(address))
+		} else {
+			None
+		}
+	}
+
+	/// Nicknames and addresses bound to `account`.
+	pub fn linked_addresses(account: &T::AccountId) -> Vec<AccountServiceEnum> {
+		let details = MultiAddressOf::<T>::get(account).unwrap_or_default();
+		let mut addresses = Vec::new();
+		if details.nickname != MultiAddressDetails::default().nickname {
+			addresses.push(details.nickname);
+		}
+		if details.ethereum != MultiAddressDetails::default().ethereum {
+			addresses.push(details.ethereum);
+		}
+		addresses.extend(ChainAddressesOf::<T>::get(account));
+		addresses
+	}
+
+	/// Message the key of an address signs to bind it to `account`.
+	pub fn ownership_message(account: &T::AccountId) -> Vec<u8> {
+		let genesis_hash = frame_system::Pallet::<T>::block_hash(T::BlockNumber::zero());
+		let mut message = b"Bind to MathChain account 0x".to_vec();
+		message.extend(hex(&account.encode()));
+		message.extend_from_slice(b" on chain 0x");
+		message.extend(hex(genesis_hash.as_ref()));
+		message
+	}
+
+	fn do_bind(who: T::AccountId, address: AccountServiceEnum) -> frame_support::dispatch::DispatchResult {
+		match Self::account_of(&address) {
+			Some(account) if account == who => return Ok(()),
+			Some(_) => return Err(Error::<T>::AlreadyBound.into()),
+			None => {},
+		}
+
+		let mut details = MultiAddressOf::<T>::get(&who).unwrap_or_default();
+		let replaced = match &address {
+			AccountServiceEnum::Nickname(_) => Some(sp_std::mem::replace(&mut details.nickname, address.clone())),
+			AccountServiceEnum::Ethereum(_) => Some(sp_std::mem::replace(&mut details.ethereum, address.clone())),
+			_ => None,
+		};
+		match replaced {
+			Some(replaced) => {
+				FromNickname::<T>::remove(&replaced);
+				MultiAddressOf::<T>::insert(&who, details);
+			},
+			None => ChainAddressesOf::<T>::try_mutate(&who, |addresses| {
+				ensure!(addresses.len() < MAX_CHAIN_ADDRESSES, Error::<T>::TooManyAddresses);
+				addresses.push(address.clone());
+				Ok::<_, Error<T>>(())
+			})?,
+		}
+		FromNickname::<T>::insert(&address, &who);
+
+		Self::deposit_event(RawEvent::Bound(who, address));
+		Ok(())
+	}
+
+	fn do_unbind(who: &T::AccountId, address: &AccountServiceEnum) -> frame_support::dispatch::DispatchResult {
+		ensure!(Self::account_of(address).as_ref() == Some(who), Error::<T>::NotBound);
+
+		let mut details = MultiAddressOf::<T>::get(who).unwrap_or_default();
+		let defaults = MultiAddressDetails::default();
+		if &details.nickname == address {
+			details.nickname = defaults.nickname;
+			MultiAddressOf::<T>::insert(who, details);
+		} else if &details.ethereum == address {
+			details.ethereum = defaults.ethereum;
+			MultiAddressOf::<T>::insert(who, details);
+		} else {
+			ChainAddressesOf::<T>::mutate(who, |addresses| addresses.retain(|bound| bound != address));
+		}
+		FromNickname::<T>::remove(address);
+		Ok(())
+	}
+
+	fn do_clear(who: &T::AccountId) {
+		for address in Self::linked_addresses(who) {
+			FromNickname::<T>::remove(&address);
+		}
+		MultiAddressOf::<T>::remove(who);
+		ChainAddressesOf::<T>::remove(who);
+	}
+
+	/// Migrates the storage of version 2.0.0, so that every binding in `FromNickname`
+	/// is listed by its account.
+	///
+	/// Removes the bindings the account does not list, left when a nickname or an
+	/// Ethereum address was replaced, those of an empty nickname or of the zero
+	/// address, and the `MultiAddressOf` of the accounts with nothing bound.
+	pub fn migrate_to_v3() -> Weight {
+		let mut stale = Vec::new();
+		let mut reads = 0;
+		for (address, account) in FromNickname::<T>::iter() {
+			reads += 2;
+			if !Self::linked_addresses(&account).contains(&address) {
+				stale.push(address);
+			}
+		}
+		let mut empty = Vec::new();
+		for (account, details) in MultiAddressOf::<T>::iter() {
+			reads += 1;
+			if details == MultiAddressDetails::default() {
+				empty.push(account);
+			}
+		}
+
+		let writes = (stale.len() + empty.len()) as Weight;
+		for address in stale {
+			FromNickname::<T>::remove(&address);
+		}
+		for account in empty {
+			MultiAddressOf::<T>::remove(&account);
+		}
+		T::DbWeight::get().reads_writes(reads, writes)
+	}
+}
+
+/// Whether `proof` is a signature of `message` by the key of `address`.
+pub fn verify_proof(address: &AccountServiceEnum, proof: &[u8], message: &[u8]) -> bool {
+	match address {
+		AccountServiceEnum::Nickname(_) => false,
+		AccountServiceEnum::Ethereum(address) =>
+			recover_prefixed(b"\x19Ethereum Signed Message:\n", proof, message) == Some(*address),
+		AccountServiceEnum::Tron(address) =>
+			recover_prefixed(b"\x19TRON Signed Message:\n", proof, message) == Some(*address),
+		AccountServiceEnum::Bitcoin(hash) => recover_bitcoin(proof, message).as_ref() == Some(hash),
+		AccountServiceEnum::Solana(public) => match <[u8; 64]>::try_from(proof) {
+			Ok(signature) => sp_io::crypto::ed25519_verify(
+				&ed25519::Signature::from_raw(signature),
+				message,
+				&ed25519::Public::from_raw(*public),
+			),
+			Err(_) => false,
+		},
+		AccountServiceEnum::Polkadot(public) => match MultiSignature::decode(&mut &proof[..]) {
+			Ok(signature) => {
+				let mut wrapped = b"<Bytes>".to_vec();
+				wrapped.extend_from_slice(message);
+				wrapped.extend_from_slice(b"</Bytes>");
+				signature.verify(&wrapped[..], &AccountId32::new(*public))
+			},
+			Err(_) => false,
+		},
+	}
+}
+
+/// Address of the key which signed `message` behind `prefix` and its decimal length,
+/// as Ethereum `personal_sign` does.
+fn recover_prefixed(prefix: &[u8], proof: &[u8], message: &[u8]) -> Option<H160> {
+	let mut signature = <[u8; 65]>::try_from(proof).ok()?;
+	// Wallets sign with a recovery id of 27 or 28.
+	if signature[64] >= 27 {
+		signature[64] -= 27;
+	}
+	let mut prefixed = prefix.to_vec();
+	prefixed.extend(decimal(message.len()));
+	prefixed.extend_from_slice(message);
+	let public = sp_io::crypto::secp256k1_ecdsa_recover(&signature, &keccak_256(&prefixed)).ok()?;
+	Some(H160::from_slice(&keccak_256(&public)[12..]))
+}
+
+/// Hash160 of the public key which signed `message` as a Bitcoin signed message.
+///
+/// The header byte of the signature holds the recovery id and, from 31, whether
+/// the address is of the compressed key.
+fn recover_bitcoin(proof: &[u8], message: &[u8]) -> Option<[u8; 20]> {
+	use ripemd160::{Digest, Ripemd160};
+
+	let proof = <[u8; 65]>::try_from(proof).ok()?;
+	let header = proof[0];
+	if !(27..=34).contains(&header) {
+		return None;
+	}
+	let mut signature = [0u8; 65];
+	signature[..64].copy_from_slice(&proof[1..]);
+	signature[64] = (header - 27) % 4;
+
+	let mut prefixed = compact_size(24);
+	prefixed.extend_from_slice(b"Bitcoin Signed Message:\n");
+	prefixed.extend(compact_size(message.len()));
+	prefixed.extend_from_slice(message);
+	let hash = sha2_256(&sha2_256(&prefixed));
+
+	let public = if header >= 31 {
+		sp_io::crypto::secp256k1_ecdsa_recover_compressed(&signature, &hash).ok()?.to_vec()
+	} else {
+		let mut public = vec![4u8];
+		public.extend_from_slice(&sp_io::crypto::secp256k1_ecdsa_recover(&signature, &hash).ok()?);
+		public
+	};
+	let mut hash160 = [0u8; 20];
+	hash160.copy_from_slice(&Ripemd160::digest(&sha2_256(&public)));
+	Some(hash160)
+}
+
+/// Bitcoin variable length integer.
+fn compact_size(len: usize) -> Vec<u8> {
+	match len {
+		0..=0xfc => vec![len as u8],
+		0xfd..=0xffff => {
+			let mut bytes = vec![0xfd];
+			bytes.extend_from_slice(&(len as u16).to_le_bytes());
+			bytes
+		},
+		_ => {
+			let mut bytes = vec![0xfe];
+			bytes.extend_from_slice(&(len as u32).to_le_bytes());
+			bytes
+		},
+	}
+}
+
+/// ASCII decimal digits of `n`.
+fn decimal(mut n: usize) -> Vec<u8> {
+	let mut digits = Vec::new();
+	loop {
+		digits.push(b'0' + (n % 10) as u8);
+		n /= 10;
+		if n == 0 {
+			break;
+		}
+	}
+	digits.reverse();
+	digits
+}
+
+/// Lower case hex of `bytes`.
+fn hex(bytes: &[u8]) -> Vec<u8> {
+	const DIGITS: &[u8; 16] = b"0123456789abcdef";
+	bytes.iter().flat_map(|byte| vec![DIGITS[(byte >> 4) as usize], DIGITS[(byte & 0xf) as usize]]).collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use frame_support::{assert_noop, assert_ok, construct_runtime, parameter_types};
+	use sp_core::{H256, Pair, sr25519};
+	use sp_runtime::{testing::Header, traits::{BadOrigin, BlakeTwo256, IdentityLookup}};
+
+	type UncheckedExtrinsic = frame_system::mocking::MockUncheckedExtrinsic<Test>;
+	type Block = frame_system::mocking::MockBlock<Test>;
+
+	construct_runtime!(
+		pub enum Test where
+			Block = Block,
+			NodeBlock = Block,
+			UncheckedExtrinsic = UncheckedExtrinsic,
+		{
+			System: frame_system::{Pallet, Call, Config, Storage, Event<T>},
+			AccountService: super::{Pallet, Call, Storage, Event<T>},
+		}
+	);
+
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MinLength: usize = 3;
+		pub const MaxLength: usize = 16;
+	}
+
+	impl frame_system::Config for Test {
+		type BaseCallFilter = ();
+		type BlockWeights = ();
+		type BlockLength = ();
+		type DbWeight = ();
+		type Origin = Origin;
+		type Call = Call;
+		type Index = u64;
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = Event;
+		type BlockHashCount = BlockHashCount;
+		type Version = ();
+		type PalletInfo = PalletInfo;
+		type AccountData = ();
+		type OnNewAccount = ();
+		type OnKilledAccount = ();
+		type SystemWeightInfo = ();
+		type SS58Prefix = ();
+	}
+
+	impl Config for Test {
+		type Event = Event;
+		type ForceOrigin = frame_system::EnsureRoot<u64>;
+		type MinLength = MinLength;
+		type MaxLength = MaxLength;
+	}
+
+	fn new_test_ext() -> sp_io::TestExternalities {
+		frame_system::GenesisConfig::default().build_storage::<Test>().unwrap().into()
+	}
+
+	fn secp256k1_key(seed: u8) -> secp256k1::SecretKey {
+		secp256k1::SecretKey::parse(&[seed; 32]).unwrap()
+	}
+
+	/// Signature as `[r, s, v]` with `v` of 27 or 28.
+	fn secp256k1_sign(secret: &secp256k1::SecretKey, hash: [u8; 32]) -> Vec<u8> {
+		let (signature, recovery_id) = secp256k1::sign(&secp256k1::Message::parse(&hash), secret);
+		let mut proof = signature.serialize().to_vec();
+		proof.push(recovery_id.serialize() + 27);
+		proof
+	}
+
+	fn personal_sign(prefix: &[u8], secret: &secp256k1::SecretKey, message: &[u8]) -> (H160, Vec<u8>) {
+		let mut prefixed = prefix.to_vec();
+		prefixed.extend(decimal(message.len()));
+		prefixed.extend_from_slice(message);
+		let public = secp256k1::PublicKey::from_secret_key(secret).serialize();
+		(H160::from_slice(&keccak_256(&public[1..])[12..]), secp256k1_sign(secret, keccak_256(&prefixed)))
+	}
+
+	#[test]
+	fn binds_ethereum_and_tron_addresses_with_personal_sign() {
+		new_test_ext().execute_with(|| {
+			let message = AccountService::ownership_message(&1);
+
+			let (address, proof) = personal_sign(b"\x19Ethereum Signed Message:\n", &secp256k1_key(1), &message);
+			assert_ok!(AccountService::bind(Origin::signed(1), AccountServiceEnum::Ethereum(address), proof));
+			assert_eq!(AccountService::account_of(&AccountServiceEnum::Ethereum(address)), Some(1));
+
+			let (address, proof) = personal_sign(b"\x19TRON Signed Message:\n", &secp256k1_key(2), &message);
+			assert_ok!(AccountService::bind(Origin::signed(1), AccountServiceEnum::Tron(address), proof.clone()));
+			assert_eq!(AccountService::account_of(&AccountServiceEnum::Tron(address)), Some(1));
+
+			// A Tron signature does not prove an Ethereum address.
+			assert_noop!(
+				AccountService::bind(Origin::signed(1), AccountServiceEnum::Ethereum(address), proof),
+				Error::<Test>::InvalidProof,
+			);
+		});
+	}
+
+	#[test]
+	fn binds_bitcoin_addresses_of_compressed_and_uncompressed_keys() {
+		use ripemd160::{Digest, Ripemd160};
+
+		new_test_ext().execute_with(|| {
+			let message = AccountService::ownership_message(&1);
+			let mut prefixed = b"\x18Bitcoin Signed Message:\n".to_vec();
+			prefixed.extend(compact_size(message.len()));
+			prefixed.extend_from_slice(&message);
+			let hash = sha2_256(&sha2_256(&prefixed));
+
+			for (seed, compressed) in vec![(3, true), (4, false)] {
+				let secret = secp256k1_key(seed);
+				let public = secp256k1::PublicKey::from_secret_key(&secret);
+				let serialized = if compressed {
+					public.serialize_compressed().to_vec()
+				} else {
+					public.serialize().to_vec()
+				};
+				let mut hash160 = [0u8; 20];
+				hash160.copy_from_slice(&Ripemd160::digest(&sha2_256(&serialized)));
+
+				let signature = secp256k1_sign(&secret, hash);
+				let mut proof = vec![signature[64] + if compressed { 4 } else { 0 }];
+				proof.extend_from_slice(&signature[..64]);
+
+				assert_ok!(AccountService::bind(Origin::signed(1), AccountServiceEnum::Bitcoin(hash160), proof));
+				assert_eq!(AccountService::account_of(&AccountServiceEnum::Bitcoin(hash160)), Some(1));
+			}
+		});
+	}
+
+	#[test]
+	fn binds_solana_and_polkadot_addresses() {
+		new_test_ext().execute_with(|| {
+			let message = AccountService::ownership_message(&1);
+
+			let solana = ed25519::Pair::from_seed(&[5; 32]);
+			let proof = solana.sign(&message).0.to_vec();
+			assert_ok!(AccountService::bind(Origin::signed(1), AccountServiceEnum::Solana(solana.public().0), proof));
+
+			let polkadot = sr25519::Pair::from_seed(&[6; 32]);
+			let mut wrapped = b"<Bytes>".to_vec();
+			wrapped.extend_from_slice(&message);
+			wrapped.extend_from_slice(b"</Bytes>");
+			let proof = MultiSignature::from(polkadot.sign(&wrapped)).encode();
+			assert_ok!(AccountService::bind(Origin::signed(1), AccountServiceEnum::Polkadot(polkadot.public().0), proof));
+
+			assert_eq!(AccountService::linked_addresses(&1), vec![
+				AccountServiceEnum::Solana(solana.public().0),
+				AccountServiceEnum::Polkadot(polkadot.public().0),
+			]);
+		});
+	}
+
+	#[test]
+	fn proofs_are_bound_to_the_account_and_the_chain() {
+		new_test_ext().execute_with(|| {
+			let solana = ed25519::Pair::from_seed(&[5; 32]);
+			let address = AccountServiceEnum::Solana(solana.public().0);
+
+			let proof = solana.sign(&AccountService::ownership_message(&2)).0.to_vec();
+			assert_noop!(
+				AccountService::bind(Origin::signed(1), address.clone(), proof),
+				Error::<Test>::InvalidProof,
+			);
+
+			let proof = solana.sign(&AccountService::ownership_message(&1)).0.to_vec();
+			frame_system::BlockHash::<Test>::insert(0, H256::repeat_byte(1));
+			assert_noop!(
+				AccountService::bind(Origin::signed(1), address, proof),
+				Error::<Test>::InvalidProof,
+			);
+		});
+	}
+
+	#[test]
+	fn nicknames_are_unique_and_replaced() {
+		new_test_ext().execute_with(|| {
+			let nickname = |name: &[u8]| AccountServiceEnum::Nickname(name.to_vec());
+
+			assert_noop!(AccountService::bind(Origin::signed(1), nickname(b"ab"), vec![]), Error::<Test>::TooShort);
+			assert_ok!(AccountService::bind(Origin::signed(1), nickname(b"alice"), vec![]));
+			assert_noop!(AccountService::bind(Origin::signed(2), nickname(b"alice"), vec![]), Error::<Test>::AlreadyBound);
+
+			assert_ok!(AccountService::bind(Origin::signed(1), nickname(b"alicia"), vec![]));
+			assert_eq!(AccountService::account_of(&nickname(b"alice")), None);
+			assert_eq!(AccountService::linked_addresses(&1), vec![nickname(b"alicia")]);
+
+			assert_ok!(AccountService::force_clear(Origin::root(), 1));
+			assert_eq!(AccountService::account_of(&nickname(b"alicia")), None);
+			assert_eq!(AccountService::linked_addresses(&1), vec![]);
+		});
+	}
+
+	#[test]
+	fn migrates_the_bindings_of_v2() {
+		new_test_ext().execute_with(|| {
+			let nickname = |name: &[u8]| AccountServiceEnum::Nickname(name.to_vec());
+			let ethereum = AccountServiceEnum::Ethereum(H160::repeat_byte(1));
+			// The nickname `old` was replaced by `new`, but still resolves.
+			MultiAddressOf::<Test>::insert(1, MultiAddressDetails { nickname: nickname(b"new"), ethereum: ethereum.clone() });
+			FromNickname::<Test>::insert(nickname(b"new"), 1);
+			FromNickname::<Test>::insert(nickname(b"old"), 1);
+			FromNickname::<Test>::insert(&ethereum, 1);
+			// Account 2 cleared its addresses.
+			MultiAddressOf::<Test>::insert(2, MultiAddressDetails::default());
+			FromNickname::<Test>::insert(AccountServiceEnum::Ethereum(H160::zero()), 2);
+
+			AccountService::migrate_to_v3();
+
+			assert_eq!(FromNickname::<Test>::iter().count(), 2);
+			assert_eq!(AccountService::account_of(&nickname(b"new")), Some(1));
+			assert_eq!(AccountService::account_of(&ethereum), Some(1));
+			assert_eq!(AccountService::account_of(&nickname(b"old")), None);
+			assert_eq!(AccountService::multi_address_of(2), None);
+			assert_ok!(AccountService::bind(Origin::signed(2), nickname(b"old"), vec![]));
+		});
+	}
+
+	#[test]
+	fn force_unbind_keeps_the_other_addresses() {
+		new_test_ext().execute_with(|| {
+			let nickname = AccountServiceEnum::Nickname(b"alice".to_vec());
+			let ethereum = AccountServiceEnum::Ethereum(H160::repeat_byte(1));
+			let tron = AccountServiceEnum::Tron(H160::repeat_byte(2));
+			for address in vec![nickname.clone(), ethereum.clone(), tron.clone()] {
+				assert_ok!(AccountService::force_bind(Origin::root(), 1, address));
+			}
+
+			assert_noop!(AccountService::force_unbind(Origin::signed(1), 1, ethereum.clone()), BadOrigin);
+			assert_noop!(AccountService::force_unbind(Origin::root(), 2, ethereum.clone()), Error::<Test>::NotBound);
+			assert_ok!(AccountService::force_unbind(Origin::root(), 1, ethereum.clone()));
+			assert_eq!(AccountService::account_of(&ethereum), None);
+			assert_eq!(AccountService::linked_addresses(&1), vec![nickname, tron]);
+		});
+	}
+}
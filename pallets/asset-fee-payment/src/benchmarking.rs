@@ -0,0 +1,59 @@
+//! Benchmarks of the asset fee payment pallet.
+
+#![cfg(feature = "runtime-benchmarks")]
+
+use super::*;
+use frame_benchmarking::{account, benchmarks, impl_benchmark_test_suite};
+use frame_system::RawOrigin;
+
+const SEED: u32 = 0;
+
+fn rate() -> FixedU128 {
+	FixedU128::saturating_from_integer(2)
+}
+
+/// A sufficient asset the fees can be paid in.
+fn fee_asset<T: Config>() -> AssetIdOf<T> {
+	let asset = T::SufficientAssets::create_sufficient();
+	FeeRates::<T>::insert(asset, rate());
+	asset
+}
+
+benchmarks! {
+	set_fee_asset {
+		let asset = T::SufficientAssets::create_sufficient();
+	}: _(RawOrigin::Root, asset, rate())
+	verify {
+		assert_eq!(Module::<T>::fee_rate(asset), Some(rate()));
+	}
+
+	remove_fee_asset {
+		let asset = fee_asset::<T>();
+	}: _(RawOrigin::Root, asset)
+	verify {
+		assert_eq!(Module::<T>::fee_rate(asset), None);
+	}
+
+	set_oracle {
+		let oracle: T::AccountId = account("oracle", 0, SEED);
+	}: _(RawOrigin::Root, Some(oracle.clone()))
+	verify {
+		assert_eq!(Module::<T>::oracle(), Some(oracle));
+	}
+
+	update_rate {
+		let asset = fee_asset::<T>();
+		let oracle: T::AccountId = account("oracle", 0, SEED);
+		Oracle::<T>::put(&oracle);
+		let new_rate = FixedU128::saturating_from_integer(3);
+	}: _(RawOrigin::Signed(oracle), asset, new_rate)
+	verify {
+		assert_eq!(Module::<T>::fee_rate(asset), Some(new_rate));
+	}
+}
+
+impl_benchmark_test_suite!(
+	Module,
+	crate::tests::new_test_ext(),
+	crate::tests::Test,
+);
@@ -0,0 +1,678 @@
+//! # Asset Fee Payment Pallet
+//!
+//! Transaction fees paid in assets instead of MATH.
+//!
+//! Root whitelists an asset for the fees with its rate, the amount of the asset
+//! paid for one unit of MATH. Only a sufficient asset, which accounts can hold
+//! without holding MATH, can be whitelisted. The rate is then kept up to date by
+//! Root or by the fee oracle, an account set by Root.
+//!
+//! `ChargeAssetTxPayment` takes the place of `ChargeTransactionPayment` in the
+//! signed extensions. A transaction naming no asset pays its fee in MATH, as
+//! before. A transaction naming a whitelisted asset pays the fee computed by
+//! `pallet_transaction_payment`, converted at the rate of the asset: the fee is
+//! burnt before the dispatch, the unused part is minted back after it, and the fee
+//! actually paid is minted to `Config::FeeReceiver`.
+//!
+//! Paying the fee must leave the payer at least the minimum balance of the asset.
+//! A part of the fee which cannot be minted back, such as dust below the minimum
+//! balance of an account the call emptied, goes to the fee receiver. If the fee
+//! receiver cannot be credited either, the transaction is invalid.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+mod benchmarking;
+pub mod weights;
+
+use sp_std::{prelude::*, marker::PhantomData};
+use codec::{Encode, Decode};
+use frame_support::{
+	decl_module, decl_storage, decl_event, decl_error, ensure,
+	dispatch::DispatchResult,
+	traits::{Currency, Get, PalletInfo, tokens::{fungibles, DepositConsequence, WithdrawConsequence}},
+	weights::{DispatchInfo, PostDispatchInfo},
+};
+use frame_system::{ensure_root, RawOrigin};
+use sp_runtime::{
+	DispatchError, FixedPointNumber, FixedPointOperand, FixedU128, SaturatedConversion,
+	traits::{DispatchInfoOf, Dispatchable, PostDispatchInfoOf, SignedExtension, Zero},
+	transaction_validity::{
+		InvalidTransaction, TransactionPriority, TransactionValidity, TransactionValidityError,
+		ValidTransaction,
+	},
+};
+use pallet_transaction_payment::{
+	ChargeTransactionPayment, FeeDetails, InclusionFee, OnChargeTransaction,
+};
+
+pub use weights::WeightInfo;
+
+type BalanceOf<T> = <<T as pallet_transaction_payment::Config>::OnChargeTransaction as OnChargeTransaction<T>>::Balance;
+type LiquidityInfoOf<T> = <<T as pallet_transaction_payment::Config>::OnChargeTransaction as OnChargeTransaction<T>>::LiquidityInfo;
+type AssetIdOf<T> = <<T as Config>::Assets as fungibles::Inspect<<T as frame_system::Config>::AccountId>>::AssetId;
+type AssetBalanceOf<T> = <<T as Config>::Assets as fungibles::Inspect<<T as frame_system::Config>::AccountId>>::Balance;
+
+pub trait Config: frame_system::Config + pallet_transaction_payment::Config {
+	/// The overarching event type.
+	type Event: From<Event<Self>> + Into<<Self as frame_system::Config>::Event>;
+
+	/// Assets the fees can be paid in.
+	type Assets: fungibles::Inspect<Self::AccountId> + fungibles::Mutate<Self::AccountId>;
+
+	/// Receiver of the fees paid in assets.
+	type FeeReceiver: Get<Self::AccountId>;
+
+	/// Whether accounts can hold an asset without holding MATH.
+	type SufficientAssets: IsSufficient<AssetIdOf<Self>>;
+
+	/// Weight information for extrinsics in this pallet.
+	type WeightInfo: WeightInfo;
+}
+
+/// Whether accounts can hold an asset without holding MATH.
+pub trait IsSufficient<AssetId> {
+	fn is_sufficient(asset: AssetId) -> bool;
+
+	/// Creates a sufficient asset, for the benchmarks.
+	#[cfg(feature = "runtime-benchmarks")]
+	fn create_sufficient() -> AssetId;
+}
+
+/// Reads whether an asset of `pallet_assets` is sufficient out of the details the
+/// pallet keeps to itself.
+pub struct SufficientAssetsOf<T>(PhantomData<T>);
+
+impl<T: pallet_assets::Config> IsSufficient<T::AssetId> for SufficientAssetsOf<T> {
+	fn is_sufficient(asset: T::AssetId) -> bool {
+		use frame_support::{Blake2_128Concat, StorageHasher};
+
+		// The leading fields of `pallet_assets::AssetDetails`: owner, issuer, admin,
+		// freezer, supply, deposit, min_balance and is_sufficient.
+		type Details<T> = (
+			<T as frame_system::Config>::AccountId,
+			<T as frame_system::Config>::AccountId,
+			<T as frame_system::Config>::AccountId,
+			<T as frame_system::Config>::AccountId,
+			<T as pallet_assets::Config>::Balance,
+			<<T as pallet_assets::Config>::Currency as Currency<<T as frame_system::Config>::AccountId>>::Balance,
+			<T as pallet_assets::Config>::Balance,
+			bool,
+		);
+		let pallet = match T::PalletInfo::name::<pallet_assets::Pallet<T>>() {
+			Some(pallet) => pallet,
+			None => return false,
+		};
+		frame_support::storage::migration::get_storage_value::<Details<T>>(
+			pallet.as_bytes(),
+			b"Asset",
+			&asset.using_encoded(Blake2_128Concat::hash),
+		).map_or(false, |details| details.7)
+	}
+
+	#[cfg(feature = "runtime-benchmarks")]
+	fn create_sufficient() -> T::AssetId {
+		use frame_support::traits::EnsureOrigin;
+		use sp_runtime::traits::{One, StaticLookup};
+
+		let asset = T::AssetId::default();
+		pallet_assets::Pallet::<T>::force_create(
+			T::ForceOrigin::successful_origin(),
+			asset,
+			T::Lookup::unlookup(T::AccountId::default()),
+			true,
+			One::one(),
+		).expect("the asset does not exist yet; qed");
+		asset
+	}
+}
+
+decl_storage! {
+	trait Store for Module<T: Config> as AssetFeePayment {
+		/// Rate of each asset the fees can be paid in, in units of the asset per unit of MATH.
+		pub FeeRates get(fn fee_rate): map hasher(blake2_128_concat) AssetIdOf<T> => Option<FixedU128>;
+		/// Account allowed to update the rates besides Root.
+		pub Oracle get(fn oracle): Option<T::AccountId>;
+	}
+}
+
+decl_event! {
+	pub enum Event<T> where
+		AccountId = <T as frame_system::Config>::AccountId,
+		AssetId = AssetIdOf<T>,
+		AssetBalance = AssetBalanceOf<T>,
+	{
+		/// The rate of an asset was set, the fees can be paid in it. \[asset, rate\]
+		FeeRateSet(AssetId, FixedU128),
+		/// The fees can no longer be paid in an asset. \[asset\]
+		FeeAssetRemoved(AssetId),
+		/// The fee oracle was set. \[oracle\]
+		OracleSet(Option<AccountId>),
+		/// A transaction fee was paid in an asset. \[who, asset, fee\]
+		AssetFeePaid(AccountId, AssetId, AssetBalance),
+	}
+}
+
+decl_error! {
+	pub enum Error for Module<T: Config> {
+		/// The fees cannot be paid in the asset.
+		NotFeeAsset,
+		/// The rate of an asset cannot be zero.
+		ZeroRate,
+		/// The asset is not sufficient, accounts cannot hold it without MATH.
+		NotSufficient,
+	}
+}
+
+decl_module! {
+	pub struct Module<T: Config> for enum Call where origin: T::Origin {
+		type Error = Error<T>;
+
+		fn deposit_event() = default;
+
+		/// Allow the fees to be paid in `asset`, at `rate` units of the asset per unit of MATH.
+		///
+		/// The dispatch origin for this call must be _Root_.
+		#[weight = T::WeightInfo::set_fee_asset()]
+		fn set_fee_asset(origin, asset: AssetIdOf<T>, rate: FixedU128) {
+			ensure_root(origin)?;
+			ensure!(!rate.is_zero(), Error::<T>::ZeroRate);
+			ensure!(T::SufficientAssets::is_sufficient(asset), Error::<T>::NotSufficient);
+
+			FeeRates::<T>::insert(asset, rate);
+			Self::deposit_event(RawEvent::FeeRateSet(asset, rate));
+		}
+
+		/// Stop the fees from being paid in `asset`.
+		///
+		/// The dispatch origin for this call must be _Root_.
+		#[weight = T::WeightInfo::remove_fee_asset()]
+		fn remove_fee_asset(origin, asset: AssetIdOf<T>) {
+			ensure_root(origin)?;
+			ensure!(FeeRates::<T>::contains_key(asset), Error::<T>::NotFeeAsset);
+
+			FeeRates::<T>::remove(asset);
+			Self::deposit_event(RawEvent::FeeAssetRemoved(asset));
+		}
+
+		/// Set the account allowed to update the rates, or none.
+		///
+		/// The dispatch origin for this call must be _Root_.
+		#[weight = T::WeightInfo::set_oracle()]
+		fn set_oracle(origin, oracle: Option<T::AccountId>) {
+			ensure_root(origin)?;
+
+			match oracle.clone() {
+				Some(oracle) => Oracle::<T>::put(oracle),
+				None => Oracle::<T>::kill(),
+			}
+			Self::deposit_event(RawEvent::OracleSet(oracle));
+		}
+
+		/// Update the rate of `asset`, which the fees can already be paid in.
+		///
+		/// The dispatch origin for this call must be _Root_ or _Signed_ by the fee oracle.
+		#[weight = T::WeightInfo::update_rate()]
+		fn update_rate(origin, asset: AssetIdOf<T>, rate: FixedU128) {
+			Self::ensure_rate_origin(origin)?;
+			ensure!(FeeRates::<T>::contains_key(asset), Error::<T>::NotFeeAsset);
+			ensure!(!rate.is_zero(), Error::<T>::ZeroRate);
+
+			FeeRates::<T>::insert(asset, rate);
+			Self::deposit_event(RawEvent::FeeRateSet(asset, rate));
+		}
+	}
+}
+
+impl<T: Config> Module<T> {
+	fn ensure_rate_origin(origin: T::Origin) -> DispatchResult {
+		match origin.into() {
+			Ok(RawOrigin::Root) => Ok(()),
+			Ok(RawOrigin::Signed(who)) if Self::oracle().as_ref() == Some(&who) => Ok(()),
+			_ => Err(DispatchError::BadOrigin),
+		}
+	}
+
+	/// `fee` converted to `asset` at `rate`.
+	fn convert(rate: FixedU128, fee: BalanceOf<T>) -> AssetBalanceOf<T> {
+		rate.saturating_mul_int(fee.saturated_into::<u128>()).saturated_into()
+	}
+
+	/// `fee` in MATH converted to `asset`, `None` if the fees cannot be paid in it.
+	pub fn asset_fee(asset: AssetIdOf<T>, fee: BalanceOf<T>) -> Option<AssetBalanceOf<T>> {
+		Self::fee_rate(asset).map(|rate| Self::convert(rate, fee))
+	}
+
+	/// Whether `who` can pay `fee` in `asset` and keep the minimum balance of the asset.
+	fn can_pay(asset: AssetIdOf<T>, who: &T::AccountId, fee: AssetBalanceOf<T>) -> bool {
+		<T::Assets as fungibles::Inspect<_>>::can_withdraw(asset, who, fee) == WithdrawConsequence::Success
+	}
+
+	/// Whether `amount` of `asset` can be minted to `who`.
+	fn can_credit(asset: AssetIdOf<T>, who: &T::AccountId, amount: AssetBalanceOf<T>) -> bool {
+		<T::Assets as fungibles::Inspect<_>>::can_deposit(asset, who, amount) == DepositConsequence::Success
+	}
+
+	/// `details` of a fee in MATH converted to `asset`, `None` if the fees cannot be paid in it.
+	pub fn quote_fee_details(
+		asset: AssetIdOf<T>,
+		details: FeeDetails<BalanceOf<T>>,
+	) -> Option<FeeDetails<AssetBalanceOf<T>>> {
+		let rate = Self::fee_rate(asset)?;
+		Some(FeeDetails {
+			inclusion_fee: details.inclusion_fee.map(|fee| InclusionFee {
+				base_fee: Self::convert(rate, fee.base_fee),
+				len_fee: Self::convert(rate, fee.len_fee),
+				adjusted_weight_fee: Self::convert(rate, fee.adjusted_weight_fee),
+			}),
+			tip: Self::convert(rate, details.tip),
+		})
+	}
+}
+
+/// Fee paid before the dispatch of a transaction.
+pub enum FeePayment<T: Config> {
+	/// Paid in MATH by `ChargeTransactionPayment`.
+	Native((BalanceOf<T>, T::AccountId, LiquidityInfoOf<T>)),
+	/// `paid` burnt from `who`, converted at `rate`.
+	Asset {
+		who: T::AccountId,
+		asset: AssetIdOf<T>,
+		rate: FixedU128,
+		paid: AssetBalanceOf<T>,
+		tip: BalanceOf<T>,
+	},
+}
+
+/// Pays the fee of a transaction, and its `tip`, in MATH or in `asset`.
+#[derive(Encode, Decode, Clone, Eq, PartialEq)]
+pub struct ChargeAssetTxPayment<T: Config> {
+	#[codec(compact)]
+	pub tip: BalanceOf<T>,
+	pub asset: Option<AssetIdOf<T>>,
+}
+
+impl<T: Config> ChargeAssetTxPayment<T> {
+	pub fn new(tip: BalanceOf<T>, asset: Option<AssetIdOf<T>>) -> Self {
+		Self { tip, asset }
+	}
+}
+
+impl<T: Config> sp_std::fmt::Debug for ChargeAssetTxPayment<T> {
+	#[cfg(feature = "std")]
+	fn fmt(&self, f: &mut sp_std::fmt::Formatter) -> sp_std::fmt::Result {
+		write!(f, "ChargeAssetTxPayment<{:?}, {:?}>", self.tip, self.asset)
+	}
+	#[cfg(not(feature = "std"))]
+	fn fmt(&self, _: &mut sp_std::fmt::Formatter) -> sp_std::fmt::Result {
+		Ok(())
+	}
+}
+
+impl<T: Config + Send + Sync> SignedExtension for ChargeAssetTxPayment<T> where
+	BalanceOf<T>: Send + Sync + From<u64> + FixedPointOperand,
+	T::Call: Dispatchable<Info=DispatchInfo, PostInfo=PostDispatchInfo>,
+{
+	const IDENTIFIER: &'static str = "ChargeAssetTxPayment";
+	type AccountId = T::AccountId;
+	type Call = T::Call;
+	type AdditionalSigned = ();
+	type Pre = Option<FeePayment<T>>;
+
+	fn additional_signed(&self) -> Result<(), TransactionValidityError> {
+		Ok(())
+	}
+
+	fn validate(
+		&self,
+		who: &Self::AccountId,
+		call: &Self::Call,
+		info: &DispatchInfoOf<Self::Call>,
+		len: usize,
+	) -> TransactionValidity {
+		let asset = match self.asset {
+			Some(asset) => asset,
+			None => return ChargeTransactionPayment::<T>::from(self.tip).validate(who, call, info, len),
+		};
+
+		let fee = pallet_transaction_payment::Module::<T>::compute_fee(len as u32, info, self.tip);
+		let asset_fee = Module::<T>::asset_fee(asset, fee).ok_or(InvalidTransaction::Payment)?;
+		if !Module::<T>::can_pay(asset, who, asset_fee) {
+			return InvalidTransaction::Payment.into();
+		}
+
+		Ok(ValidTransaction {
+			priority: fee.saturated_into::<TransactionPriority>(),
+			..Default::default()
+		})
+	}
+
+	fn pre_dispatch(
+		self,
+		who: &Self::AccountId,
+		call: &Self::Call,
+		info: &DispatchInfoOf<Self::Call>,
+		len: usize,
+	) -> Result<Self::Pre, TransactionValidityError> {
+		let asset = match self.asset {
+			Some(asset) => asset,
+			None => return ChargeTransactionPayment::<T>::from(self.tip)
+				.pre_dispatch(who, call, info, len)
+				.map(|pre| Some(FeePayment::Native(pre))),
+		};
+
+		let rate = Module::<T>::fee_rate(asset).ok_or(InvalidTransaction::Payment)?;
+		let fee = pallet_transaction_payment::Module::<T>::compute_fee(len as u32, info, self.tip);
+		let fee = Module::<T>::convert(rate, fee);
+		// The fee receiver may have to be credited the whole fee, see `post_dispatch`.
+		if !Module::<T>::can_pay(asset, who, fee)
+			|| (!fee.is_zero() && !Module::<T>::can_credit(asset, &T::FeeReceiver::get(), fee))
+		{
+			return Err(InvalidTransaction::Payment.into());
+		}
+		let paid = <T::Assets as fungibles::Mutate<_>>::burn_from(asset, who, fee)
+			.map_err(|_| InvalidTransaction::Payment)?;
+
+		Ok(Some(FeePayment::Asset { who: who.clone(), asset, rate, paid, tip: self.tip }))
+	}
+
+	fn post_dispatch(
+		pre: Self::Pre,
+		info: &DispatchInfoOf<Self::Call>,
+		post_info: &PostDispatchInfoOf<Self::Call>,
+		len: usize,
+		result: &DispatchResult,
+	) -> Result<(), TransactionValidityError> {
+		match pre {
+			Some(FeePayment::Native(pre)) =>
+				ChargeTransactionPayment::<T>::post_dispatch(pre, info, post_info, len, result),
+			Some(FeePayment::Asset { who, asset, rate, paid, tip }) => {
+				let fee = pallet_transaction_payment::Module::<T>::compute_actual_fee(len as u32, info, post_info, tip);
+				// Converted at the rate it was paid at, the rate may have changed since.
+				let mut actual = Module::<T>::convert(rate, fee).min(paid);
+				let receiver = T::FeeReceiver::get();
+
+				// A refund which cannot be minted back, or a fee the receiver cannot take
+				// alone, goes to the receiver as part of the fee.
+				if actual != paid && (
+					!Module::<T>::can_credit(asset, &who, paid - actual)
+						|| (!actual.is_zero() && !Module::<T>::can_credit(asset, &receiver, actual))
+				) {
+					actual = paid;
+				}
+
+				let refund = paid - actual;
+				if !refund.is_zero() {
+					<T::Assets as fungibles::Mutate<_>>::mint_into(asset, &who, refund)
+						.map_err(|_| InvalidTransaction::Payment)?;
+				}
+				if !actual.is_zero() {
+					<T::Assets as fungibles::Mutate<_>>::mint_into(asset, &receiver, actual)
+						.map_err(|_| InvalidTransaction::Payment)?;
+				}
+
+				Module::<T>::deposit_event(RawEvent::AssetFeePaid(who, asset, actual));
+				Ok(())
+			},
+			None => Ok(()),
+		}
+	}
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+	use super::*;
+	use crate as pallet_asset_fee_payment;
+	use frame_support::{
+		assert_noop, assert_ok, construct_runtime, parameter_types,
+		weights::{DispatchClass, Pays, Weight},
+	};
+	use sp_core::H256;
+	use sp_runtime::{testing::Header, traits::{BlakeTwo256, IdentityLookup}};
+	use pallet_transaction_payment::CurrencyAdapter;
+
+	type UncheckedExtrinsic = frame_system::mocking::MockUncheckedExtrinsic<Test>;
+	type Block = frame_system::mocking::MockBlock<Test>;
+
+	construct_runtime!(
+		pub enum Test where
+			Block = Block,
+			NodeBlock = Block,
+			UncheckedExtrinsic = UncheckedExtrinsic,
+		{
+			System: frame_system::{Pallet, Call, Config, Storage, Event<T>},
+			Balances: pallet_balances::{Pallet, Call, Storage, Config<T>, Event<T>},
+			TransactionPayment: pallet_transaction_payment::{Pallet, Storage},
+			Assets: pallet_assets::{Pallet, Call, Storage, Event<T>},
+			AssetFeePayment: pallet_asset_fee_payment::{Pallet, Call, Storage, Event<T>},
+		}
+	);
+
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		// No base fee, a fee is its length plus its weight.
+		pub BlockWeights: frame_system::limits::BlockWeights = frame_system::limits::BlockWeights::builder()
+			.base_block(0)
+			.for_class(DispatchClass::all(), |weights| weights.base_extrinsic = 0)
+			.for_class(DispatchClass::non_mandatory(), |weights| weights.max_total = 1024.into())
+			.build_or_panic();
+		pub const ExistentialDeposit: u64 = 1;
+		pub const TransactionByteFee: u64 = 1;
+		pub const AssetDeposit: u64 = 1;
+		pub const ApprovalDeposit: u64 = 1;
+		pub const StringLimit: u32 = 50;
+		pub const MetadataDepositBase: u64 = 1;
+		pub const MetadataDepositPerByte: u64 = 1;
+		pub const FeeReceiver: u64 = RECEIVER;
+	}
+
+	impl frame_system::Config for Test {
+		type BaseCallFilter = ();
+		type BlockWeights = BlockWeights;
+		type BlockLength = ();
+		type DbWeight = ();
+		type Origin = Origin;
+		type Call = Call;
+		type Index = u64;
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = Event;
+		type BlockHashCount = BlockHashCount;
+		type Version = ();
+		type PalletInfo = PalletInfo;
+		type AccountData = pallet_balances::AccountData<u64>;
+		type OnNewAccount = ();
+		type OnKilledAccount = ();
+		type SystemWeightInfo = ();
+		type SS58Prefix = ();
+	}
+
+	impl pallet_balances::Config for Test {
+		type MaxLocks = ();
+		type Balance = u64;
+		type Event = Event;
+		type DustRemoval = ();
+		type ExistentialDeposit = ExistentialDeposit;
+		type AccountStore = System;
+		type WeightInfo = ();
+	}
+
+	impl pallet_transaction_payment::Config for Test {
+		type OnChargeTransaction = CurrencyAdapter<Balances, ()>;
+		type TransactionByteFee = TransactionByteFee;
+		type WeightToFee = frame_support::weights::IdentityFee<u64>;
+		type FeeMultiplierUpdate = ();
+	}
+
+	impl pallet_assets::Config for Test {
+		type Event = Event;
+		type Balance = u64;
+		type AssetId = u32;
+		type Currency = Balances;
+		type ForceOrigin = frame_system::EnsureRoot<u64>;
+		type AssetDeposit = AssetDeposit;
+		type MetadataDepositBase = MetadataDepositBase;
+		type MetadataDepositPerByte = MetadataDepositPerByte;
+		type ApprovalDeposit = ApprovalDeposit;
+		type StringLimit = StringLimit;
+		type Freezer = ();
+		type Extra = ();
+		type WeightInfo = ();
+	}
+
+	impl Config for Test {
+		type Event = Event;
+		type Assets = Assets;
+		type FeeReceiver = FeeReceiver;
+		type SufficientAssets = SufficientAssetsOf<Test>;
+		type WeightInfo = ();
+	}
+
+	const ASSET: u32 = 1;
+	const INSUFFICIENT: u32 = 2;
+	const MIN_BALANCE: u64 = 10;
+	const ALICE: u64 = 1;
+	const RECEIVER: u64 = 99;
+
+	pub(crate) fn new_test_ext() -> sp_io::TestExternalities {
+		let storage = frame_system::GenesisConfig::default().build_storage::<Test>().unwrap();
+		let mut ext = sp_io::TestExternalities::from(storage);
+		ext.execute_with(|| {
+			System::set_block_number(1);
+			assert_ok!(Assets::force_create(Origin::root(), ASSET, ALICE, true, MIN_BALANCE));
+			assert_ok!(Assets::force_create(Origin::root(), INSUFFICIENT, ALICE, false, MIN_BALANCE));
+		});
+		ext
+	}
+
+	/// Alice holds `balance` of `ASSET`, which pays fees at 2 units per unit of MATH.
+	fn fee_asset_ext(balance: u64) -> sp_io::TestExternalities {
+		let mut ext = new_test_ext();
+		ext.execute_with(|| {
+			assert_ok!(<Assets as fungibles::Mutate<_>>::mint_into(ASSET, &ALICE, balance));
+			assert_ok!(AssetFeePayment::set_fee_asset(Origin::root(), ASSET, FixedU128::saturating_from_integer(2)));
+		});
+		ext
+	}
+
+	fn balance(who: u64) -> u64 {
+		<Assets as fungibles::Inspect<_>>::balance(ASSET, &who)
+	}
+
+	fn call() -> Call {
+		Call::Balances(pallet_balances::Call::transfer(2, 1))
+	}
+
+	fn info(weight: Weight) -> DispatchInfo {
+		DispatchInfo { weight, ..Default::default() }
+	}
+
+	fn post_info(actual_weight: Weight) -> PostDispatchInfo {
+		PostDispatchInfo { actual_weight: Some(actual_weight), pays_fee: Pays::Yes }
+	}
+
+	/// Pays for a call of `weight` and `len` in `ASSET`, which weighs `actual_weight`.
+	fn pay(weight: Weight, actual_weight: Weight, len: usize) -> Result<(), TransactionValidityError> {
+		let pre = ChargeAssetTxPayment::<Test>::new(0, Some(ASSET)).pre_dispatch(&ALICE, &call(), &info(weight), len)?;
+		ChargeAssetTxPayment::<Test>::post_dispatch(pre, &info(weight), &post_info(actual_weight), len, &Ok(()))
+	}
+
+	#[test]
+	fn only_sufficient_assets_pay_fees() {
+		new_test_ext().execute_with(|| {
+			assert_noop!(
+				AssetFeePayment::set_fee_asset(Origin::root(), INSUFFICIENT, FixedU128::saturating_from_integer(1)),
+				Error::<Test>::NotSufficient,
+			);
+			assert_noop!(
+				AssetFeePayment::set_fee_asset(Origin::root(), 3, FixedU128::saturating_from_integer(1)),
+				Error::<Test>::NotSufficient,
+			);
+			assert_ok!(AssetFeePayment::set_fee_asset(Origin::root(), ASSET, FixedU128::saturating_from_integer(1)));
+		});
+	}
+
+	#[test]
+	fn withdraws_the_fee_and_refunds_the_unused_part() {
+		fee_asset_ext(1_000).execute_with(|| {
+			assert_ok!(<Assets as fungibles::Mutate<_>>::mint_into(ASSET, &RECEIVER, 100));
+
+			// A fee of 10 + 100 MATH, 220 in the asset.
+			let pre = ChargeAssetTxPayment::<Test>::new(0, Some(ASSET))
+				.pre_dispatch(&ALICE, &call(), &info(100), 10)
+				.unwrap();
+			assert_eq!(balance(ALICE), 780);
+
+			// Only 10 + 50 MATH are charged, 120 in the asset.
+			assert_ok!(ChargeAssetTxPayment::<Test>::post_dispatch(pre, &info(100), &post_info(50), 10, &Ok(())));
+			assert_eq!(balance(ALICE), 880);
+			assert_eq!(balance(RECEIVER), 220);
+			System::assert_last_event(Event::pallet_asset_fee_payment(RawEvent::AssetFeePaid(ALICE, ASSET, 120)));
+		});
+	}
+
+	#[test]
+	fn keeps_the_minimum_balance_of_the_payer() {
+		// 220 leaves 5, below the minimum balance.
+		fee_asset_ext(225).execute_with(|| {
+			let extension = ChargeAssetTxPayment::<Test>::new(0, Some(ASSET));
+			assert!(extension.validate(&ALICE, &call(), &info(100), 10).is_err());
+			assert_eq!(pay(100, 100, 10), Err(InvalidTransaction::Payment.into()));
+			assert_eq!(balance(ALICE), 225);
+		});
+		fee_asset_ext(230).execute_with(|| {
+			assert_ok!(pay(100, 100, 10));
+			assert_eq!(balance(ALICE), 10);
+			assert_eq!(balance(RECEIVER), 220);
+		});
+	}
+
+	#[test]
+	fn refund_dust_goes_to_the_receiver() {
+		fee_asset_ext(1_000).execute_with(|| {
+			let pre = ChargeAssetTxPayment::<Test>::new(0, Some(ASSET))
+				.pre_dispatch(&ALICE, &call(), &info(100), 10)
+				.unwrap();
+			// The call empties the account of Alice.
+			assert_ok!(<Assets as fungibles::Mutate<_>>::burn_from(ASSET, &ALICE, 780));
+
+			// A refund of 4, below the minimum balance of the emptied account.
+			assert_ok!(ChargeAssetTxPayment::<Test>::post_dispatch(pre, &info(100), &post_info(98), 10, &Ok(())));
+			assert_eq!(balance(ALICE), 0);
+			assert_eq!(balance(RECEIVER), 220);
+		});
+	}
+
+	#[test]
+	fn receiver_without_account_takes_the_whole_fee() {
+		fee_asset_ext(1_000).execute_with(|| {
+			// A fee of 2 + 100 MATH, 204 in the asset, of which only 8 are charged:
+			// below the minimum balance the receiver needs to be credited alone.
+			assert_ok!(pay(100, 2, 2));
+			assert_eq!(balance(ALICE), 796);
+			assert_eq!(balance(RECEIVER), 204);
+		});
+	}
+
+	#[test]
+	fn fee_the_receiver_cannot_take_is_not_withdrawn() {
+		fee_asset_ext(1_000).execute_with(|| {
+			// A fee of 1 + 2 MATH, 6 in the asset, below the minimum balance.
+			assert_eq!(pay(2, 2, 1), Err(InvalidTransaction::Payment.into()));
+			assert_eq!(balance(ALICE), 1_000);
+			assert_eq!(balance(RECEIVER), 0);
+		});
+	}
+
+	#[test]
+	fn rejects_assets_fees_cannot_be_paid_in() {
+		fee_asset_ext(1_000).execute_with(|| {
+			assert_ok!(AssetFeePayment::remove_fee_asset(Origin::root(), ASSET));
+			assert_eq!(pay(100, 100, 10), Err(InvalidTransaction::Payment.into()));
+			assert_eq!(balance(ALICE), 1_000);
+		});
+	}
+}
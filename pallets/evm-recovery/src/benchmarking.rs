@@ -0,0 +1,51 @@
+//! Benchmarks of the EVM recovery pallet.
+
+#![cfg(feature = "runtime-benchmarks")]
+
+use super::*;
+use frame_benchmarking::{account, benchmarks, impl_benchmark_test_suite, whitelisted_caller};
+use frame_support::traits::Get;
+use frame_system::RawOrigin;
+use sp_runtime::traits::Bounded;
+
+const SEED: u32 = 0;
+
+fn funded_address<T: Config>() -> (secp256k1::SecretKey, H160, T::AccountId) {
+	let secret = secp256k1::SecretKey::parse(&[1u8; 32]).expect("valid secret key; qed");
+	let address = eth_address(&secret);
+	let account = T::AddressMapping::into_account_id(address);
+	T::Currency::make_free_balance_be(&account, BalanceOf::<T>::max_value() / 2u32.into());
+	(secret, address, account)
+}
+
+benchmarks! {
+	create_recovery {
+		let f in 1 .. T::MaxFriends::get() as u32;
+
+		let (secret, address, account) = funded_address::<T>();
+		let mut friends: Vec<T::AccountId> = (0..f).map(|i| account("friend", i, SEED)).collect();
+		friends.sort();
+		let delay_period = T::BlockNumber::from(10u32);
+		let message = Module::<T>::recovery_message(&account, &friends, f as u16, delay_period);
+		let signature = eth_sign(&secret, &message);
+		let caller: T::AccountId = whitelisted_caller();
+	}: _(RawOrigin::Signed(caller), address, friends, f as u16, delay_period, signature)
+	verify {
+		assert!(pallet_recovery::Module::<T>::recovery_config(&account).is_some());
+	}
+
+	take_over {
+		let (_, address, account) = funded_address::<T>();
+		let rescuer: T::AccountId = whitelisted_caller();
+		pallet_recovery::Proxy::<T>::insert(&rescuer, &account);
+	}: _(RawOrigin::Signed(rescuer), address)
+	verify {
+		assert!(T::Currency::free_balance(&account).is_zero());
+	}
+}
+
+impl_benchmark_test_suite!(
+	Module,
+	crate::tests::new_test_ext(),
+	crate::tests::Test,
+);
@@ -0,0 +1,415 @@
+//! # EVM Recovery Pallet
+//!
+//! Social recovery of the accounts Ethereum addresses are mapped to.
+//!
+//! Such an account has no key of its own, so the owner of the address signs the
+//! recovery configuration with the Ethereum key (`personal_sign`) and anyone can
+//! submit it through `create_recovery`. Friends then initiate, vouch for and claim
+//! the recovery of the mapped account with `pallet_recovery`, as for any account.
+//! Once the recovery is claimed, `take_over` moves the balance of the mapped account
+//! to the rescuer and hands over the other resources of the address through
+//! `Config::OnRecovered`.
+//!
+//! The signed configuration names the chain by its genesis hash and the mapped
+//! account by its nonce, so that it is only valid once and on a single chain.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use sp_std::prelude::*;
+use codec::Encode;
+use frame_support::{
+	decl_module, decl_event, decl_error, ensure,
+	dispatch::DispatchResult,
+	traits::{Currency, ExistenceRequirement},
+	weights::Weight,
+};
+use frame_system::{ensure_signed, RawOrigin};
+use sp_core::{H160, ecdsa};
+use sp_io::hashing::keccak_256;
+use sp_runtime::traits::Zero;
+use pallet_evm::AddressMapping;
+
+mod benchmarking;
+pub mod weights;
+
+pub use weights::WeightInfo;
+
+type BalanceOf<T> = <<T as pallet_recovery::Config>::Currency as Currency<
+	<T as frame_system::Config>::AccountId
+>>::Balance;
+
+/// Prefix of the recovery configuration signed by an address.
+pub const MESSAGE_PREFIX: &[u8] = b"MathChain EVM recovery:";
+
+/// Handler of a recovered address.
+pub trait OnEthereumRecovered<AccountId> {
+	/// `rescuer` took over the account of `address`.
+	fn on_recovered(address: H160, rescuer: &AccountId) -> DispatchResult;
+
+	/// Weight of `on_recovered` at most.
+	fn weight() -> Weight;
+}
+
+impl<AccountId> OnEthereumRecovered<AccountId> for () {
+	fn on_recovered(_address: H160, _rescuer: &AccountId) -> DispatchResult {
+		Ok(())
+	}
+
+	fn weight() -> Weight {
+		0
+	}
+}
+
+pub trait Config: frame_system::Config + pallet_recovery::Config {
+	/// The overarching event type.
+	type Event: From<Event<Self>> + Into<<Self as frame_system::Config>::Event>;
+
+	/// Mapping of the addresses to the accounts holding their EVM balance.
+	type AddressMapping: AddressMapping<Self::AccountId>;
+
+	/// Hands the other resources of a recovered address to its rescuer.
+	type OnRecovered: OnEthereumRecovered<Self::AccountId>;
+
+	/// Weight information for extrinsics in this pallet.
+	type WeightInfo: WeightInfo;
+}
+
+decl_event! {
+	pub enum Event<T> where
+		AccountId = <T as frame_system::Config>::AccountId,
+		Balance = BalanceOf<T>,
+	{
+		/// A recovery was configured for an address. \[address, account\]
+		RecoveryCreated(H160, AccountId),
+		/// A rescuer took over an address. \[address, rescuer, balance\]
+		TakenOver(H160, AccountId, Balance),
+	}
+}
+
+decl_error! {
+	pub enum Error for Module<T: Config> {
+		/// The signature was not made by the address.
+		InvalidSignature,
+		/// The caller has not claimed the recovery of the address.
+		NotRescuer,
+	}
+}
+
+decl_module! {
+	pub struct Module<T: Config> for enum Call where origin: T::Origin {
+		type Error = Error<T>;
+
+		fn deposit_event() = default;
+
+		/// Configure the recovery of the account `address` is mapped to.
+		///
+		/// `signature` is the `personal_sign` signature by `address` of `recovery_message`.
+		/// The deposit of `pallet_recovery` is reserved from the mapped account.
+		///
+		/// The dispatch origin for this call must be _Signed_, by any account.
+		#[weight = T::WeightInfo::create_recovery(friends.len() as u32)]
+		fn create_recovery(
+			origin,
+			address: H160,
+			friends: Vec<T::AccountId>,
+			threshold: u16,
+			delay_period: T::BlockNumber,
+			signature: ecdsa::Signature,
+		) {
+			ensure_signed(origin)?;
+
+			let account = T::AddressMapping::into_account_id(address);
+			let message = Self::recovery_message(&account, &friends, threshold, delay_period);
+			ensure!(eth_recover(&signature, &message) == Some(address), Error::<T>::InvalidSignature);
+
+			pallet_recovery::Module::<T>::create_recovery(
+				RawOrigin::Signed(account.clone()).into(),
+				friends,
+				threshold,
+				delay_period,
+			)?;
+			// The nonce is part of the message, a signature is only used once.
+			frame_system::Pallet::<T>::inc_account_nonce(&account);
+
+			Self::deposit_event(RawEvent::RecoveryCreated(address, account));
+		}
+
+		/// Move the balance of the account `address` is mapped to, and the other resources
+		/// of the address, to the caller.
+		///
+		/// The dispatch origin for this call must be _Signed_, by the account which claimed
+		/// the recovery of the mapped account.
+		#[weight = T::WeightInfo::take_over().saturating_add(T::OnRecovered::weight())]
+		fn take_over(origin, address: H160) {
+			let rescuer = ensure_signed(origin)?;
+
+			let account = T::AddressMapping::into_account_id(address);
+			ensure!(
+				pallet_recovery::Module::<T>::proxy(&rescuer).as_ref() == Some(&account),
+				Error::<T>::NotRescuer,
+			);
+
+			let balance = T::Currency::free_balance(&account);
+			T::Currency::transfer(&account, &rescuer, balance, ExistenceRequirement::AllowDeath)?;
+			T::OnRecovered::on_recovered(address, &rescuer)?;
+
+			Self::deposit_event(RawEvent::TakenOver(address, rescuer, balance));
+		}
+	}
+}
+
+impl<T: Config> Module<T> {
+	/// Message an address signs to configure the recovery of `account`, on the chain
+	/// of this genesis hash.
+	pub fn recovery_message(
+		account: &T::AccountId,
+		friends: &[T::AccountId],
+		threshold: u16,
+		delay_period: T::BlockNumber,
+	) -> Vec<u8> {
+		let genesis_hash = frame_system::Pallet::<T>::block_hash(T::BlockNumber::zero());
+		let nonce = frame_system::Pallet::<T>::account_nonce(account);
+		let mut message = MESSAGE_PREFIX.to_vec();
+		message.extend((genesis_hash, account, nonce, friends, threshold, delay_period).encode());
+		message
+	}
+}
+
+/// Address which signed `message` with `personal_sign`.
+fn eth_recover(signature: &ecdsa::Signature, message: &[u8]) -> Option<H160> {
+	let mut prefixed = format_prefix(message.len());
+	prefixed.extend_from_slice(message);
+	let signature: &[u8; 65] = signature.as_ref();
+	let public = sp_io::crypto::secp256k1_ecdsa_recover(signature, &keccak_256(&prefixed)).ok()?;
+	Some(H160::from_slice(&keccak_256(&public)[12..]))
+}
+
+/// `personal_sign` signature of `message` by `secret`.
+#[cfg(any(test, feature = "runtime-benchmarks"))]
+fn eth_sign(secret: &secp256k1::SecretKey, message: &[u8]) -> ecdsa::Signature {
+	let mut prefixed = format_prefix(message.len());
+	prefixed.extend_from_slice(message);
+	let hash = secp256k1::Message::parse(&keccak_256(&prefixed));
+	let (signature, recovery_id) = secp256k1::sign(&hash, secret);
+	let mut raw = [0u8; 65];
+	raw[..64].copy_from_slice(&signature.serialize());
+	raw[64] = recovery_id.serialize();
+	ecdsa::Signature::from_raw(raw)
+}
+
+/// Address of the key `secret`.
+#[cfg(any(test, feature = "runtime-benchmarks"))]
+fn eth_address(secret: &secp256k1::SecretKey) -> H160 {
+	let public = secp256k1::PublicKey::from_secret_key(secret).serialize();
+	H160::from_slice(&keccak_256(&public[1..])[12..])
+}
+
+/// `personal_sign` prefix of a message of `len` bytes.
+fn format_prefix(len: usize) -> Vec<u8> {
+	let mut prefix = b"\x19Ethereum Signed Message:\n".to_vec();
+	let mut digits = Vec::new();
+	let mut len = len;
+	loop {
+		digits.push(b'0' + (len % 10) as u8);
+		len /= 10;
+		if len == 0 {
+			break;
+		}
+	}
+	prefix.extend(digits.into_iter().rev());
+	prefix
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+	use super::*;
+	use frame_support::{assert_noop, assert_ok, construct_runtime, parameter_types};
+	use sp_core::H256;
+	use sp_runtime::{testing::Header, traits::{BlakeTwo256, IdentityLookup}};
+
+	type UncheckedExtrinsic = frame_system::mocking::MockUncheckedExtrinsic<Test>;
+	type Block = frame_system::mocking::MockBlock<Test>;
+
+	construct_runtime!(
+		pub enum Test where
+			Block = Block,
+			NodeBlock = Block,
+			UncheckedExtrinsic = UncheckedExtrinsic,
+		{
+			System: frame_system::{Pallet, Call, Config, Storage, Event<T>},
+			Balances: pallet_balances::{Pallet, Call, Storage, Config<T>, Event<T>},
+			Recovery: pallet_recovery::{Pallet, Call, Storage, Event<T>},
+			EVMRecovery: super::{Pallet, Call, Event<T>},
+		}
+	);
+
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const ExistentialDeposit: u64 = 1;
+		pub const ConfigDepositBase: u64 = 10;
+		pub const FriendDepositFactor: u64 = 1;
+		pub const MaxFriends: u16 = 3;
+		pub const RecoveryDeposit: u64 = 10;
+	}
+
+	impl frame_system::Config for Test {
+		type BaseCallFilter = ();
+		type BlockWeights = ();
+		type BlockLength = ();
+		type DbWeight = ();
+		type Origin = Origin;
+		type Call = Call;
+		type Index = u64;
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = Event;
+		type BlockHashCount = BlockHashCount;
+		type Version = ();
+		type PalletInfo = PalletInfo;
+		type AccountData = pallet_balances::AccountData<u64>;
+		type OnNewAccount = ();
+		type OnKilledAccount = ();
+		type SystemWeightInfo = ();
+		type SS58Prefix = ();
+	}
+
+	impl pallet_balances::Config for Test {
+		type MaxLocks = ();
+		type Balance = u64;
+		type Event = Event;
+		type DustRemoval = ();
+		type ExistentialDeposit = ExistentialDeposit;
+		type AccountStore = System;
+		type WeightInfo = ();
+	}
+
+	impl pallet_recovery::Config for Test {
+		type Event = Event;
+		type Call = Call;
+		type Currency = Balances;
+		type ConfigDepositBase = ConfigDepositBase;
+		type FriendDepositFactor = FriendDepositFactor;
+		type MaxFriends = MaxFriends;
+		type RecoveryDeposit = RecoveryDeposit;
+	}
+
+	/// Maps an address to the account of its last 8 bytes.
+	pub struct TruncatedAddressMapping;
+
+	impl AddressMapping<u64> for TruncatedAddressMapping {
+		fn into_account_id(address: H160) -> u64 {
+			let mut bytes = [0u8; 8];
+			bytes.copy_from_slice(&address[12..]);
+			u64::from_be_bytes(bytes)
+		}
+	}
+
+	impl Config for Test {
+		type Event = Event;
+		type AddressMapping = TruncatedAddressMapping;
+		type OnRecovered = ();
+		type WeightInfo = ();
+	}
+
+	const FRIENDS: [u64; 3] = [1, 2, 3];
+	const RESCUER: u64 = 4;
+
+	pub(crate) fn new_test_ext() -> sp_io::TestExternalities {
+		let mut storage = frame_system::GenesisConfig::default().build_storage::<Test>().unwrap();
+		pallet_balances::GenesisConfig::<Test> {
+			balances: vec![(1, 100), (2, 100), (3, 100), (RESCUER, 100)],
+		}.assimilate_storage(&mut storage).unwrap();
+		let mut ext = sp_io::TestExternalities::from(storage);
+		ext.execute_with(|| System::set_block_number(1));
+		ext
+	}
+
+	fn owner() -> (secp256k1::SecretKey, H160, u64) {
+		let secret = secp256k1::SecretKey::parse(&[7u8; 32]).unwrap();
+		let address = eth_address(&secret);
+		let account = TruncatedAddressMapping::into_account_id(address);
+		let _ = Balances::deposit_creating(&account, 1_000);
+		(secret, address, account)
+	}
+
+	fn signed_config(secret: &secp256k1::SecretKey, account: u64) -> ecdsa::Signature {
+		eth_sign(secret, &EVMRecovery::recovery_message(&account, &FRIENDS, 2, 10))
+	}
+
+	#[test]
+	fn create_recovery_checks_the_signature_of_the_address() {
+		new_test_ext().execute_with(|| {
+			let (secret, address, account) = owner();
+			let other = secp256k1::SecretKey::parse(&[8u8; 32]).unwrap();
+
+			assert_noop!(
+				EVMRecovery::create_recovery(Origin::signed(1), address, FRIENDS.to_vec(), 2, 10, signed_config(&other, account)),
+				Error::<Test>::InvalidSignature,
+			);
+			assert_ok!(EVMRecovery::create_recovery(
+				Origin::signed(1), address, FRIENDS.to_vec(), 2, 10, signed_config(&secret, account),
+			));
+			assert!(Recovery::recovery_config(&account).is_some());
+			assert_eq!(Balances::reserved_balance(&account), 13);
+		});
+	}
+
+	#[test]
+	fn signed_configurations_are_not_replayed() {
+		new_test_ext().execute_with(|| {
+			let (secret, address, account) = owner();
+			let signature = signed_config(&secret, account);
+
+			assert_ok!(EVMRecovery::create_recovery(
+				Origin::signed(1), address, FRIENDS.to_vec(), 2, 10, signature.clone(),
+			));
+			assert_ok!(Recovery::remove_recovery(Origin::signed(account)));
+			assert_noop!(
+				EVMRecovery::create_recovery(Origin::signed(1), address, FRIENDS.to_vec(), 2, 10, signature),
+				Error::<Test>::InvalidSignature,
+			);
+		});
+	}
+
+	#[test]
+	fn signed_configurations_are_bound_to_the_chain() {
+		new_test_ext().execute_with(|| {
+			let (secret, address, account) = owner();
+			let signature = signed_config(&secret, account);
+
+			frame_system::BlockHash::<Test>::insert(0, H256::repeat_byte(1));
+			assert_noop!(
+				EVMRecovery::create_recovery(Origin::signed(1), address, FRIENDS.to_vec(), 2, 10, signature),
+				Error::<Test>::InvalidSignature,
+			);
+		});
+	}
+
+	#[test]
+	fn rescuer_takes_over_after_claiming_the_recovery() {
+		new_test_ext().execute_with(|| {
+			let (secret, address, account) = owner();
+			assert_ok!(EVMRecovery::create_recovery(
+				Origin::signed(1), address, FRIENDS.to_vec(), 2, 10, signed_config(&secret, account),
+			));
+
+			assert_ok!(Recovery::initiate_recovery(Origin::signed(RESCUER), account));
+			assert_ok!(Recovery::vouch_recovery(Origin::signed(1), account, RESCUER));
+			assert_ok!(Recovery::vouch_recovery(Origin::signed(2), account, RESCUER));
+			assert_noop!(EVMRecovery::take_over(Origin::signed(RESCUER), address), Error::<Test>::NotRescuer);
+
+			System::set_block_number(11);
+			assert_ok!(Recovery::claim_recovery(Origin::signed(RESCUER), account));
+			assert_ok!(EVMRecovery::take_over(Origin::signed(RESCUER), address));
+
+			// The configuration deposit stays reserved on the mapped account.
+			assert_eq!(Balances::free_balance(&account), 0);
+			assert_eq!(Balances::free_balance(&RESCUER), 90 + 1_000 - 13);
+			assert_noop!(EVMRecovery::take_over(Origin::signed(1), address), Error::<Test>::NotRescuer);
+		});
+	}
+}
@@ -0,0 +1,50 @@
+//! Weights of the EVM recovery pallet.
+//!
+//! Derived from the storage accesses of each call and from `benchmarking`, to be
+//! regenerated with `benchmark --pallet pallet_evm_recovery --extrinsic '*'`.
+
+#![allow(unused_parens)]
+#![allow(unused_imports)]
+
+use frame_support::{traits::Get, weights::{Weight, constants::RocksDbWeight}};
+use sp_std::marker::PhantomData;
+
+/// Weight functions needed for pallet_evm_recovery.
+pub trait WeightInfo {
+	fn create_recovery(f: u32) -> Weight;
+	fn take_over() -> Weight;
+}
+
+/// Weights for pallet_evm_recovery using the Substrate node and recommended hardware.
+pub struct SubstrateWeight<T>(PhantomData<T>);
+impl<T: frame_system::Config> WeightInfo for SubstrateWeight<T> {
+	// Recovering the signer, then `pallet_recovery::create_recovery`.
+	// Reads: BlockHash, System Account, Recoverable. Writes: System Account, Recoverable.
+	fn create_recovery(f: u32) -> Weight {
+		(150_000_000 as Weight)
+			.saturating_add((1_000_000 as Weight).saturating_mul(f as Weight))
+			.saturating_add(T::DbWeight::get().reads(3 as Weight))
+			.saturating_add(T::DbWeight::get().writes(2 as Weight))
+	}
+	// Reads: Proxy, System Account of both accounts. Writes: System Account of both accounts.
+	fn take_over() -> Weight {
+		(60_000_000 as Weight)
+			.saturating_add(T::DbWeight::get().reads(3 as Weight))
+			.saturating_add(T::DbWeight::get().writes(2 as Weight))
+	}
+}
+
+// For backwards compatibility and tests
+impl WeightInfo for () {
+	fn create_recovery(f: u32) -> Weight {
+		(150_000_000 as Weight)
+			.saturating_add((1_000_000 as Weight).saturating_mul(f as Weight))
+			.saturating_add(RocksDbWeight::get().reads(3 as Weight))
+			.saturating_add(RocksDbWeight::get().writes(2 as Weight))
+	}
+	fn take_over() -> Weight {
+		(60_000_000 as Weight)
+			.saturating_add(RocksDbWeight::get().reads(3 as Weight))
+			.saturating_add(RocksDbWeight::get().writes(2 as Weight))
+	}
+}
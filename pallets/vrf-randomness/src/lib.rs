@@ -0,0 +1,501 @@
+//! # VRF Randomness Pallet
+//!
+//! On-chain randomness out of the VRF outputs of the block authors.
+//!
+//! The author of a block evaluates its sr25519 VRF over the current randomness
+//! and the number of the block, and puts the output with its proof in the block
+//! through the `set_randomness` inherent. The output is verified against the key
+//! of the author and becomes the new randomness. The inherent is required in every
+//! block, so the author can neither choose the output nor leave the randomness as
+//! it is.
+//!
+//! The authorities may change while a block is executed, when a session ends. The
+//! keys of the authorities are kept at the end of every block, and the next block
+//! is checked against this snapshot. `check_inherent` runs on the state of the
+//! parent block, before the author is known, and checks the output against the
+//! keys of all the authorities. `set_randomness` checks it against the key of the
+//! author, the authority of the slot of the block, whose failure invalidates the
+//! block as for any mandatory call.
+//!
+//! The randomness is exposed to the other pallets through `Randomness`, and to
+//! contracts through `RandomnessPrecompile`.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use sp_std::{prelude::*, marker::PhantomData};
+use codec::Encode;
+use frame_support::{
+	decl_module, decl_storage, decl_error, ensure,
+	traits::{Get, Randomness},
+	weights::{DispatchClass, Weight},
+};
+use frame_system::ensure_none;
+use sp_core::{U256, sr25519};
+use sp_runtime::{ConsensusEngineId, traits::{Hash, SaturatedConversion}};
+use sp_inherents::{InherentData, InherentIdentifier, MakeFatalError, ProvideInherent};
+use sp_consensus_vrf::schnorrkel::{VRFOutput, VRFProof};
+use fp_evm::{Context, ExitError, ExitSucceed, Precompile};
+
+/// Identifier of the VRF outputs in the inherent data.
+pub const INHERENT_IDENTIFIER: InherentIdentifier = *b"vrfrandm";
+
+/// Label of the VRF transcript.
+pub const VRF_LABEL: &[u8] = b"MathChain VRF randomness";
+
+/// Context of the randomness made out of a VRF output.
+const RANDOMNESS_CONTEXT: &[u8] = b"MathChain randomness";
+
+/// VRF outputs of the local authority keys, with their proofs.
+///
+/// The node does not know which of its keys authors the block, the runtime
+/// keeps the output of the author.
+pub type InherentType = Vec<(VRFOutput, VRFProof)>;
+
+/// Slot of a block, out of its pre-runtime digests.
+pub trait FindSlot {
+	fn find_slot<'a, I>(digests: I) -> Option<u64> where
+		I: 'a + IntoIterator<Item=(ConsensusEngineId, &'a [u8])>;
+}
+
+pub trait Config: frame_system::Config {
+	/// Slot of the current block, the author is the authority at the slot modulo the
+	/// number of authorities.
+	type FindSlot: FindSlot;
+
+	/// Keys of the authorities, in the order they take turns to author blocks.
+	type Authorities: Get<Vec<sr25519::Public>>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Config> as VrfRandomness {
+		/// Randomness made out of the latest VRF output.
+		pub CurrentRandomness get(fn current_randomness): [u8; 32];
+		/// Block `CurrentRandomness` was set in.
+		pub UpdatedAt get(fn updated_at): T::BlockNumber;
+		/// Keys of the authorities at the end of the last block, which author the next one.
+		pub AuthoritySnapshot get(fn authority_snapshot): Option<Vec<sr25519::Public>>;
+	}
+}
+
+decl_error! {
+	pub enum Error for Module<T: Config> {
+		/// The randomness was already set in this block.
+		AlreadySet,
+		/// The output was not made by the VRF of the block author.
+		InvalidVrfOutput,
+	}
+}
+
+decl_module! {
+	pub struct Module<T: Config> for enum Call where origin: T::Origin {
+		type Error = Error<T>;
+
+		fn on_initialize() -> Weight {
+			// The snapshot taken in `on_finalize`.
+			T::DbWeight::get().writes(1)
+		}
+
+		fn on_finalize() {
+			AuthoritySnapshot::put(T::Authorities::get());
+		}
+
+		fn on_runtime_upgrade() -> Weight {
+			// The block being initialized follows a block executed by a runtime which
+			// took no snapshot.
+			if !AuthoritySnapshot::exists() {
+				AuthoritySnapshot::put(T::Authorities::get());
+			}
+			T::DbWeight::get().reads_writes(2, 1)
+		}
+
+		/// Set the randomness out of the VRF output of the block author.
+		///
+		/// The dispatch origin for this call must be _None_, it is an inherent.
+		#[weight = (50_000_000 + T::DbWeight::get().reads_writes(4, 2), DispatchClass::Mandatory)]
+		fn set_randomness(origin, output: VRFOutput, proof: VRFProof) {
+			ensure_none(origin)?;
+
+			let now = frame_system::Pallet::<T>::block_number();
+			ensure!(Self::updated_at() < now, Error::<T>::AlreadySet);
+
+			let randomness = Self::verify(&output, &proof).ok_or(Error::<T>::InvalidVrfOutput)?;
+			CurrentRandomness::put(randomness);
+			UpdatedAt::<T>::put(now);
+		}
+	}
+}
+
+impl<T: Config> Module<T> {
+	/// Keys of the authorities at the end of the parent block, the authorities the
+	/// current block is checked against.
+	///
+	/// Until a first snapshot is taken after genesis, the keys of the authorities.
+	pub fn authorities() -> Vec<sr25519::Public> {
+		Self::authority_snapshot().unwrap_or_else(T::Authorities::get)
+	}
+
+	/// Randomness out of the VRF output of the block author, if `proof` is valid.
+	fn verify(output: &VRFOutput, proof: &VRFProof) -> Option<[u8; 32]> {
+		let digest = frame_system::Pallet::<T>::digest();
+		let pre_runtime_digests = digest.logs.iter().filter_map(|d| d.as_pre_runtime());
+		let slot = T::FindSlot::find_slot(pre_runtime_digests)?;
+		let authorities = Self::authorities();
+		if authorities.is_empty() {
+			return None;
+		}
+		let author = authorities[(slot % authorities.len() as u64) as usize];
+
+		let number = frame_system::Pallet::<T>::block_number().saturated_into();
+		verify_with(&author, &Self::current_randomness(), number, output, proof)
+	}
+}
+
+/// Randomness out of the VRF output of `author` for the block `number`, if `proof` is valid.
+fn verify_with(
+	author: &sr25519::Public,
+	randomness: &[u8; 32],
+	number: u64,
+	output: &VRFOutput,
+	proof: &VRFProof,
+) -> Option<[u8; 32]> {
+	let public = schnorrkel::PublicKey::from_bytes(author.as_ref()).ok()?;
+	let (inout, _) = public.vrf_verify(make_transcript(randomness, number), output, proof).ok()?;
+	Some(inout.make_bytes(RANDOMNESS_CONTEXT))
+}
+
+impl<T: Config> Randomness<T::Hash, T::BlockNumber> for Module<T> {
+	/// Randomness for `subject`, and the block it was determined in.
+	fn random(subject: &[u8]) -> (T::Hash, T::BlockNumber) {
+		let mut material = Self::current_randomness().to_vec();
+		material.extend_from_slice(subject);
+		(T::Hashing::hash(&material), Self::updated_at())
+	}
+}
+
+impl<T: Config> ProvideInherent for Module<T> {
+	type Call = Call<T>;
+	type Error = MakeFatalError<()>;
+	const INHERENT_IDENTIFIER: InherentIdentifier = INHERENT_IDENTIFIER;
+
+	fn create_inherent(data: &InherentData) -> Option<Self::Call> {
+		let outputs = data.get_data::<InherentType>(&INHERENT_IDENTIFIER).ok()??;
+		outputs.into_iter()
+			.find(|(output, proof)| Self::verify(output, proof).is_some())
+			.map(|(output, proof)| Call::set_randomness(output, proof))
+	}
+
+	fn is_inherent_required(_: &InherentData) -> Result<Option<Self::Error>, Self::Error> {
+		Ok(Some(().into()))
+	}
+
+	fn check_inherent(call: &Self::Call, _: &InherentData) -> Result<(), Self::Error> {
+		let (output, proof) = match call {
+			Call::set_randomness(output, proof) => (output, proof),
+			_ => return Ok(()),
+		};
+
+		// The block being checked follows the current state.
+		let randomness = Self::current_randomness();
+		let number = frame_system::Pallet::<T>::block_number().saturated_into::<u64>() + 1;
+		let verified = Self::authorities().iter()
+			.any(|authority| verify_with(authority, &randomness, number, output, proof).is_some());
+		if verified {
+			Ok(())
+		} else {
+			Err(().into())
+		}
+	}
+}
+
+/// VRF transcript of the block `number` over `randomness`.
+pub fn make_transcript(randomness: &[u8; 32], number: u64) -> merlin::Transcript {
+	let mut transcript = merlin::Transcript::new(VRF_LABEL);
+	transcript.append_message(b"randomness", randomness);
+	transcript.append_u64(b"block", number);
+	transcript
+}
+
+/// `make_transcript` for the keystore.
+#[cfg(feature = "std")]
+pub fn make_transcript_data(randomness: &[u8; 32], number: u64) -> sp_keystore::vrf::VRFTranscriptData {
+	use sp_keystore::vrf::{VRFTranscriptData, VRFTranscriptValue};
+
+	VRFTranscriptData {
+		label: VRF_LABEL,
+		items: vec![
+			("randomness", VRFTranscriptValue::Bytes(randomness.to_vec())),
+			("block", VRFTranscriptValue::U64(number)),
+		],
+	}
+}
+
+/// Gas of a randomness request.
+const BASE_GAS: u64 = 2_100;
+/// Gas of every word of the subject, as for hashing.
+const WORD_GAS: u64 = 6;
+
+/// Randomness for contracts.
+///
+/// The input is the subject. The output is the random `bytes32` for the subject,
+/// followed by the `uint256` number of the block the randomness was set in.
+pub struct RandomnessPrecompile<T>(PhantomData<T>);
+
+impl<T: Config> Precompile for RandomnessPrecompile<T> {
+	fn execute(
+		input: &[u8],
+		target_gas: Option<u64>,
+		_context: &Context,
+	) -> Result<(ExitSucceed, Vec<u8>, u64), ExitError> {
+		let cost = BASE_GAS + WORD_GAS * ((input.len() as u64 + 31) / 32);
+		if let Some(target_gas) = target_gas {
+			if cost > target_gas {
+				return Err(ExitError::OutOfGas);
+			}
+		}
+
+		let (random, block) = <Module<T> as Randomness<T::Hash, T::BlockNumber>>::random(input);
+		let mut output = random.encode();
+		let mut number = [0u8; 32];
+		U256::from(block.saturated_into::<u64>()).to_big_endian(&mut number);
+		output.extend_from_slice(&number);
+
+		Ok((ExitSucceed::Returned, output, cost))
+	}
+}
+
+sp_api::decl_runtime_apis! {
+	/// Input of the VRF of the next block author.
+	pub trait VrfRandomnessApi {
+		/// Randomness the next VRF output is made over.
+		fn current_randomness() -> [u8; 32];
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::cell::RefCell;
+	use frame_support::{assert_noop, assert_ok, construct_runtime, parameter_types, traits::OnFinalize};
+	use sp_core::H256;
+	use sp_runtime::{Digest, DigestItem, testing::Header, traits::{BlakeTwo256, IdentityLookup}};
+	use crate as pallet_vrf_randomness;
+
+	type UncheckedExtrinsic = frame_system::mocking::MockUncheckedExtrinsic<Test>;
+	type Block = frame_system::mocking::MockBlock<Test>;
+
+	construct_runtime!(
+		pub enum Test where
+			Block = Block,
+			NodeBlock = Block,
+			UncheckedExtrinsic = UncheckedExtrinsic,
+		{
+			System: frame_system::{Pallet, Call, Config, Storage, Event<T>},
+			VrfRandomness: pallet_vrf_randomness::{Pallet, Call, Storage, Inherent},
+		}
+	);
+
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+	}
+
+	impl frame_system::Config for Test {
+		type BaseCallFilter = ();
+		type BlockWeights = ();
+		type BlockLength = ();
+		type DbWeight = ();
+		type Origin = Origin;
+		type Call = Call;
+		type Index = u64;
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = Event;
+		type BlockHashCount = BlockHashCount;
+		type Version = ();
+		type PalletInfo = PalletInfo;
+		type AccountData = ();
+		type OnNewAccount = ();
+		type OnKilledAccount = ();
+		type SystemWeightInfo = ();
+		type SS58Prefix = ();
+	}
+
+	const TEST_ENGINE_ID: ConsensusEngineId = *b"test";
+
+	/// Slot of the `TEST_ENGINE_ID` pre-runtime digest.
+	pub struct TestSlot;
+	impl FindSlot for TestSlot {
+		fn find_slot<'a, I>(digests: I) -> Option<u64> where
+			I: 'a + IntoIterator<Item=(ConsensusEngineId, &'a [u8])>
+		{
+			digests.into_iter()
+				.find(|(id, _)| *id == TEST_ENGINE_ID)
+				.and_then(|(_, mut data)| codec::Decode::decode(&mut data).ok())
+		}
+	}
+
+	thread_local! {
+		static AUTHORITIES: RefCell<Vec<sr25519::Public>> = RefCell::new(Vec::new());
+	}
+
+	pub struct TestAuthorities;
+	impl Get<Vec<sr25519::Public>> for TestAuthorities {
+		fn get() -> Vec<sr25519::Public> {
+			AUTHORITIES.with(|authorities| authorities.borrow().clone())
+		}
+	}
+
+	fn set_authorities(keys: &[&schnorrkel::Keypair]) {
+		let keys = keys.iter().map(|key| sr25519::Public::from_raw(key.public.to_bytes())).collect();
+		AUTHORITIES.with(|authorities| *authorities.borrow_mut() = keys);
+	}
+
+	impl Config for Test {
+		type FindSlot = TestSlot;
+		type Authorities = TestAuthorities;
+	}
+
+	fn new_test_ext() -> sp_io::TestExternalities {
+		frame_system::GenesisConfig::default().build_storage::<Test>().unwrap().into()
+	}
+
+	fn keypair(seed: u8) -> schnorrkel::Keypair {
+		schnorrkel::MiniSecretKey::from_bytes(&[seed; 32]).unwrap()
+			.expand_to_keypair(schnorrkel::ExpansionMode::Ed25519)
+	}
+
+	/// Output of the VRF of `key` for the block `number`, over the current randomness.
+	fn vrf_sign(key: &schnorrkel::Keypair, number: u64) -> (VRFOutput, VRFProof) {
+		let transcript = make_transcript(&VrfRandomness::current_randomness(), number);
+		let (inout, proof, _) = key.vrf_sign(transcript);
+		(VRFOutput(inout.to_output()), VRFProof(proof))
+	}
+
+	/// Starts the block `number` at `slot`.
+	fn start_block(number: u64, slot: u64) {
+		System::initialize(
+			&number,
+			&Default::default(),
+			&Digest { logs: vec![DigestItem::PreRuntime(TEST_ENGINE_ID, slot.encode())] },
+			frame_system::InitKind::Full,
+		);
+	}
+
+	fn check_inherent(output: &VRFOutput, proof: &VRFProof) -> bool {
+		let call = Call::set_randomness(output.clone(), proof.clone());
+		VrfRandomness::check_inherent(&call, &InherentData::new()).is_ok()
+	}
+
+	#[test]
+	fn author_sets_the_randomness() {
+		new_test_ext().execute_with(|| {
+			let (alice, bob) = (keypair(1), keypair(2));
+			set_authorities(&[&alice, &bob]);
+			VrfRandomness::on_finalize(0);
+
+			let (output, proof) = vrf_sign(&bob, 1);
+			assert!(check_inherent(&output, &proof));
+			start_block(1, 3);
+			assert_ok!(VrfRandomness::set_randomness(Origin::none(), output, proof));
+			assert_ne!(VrfRandomness::current_randomness(), [0; 32]);
+			assert_eq!(VrfRandomness::updated_at(), 1);
+
+			let (output, proof) = vrf_sign(&alice, 1);
+			assert_noop!(VrfRandomness::set_randomness(Origin::none(), output, proof), Error::<Test>::AlreadySet);
+		});
+	}
+
+	#[test]
+	fn outputs_of_other_keys_are_rejected() {
+		new_test_ext().execute_with(|| {
+			let (alice, bob, eve) = (keypair(1), keypair(2), keypair(3));
+			set_authorities(&[&alice, &bob]);
+			VrfRandomness::on_finalize(0);
+
+			let (output, proof) = vrf_sign(&eve, 1);
+			assert!(!check_inherent(&output, &proof));
+			// An output of the wrong block.
+			let (output, proof) = vrf_sign(&alice, 2);
+			assert!(!check_inherent(&output, &proof));
+
+			// An authority which is not the author of the slot.
+			let (output, proof) = vrf_sign(&bob, 1);
+			assert!(check_inherent(&output, &proof));
+			start_block(1, 0);
+			assert_noop!(
+				VrfRandomness::set_randomness(Origin::none(), output, proof),
+				Error::<Test>::InvalidVrfOutput,
+			);
+		});
+	}
+
+	#[test]
+	fn author_is_found_among_the_authorities_of_the_parent_block() {
+		new_test_ext().execute_with(|| {
+			let (alice, bob, charlie) = (keypair(1), keypair(2), keypair(3));
+			set_authorities(&[&alice, &bob]);
+			VrfRandomness::on_finalize(0);
+
+			let (output, proof) = vrf_sign(&bob, 1);
+			assert!(check_inherent(&output, &proof));
+			start_block(1, 1);
+			// A new session starts within the block.
+			set_authorities(&[&charlie]);
+			assert_ok!(VrfRandomness::set_randomness(Origin::none(), output, proof));
+
+			// The next block is authored by the new authorities.
+			VrfRandomness::on_finalize(1);
+			let (output, proof) = vrf_sign(&bob, 2);
+			assert!(!check_inherent(&output, &proof));
+			start_block(2, 2);
+			assert_noop!(
+				VrfRandomness::set_randomness(Origin::none(), output, proof),
+				Error::<Test>::InvalidVrfOutput,
+			);
+			let (output, proof) = vrf_sign(&charlie, 2);
+			assert_ok!(VrfRandomness::set_randomness(Origin::none(), output, proof));
+		});
+	}
+
+	#[test]
+	fn precompile_returns_the_randomness_of_the_subject() {
+		new_test_ext().execute_with(|| {
+			let alice = keypair(1);
+			set_authorities(&[&alice]);
+			VrfRandomness::on_finalize(0);
+			let (output, proof) = vrf_sign(&alice, 1);
+			start_block(1, 0);
+			assert_ok!(VrfRandomness::set_randomness(Origin::none(), output, proof));
+
+			let context = Context {
+				address: Default::default(),
+				caller: Default::default(),
+				apparent_value: Default::default(),
+			};
+			let subject = [7u8; 33];
+			let (random, _) = <VrfRandomness as Randomness<H256, u64>>::random(&subject);
+			let mut expected = random.as_bytes().to_vec();
+			expected.extend_from_slice(&[0; 31]);
+			expected.push(1);
+
+			assert_eq!(
+				RandomnessPrecompile::<Test>::execute(&subject, Some(2_112), &context),
+				Ok((ExitSucceed::Returned, expected.clone(), 2_112)),
+			);
+			assert_eq!(
+				RandomnessPrecompile::<Test>::execute(&subject, None, &context),
+				Ok((ExitSucceed::Returned, expected, 2_112)),
+			);
+			assert_eq!(
+				RandomnessPrecompile::<Test>::execute(&subject, Some(2_111), &context),
+				Err(ExitError::OutOfGas),
+			);
+
+			let (other, _) = <VrfRandomness as Randomness<H256, u64>>::random(&[8u8; 33]);
+			assert_ne!(other, random);
+		});
+	}
+}
@@ -0,0 +1,66 @@
+// This file is part of Mathchain.
+
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Addresses of other chains and identities linked to MathChain accounts.
+
+use codec::{Codec, Encode, Decode};
+use sp_std::vec::Vec;
+use sp_core::H160;
+
+/// An address bound to an account through the account service.
+///
+/// Shares its encoding with `pallet_account_service::AccountServiceEnum`.
+#[derive(Clone, Eq, PartialEq, Debug, Encode, Decode)]
+pub enum LinkedAddress {
+	Nickname(Vec<u8>),
+	Ethereum(H160),
+	/// Hash160 of the public key of a P2PKH address.
+	Bitcoin([u8; 20]),
+	/// Address without its `0x41` prefix.
+	Tron(H160),
+	/// Ed25519 public key.
+	Solana([u8; 32]),
+	/// Account id, under any SS58 prefix.
+	Polkadot([u8; 32]),
+}
+
+/// Identity of an account, next to its nickname.
+#[derive(Clone, Eq, PartialEq, Debug, Encode, Decode)]
+pub struct Identity {
+	/// Display name of `pallet_identity`, if stored in clear.
+	pub display: Option<Vec<u8>>,
+	/// Nickname bound through the account service.
+	pub nickname: Option<Vec<u8>>,
+	/// Registrars which judged the identity reasonable or known good, only when
+	/// the judged display name is the nickname.
+	pub verified_by: Vec<u32>,
+}
+
+sp_api::decl_runtime_apis! {
+	/// Resolves the addresses of the account service.
+	pub trait AccountServiceRuntimeApi<AccountId> where AccountId: Codec {
+		/// Account of an Ethereum address, its `evm:` fallback account if it is not bound.
+		fn ethereum_account(address: H160) -> AccountId;
+		/// Addresses bound to `account`, and the Ethereum address of a fallback account.
+		fn linked_addresses(account: AccountId) -> Vec<LinkedAddress>;
+		/// Account holding `nickname`.
+		fn nickname_account(nickname: Vec<u8>) -> Option<AccountId>;
+		/// Account a nickname or an address of another chain is bound to.
+		fn linked_account(address: LinkedAddress) -> Option<AccountId>;
+		/// Identity and nickname of `account`, if it has either.
+		fn identity(account: AccountId) -> Option<Identity>;
+	}
+}
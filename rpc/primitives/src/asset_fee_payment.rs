@@ -0,0 +1,49 @@
+// This file is part of Mathchain.
+
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Fees of transactions paid in assets, out of `pallet_asset_fee_payment`.
+
+use codec::{Codec, Encode, Decode};
+
+/// Fee of a transaction which depends on its weight and length, in units of an asset.
+#[derive(Clone, Eq, PartialEq, Debug, Encode, Decode)]
+pub struct AssetInclusionFee<AssetBalance> {
+	pub base_fee: AssetBalance,
+	pub len_fee: AssetBalance,
+	pub adjusted_weight_fee: AssetBalance,
+}
+
+/// Fee of a transaction in the asset it pays its fee in.
+#[derive(Clone, Eq, PartialEq, Debug, Encode, Decode)]
+pub struct AssetFeeDetails<AssetId, AssetBalance> {
+	pub asset: AssetId,
+	/// `None` for a transaction which pays no fee.
+	pub inclusion_fee: Option<AssetInclusionFee<AssetBalance>>,
+	pub tip: AssetBalance,
+}
+
+sp_api::decl_runtime_apis! {
+	/// Quotes the fees of transactions in the asset they pay their fee in.
+	///
+	/// `TransactionPaymentApi` keeps quoting fees in MATH.
+	pub trait AssetFeePaymentRuntimeApi<AssetId, AssetBalance> where
+		AssetId: Codec,
+		AssetBalance: Codec,
+	{
+		/// Fee of `uxt` in the asset it pays its fee in, `None` if it pays in MATH.
+		fn query_fee_details(uxt: Block::Extrinsic, len: u32) -> Option<AssetFeeDetails<AssetId, AssetBalance>>;
+	}
+}
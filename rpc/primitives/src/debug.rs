@@ -0,0 +1,115 @@
+// This file is part of Mathchain.
+
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! EVM transaction tracing.
+
+use codec::{Encode, Decode};
+use sp_std::{vec::Vec, collections::btree_map::BTreeMap};
+use sp_core::{H160, H256, U256};
+use sp_runtime::DispatchError;
+use ethereum::Transaction;
+
+/// Kind of trace to produce while re-executing a transaction.
+#[derive(Clone, Eq, PartialEq, Debug, Encode, Decode)]
+pub enum TraceType {
+	/// One entry per executed opcode (Geth struct logger).
+	Raw {
+		disable_storage: bool,
+		disable_memory: bool,
+		disable_stack: bool,
+	},
+	/// One entry per call, create or self-destruct.
+	CallList,
+}
+
+/// A single executed opcode.
+#[derive(Clone, Eq, PartialEq, Debug, Encode, Decode)]
+pub struct RawStepLog {
+	pub depth: U256,
+	pub gas: U256,
+	pub gas_cost: U256,
+	pub memory: Option<Vec<H256>>,
+	/// Opcode mnemonic, e.g. `PUSH1`.
+	pub op: Vec<u8>,
+	pub pc: U256,
+	pub stack: Option<Vec<H256>>,
+	pub storage: Option<BTreeMap<H256, H256>>,
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Encode, Decode)]
+pub enum CallType {
+	Call,
+	CallCode,
+	DelegateCall,
+	StaticCall,
+	Create,
+	SelfDestruct,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Encode, Decode)]
+pub enum CallResult {
+	/// Returned data of a successful call, or the code of a created contract.
+	Output(Vec<u8>),
+	/// Description of the error which made the call fail.
+	Error(Vec<u8>),
+}
+
+/// A call (or create) performed while executing a transaction.
+#[derive(Clone, Eq, PartialEq, Debug, Encode, Decode)]
+pub struct Call {
+	pub call_type: CallType,
+	pub from: H160,
+	pub to: H160,
+	pub value: U256,
+	pub gas: U256,
+	pub gas_used: U256,
+	pub input: Vec<u8>,
+	pub result: CallResult,
+	/// Position of this call in the call tree, the root call being `[]`.
+	pub trace_address: Vec<u32>,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Encode, Decode)]
+pub enum TransactionTrace {
+	Raw {
+		gas: U256,
+		return_value: Vec<u8>,
+		step_logs: Vec<RawStepLog>,
+	},
+	/// Calls in the order they were entered.
+	CallList(Vec<Call>),
+}
+
+sp_api::decl_runtime_apis! {
+	/// Re-executes extrinsics with an EVM tracer attached.
+	///
+	/// Callers are expected to have initialized the block being traced on top
+	/// of its parent first, so that the extrinsics see the right state.
+	pub trait DebugRuntimeApi {
+		/// Apply `extrinsics` in order and trace the one carrying `transaction`.
+		fn trace_transaction(
+			extrinsics: Vec<Block::Extrinsic>,
+			transaction: &Transaction,
+			trace_type: TraceType,
+		) -> Result<TransactionTrace, DispatchError>;
+
+		/// Apply `extrinsics` in order and trace every Ethereum transaction among them.
+		fn trace_block(
+			extrinsics: Vec<Block::Extrinsic>,
+			trace_type: TraceType,
+		) -> Result<Vec<TransactionTrace>, DispatchError>;
+	}
+}
@@ -0,0 +1,25 @@
+// This file is part of Mathchain.
+
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Runtime APIs and shared types for the MathChain specific RPC extensions.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+pub mod account_service;
+pub mod asset_fee_payment;
+pub mod debug;
+pub mod recovery;
+pub mod txpool;
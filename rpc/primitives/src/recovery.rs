@@ -0,0 +1,67 @@
+// This file is part of Mathchain.
+
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Recovery status of an account, out of `pallet_recovery`.
+
+use codec::{Codec, Encode, Decode};
+use sp_std::vec::Vec;
+
+/// Recovery configuration of an account.
+#[derive(Clone, Eq, PartialEq, Debug, Encode, Decode)]
+pub struct RecoveryConfig<AccountId, Balance, BlockNumber> {
+	/// Friends allowed to vouch for a rescuer.
+	pub friends: Vec<AccountId>,
+	/// Number of vouches needed to claim the recovery.
+	pub threshold: u16,
+	/// Blocks between the initiation and the claim of a recovery.
+	pub delay_period: BlockNumber,
+	/// Deposit reserved for the configuration.
+	pub deposit: Balance,
+}
+
+/// Recovery of an account initiated by a rescuer.
+#[derive(Clone, Eq, PartialEq, Debug, Encode, Decode)]
+pub struct ActiveRecovery<AccountId, Balance, BlockNumber> {
+	pub rescuer: AccountId,
+	/// Block the recovery was initiated at.
+	pub created: BlockNumber,
+	/// Block from which the rescuer may claim the recovery.
+	pub delay_elapses_at: BlockNumber,
+	/// Friends who vouched for the rescuer.
+	pub vouched: Vec<AccountId>,
+	/// Deposit reserved from the rescuer.
+	pub deposit: Balance,
+}
+
+/// Everything `pallet_recovery` knows about an account.
+#[derive(Clone, Eq, PartialEq, Debug, Encode, Decode)]
+pub struct RecoveryStatus<AccountId, Balance, BlockNumber> {
+	pub config: Option<RecoveryConfig<AccountId, Balance, BlockNumber>>,
+	pub active_recoveries: Vec<ActiveRecovery<AccountId, Balance, BlockNumber>>,
+	/// Account the account may act as, after claiming its recovery.
+	pub recovered: Option<AccountId>,
+}
+
+sp_api::decl_runtime_apis! {
+	/// Reads the recovery state of accounts.
+	pub trait RecoveryRuntimeApi<AccountId, Balance, BlockNumber> where
+		AccountId: Codec,
+		Balance: Codec,
+		BlockNumber: Codec,
+	{
+		fn recovery_status(account: AccountId) -> RecoveryStatus<AccountId, Balance, BlockNumber>;
+	}
+}
@@ -0,0 +1,41 @@
+// This file is part of Mathchain.
+
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Ethereum transactions waiting in the transaction pool.
+
+use codec::{Encode, Decode};
+use sp_std::vec::Vec;
+use ethereum::Transaction;
+
+/// Ethereum transactions found among the pool extrinsics.
+#[derive(Clone, Eq, PartialEq, Debug, Encode, Decode)]
+pub struct TxPoolResponse {
+	/// Transactions ready to be included in a block.
+	pub ready: Vec<Transaction>,
+	/// Transactions waiting on a missing nonce.
+	pub future: Vec<Transaction>,
+}
+
+sp_api::decl_runtime_apis! {
+	/// Decodes the Ethereum transactions out of opaque pool extrinsics.
+	pub trait TxPoolRuntimeApi {
+		/// Keep the `pallet_ethereum::Call::transact` extrinsics of each set.
+		fn extrinsic_filter(
+			xts_ready: Vec<Block::Extrinsic>,
+			xts_future: Vec<Block::Extrinsic>,
+		) -> TxPoolResponse;
+	}
+}
@@ -0,0 +1,287 @@
+// This file is part of Mathchain.
+
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! `accountService_*` RPC, resolving addresses through the account service.
+
+use std::{sync::Arc, marker::PhantomData, convert::TryFrom};
+use codec::Codec;
+use jsonrpc_core::Result;
+use jsonrpc_derive::rpc;
+use serde::{Serialize, Deserialize};
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_core::{H160, crypto::{AccountId32, Ss58Codec}};
+use sp_runtime::{generic::BlockId, traits::Block as BlockT};
+use mathchain_rpc_primitives::account_service::{self as primitives, AccountServiceRuntimeApi, LinkedAddress};
+
+use crate::internal_err;
+
+/// An address bound to an account, other chains in their usual text form.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Address {
+	Nickname { nickname: String },
+	Ethereum { address: H160 },
+	/// Base58check P2PKH address.
+	Bitcoin { address: String },
+	/// Base58check address.
+	Tron { address: String },
+	/// Base58 public key.
+	Solana { address: String },
+	/// SS58 address, under the default prefix.
+	Polkadot { address: String },
+}
+
+const BITCOIN_P2PKH_VERSION: u8 = 0x00;
+const TRON_VERSION: u8 = 0x41;
+
+impl From<LinkedAddress> for Address {
+	fn from(address: LinkedAddress) -> Self {
+		match address {
+			LinkedAddress::Nickname(nickname) =>
+				Address::Nickname { nickname: String::from_utf8_lossy(&nickname).into_owned() },
+			LinkedAddress::Ethereum(address) => Address::Ethereum { address },
+			LinkedAddress::Bitcoin(hash) =>
+				Address::Bitcoin { address: base58check(BITCOIN_P2PKH_VERSION, &hash) },
+			LinkedAddress::Tron(address) =>
+				Address::Tron { address: base58check(TRON_VERSION, address.as_bytes()) },
+			LinkedAddress::Solana(public) =>
+				Address::Solana { address: bs58::encode(public).into_string() },
+			LinkedAddress::Polkadot(public) =>
+				Address::Polkadot { address: AccountId32::from(public).to_ss58check() },
+		}
+	}
+}
+
+impl TryFrom<Address> for LinkedAddress {
+	type Error = String;
+
+	fn try_from(address: Address) -> std::result::Result<Self, String> {
+		Ok(match address {
+			Address::Nickname { nickname } => LinkedAddress::Nickname(nickname.into_bytes()),
+			Address::Ethereum { address } => LinkedAddress::Ethereum(address),
+			Address::Bitcoin { address } =>
+				LinkedAddress::Bitcoin(from_base58check(BITCOIN_P2PKH_VERSION, &address)?),
+			Address::Tron { address } =>
+				LinkedAddress::Tron(H160(from_base58check(TRON_VERSION, &address)?)),
+			Address::Solana { address } => {
+				let bytes = bs58::decode(&address).into_vec()
+					.map_err(|err| format!("invalid Solana address: {}", err))?;
+				LinkedAddress::Solana(<[u8; 32]>::try_from(&bytes[..])
+					.map_err(|_| "invalid Solana address length".to_string())?)
+			},
+			Address::Polkadot { address } => {
+				let account = AccountId32::from_ss58check_with_version(&address)
+					.map_err(|err| format!("invalid SS58 address: {:?}", err))?
+					.0;
+				LinkedAddress::Polkadot(account.into())
+			},
+		})
+	}
+}
+
+fn checksum(payload: &[u8]) -> [u8; 4] {
+	let hash = sp_io::hashing::sha2_256(&sp_io::hashing::sha2_256(payload));
+	[hash[0], hash[1], hash[2], hash[3]]
+}
+
+/// Base58 of `version` ++ `payload` ++ checksum.
+fn base58check(version: u8, payload: &[u8]) -> String {
+	let mut bytes = vec![version];
+	bytes.extend_from_slice(payload);
+	let checksum = checksum(&bytes);
+	bytes.extend_from_slice(&checksum);
+	bs58::encode(bytes).into_string()
+}
+
+/// 20 byte payload of a base58check address of `version`.
+fn from_base58check(version: u8, address: &str) -> std::result::Result<[u8; 20], String> {
+	let bytes = bs58::decode(address).into_vec()
+		.map_err(|err| format!("invalid base58 address: {}", err))?;
+	if bytes.len() != 25 || bytes[0] != version {
+		return Err(format!("expected a version {:#04x} address of 20 bytes", version));
+	}
+	if checksum(&bytes[..21])[..] != bytes[21..] {
+		return Err("invalid address checksum".into());
+	}
+	let mut payload = [0u8; 20];
+	payload.copy_from_slice(&bytes[1..21]);
+	Ok(payload)
+}
+
+/// Identity of an account, next to its nickname.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Identity {
+	pub display: Option<String>,
+	pub nickname: Option<String>,
+	/// Registrars which judged the identity reasonable or known good, only when
+	/// the judged display name is the nickname.
+	pub verified_by: Vec<u32>,
+}
+
+impl From<primitives::Identity> for Identity {
+	fn from(identity: primitives::Identity) -> Self {
+		let text = |bytes: Vec<u8>| String::from_utf8_lossy(&bytes).into_owned();
+		Identity {
+			display: identity.display.map(text),
+			nickname: identity.nickname.map(text),
+			verified_by: identity.verified_by,
+		}
+	}
+}
+
+#[rpc(server)]
+pub trait AccountServiceApi<BlockHash, AccountId> {
+	/// Returns the account of an Ethereum address, its fallback account if it is not bound.
+	#[rpc(name = "accountService_ethereumAccount")]
+	fn ethereum_account(&self, address: H160, at: Option<BlockHash>) -> Result<AccountId>;
+
+	/// Returns the addresses bound to an account.
+	#[rpc(name = "accountService_linkedAddresses")]
+	fn linked_addresses(&self, account: AccountId, at: Option<BlockHash>) -> Result<Vec<Address>>;
+
+	/// Returns the account holding a nickname.
+	#[rpc(name = "accountService_nicknameAccount")]
+	fn nickname_account(&self, nickname: String, at: Option<BlockHash>) -> Result<Option<AccountId>>;
+
+	/// Returns the account a nickname or an address of another chain is bound to.
+	#[rpc(name = "accountService_linkedAccount")]
+	fn linked_account(&self, address: Address, at: Option<BlockHash>) -> Result<Option<AccountId>>;
+
+	/// Returns the identity and nickname of an account.
+	#[rpc(name = "accountService_identity")]
+	fn identity(&self, account: AccountId, at: Option<BlockHash>) -> Result<Option<Identity>>;
+
+	/// Returns the identity and nickname of the account of an Ethereum address.
+	#[rpc(name = "accountService_ethereumIdentity")]
+	fn ethereum_identity(&self, address: H160, at: Option<BlockHash>) -> Result<Option<Identity>>;
+}
+
+pub struct AccountService<B, C> {
+	client: Arc<C>,
+	_marker: PhantomData<B>,
+}
+
+impl<B, C> AccountService<B, C> {
+	pub fn new(client: Arc<C>) -> Self {
+		Self { client, _marker: PhantomData }
+	}
+}
+
+impl<B, C> AccountService<B, C> where
+	B: BlockT,
+	C: HeaderBackend<B>,
+{
+	fn block_id(&self, at: Option<B::Hash>) -> BlockId<B> {
+		BlockId::Hash(at.unwrap_or_else(|| self.client.info().best_hash))
+	}
+}
+
+fn runtime_err<E: std::fmt::Debug>(err: E) -> jsonrpc_core::Error {
+	internal_err(format!("call runtime failed: {:?}", err))
+}
+
+fn invalid_params(message: String) -> jsonrpc_core::Error {
+	jsonrpc_core::Error::invalid_params(message)
+}
+
+impl<B, C, AccountId> AccountServiceApi<B::Hash, AccountId> for AccountService<B, C> where
+	B: BlockT,
+	C: ProvideRuntimeApi<B> + HeaderBackend<B> + Send + Sync + 'static,
+	C::Api: AccountServiceRuntimeApi<B, AccountId>,
+	AccountId: Codec,
+{
+	fn ethereum_account(&self, address: H160, at: Option<B::Hash>) -> Result<AccountId> {
+		self.client.runtime_api()
+			.ethereum_account(&self.block_id(at), address)
+			.map_err(runtime_err)
+	}
+
+	fn linked_addresses(&self, account: AccountId, at: Option<B::Hash>) -> Result<Vec<Address>> {
+		let addresses = self.client.runtime_api()
+			.linked_addresses(&self.block_id(at), account)
+			.map_err(runtime_err)?;
+		Ok(addresses.into_iter().map(Into::into).collect())
+	}
+
+	fn nickname_account(&self, nickname: String, at: Option<B::Hash>) -> Result<Option<AccountId>> {
+		self.client.runtime_api()
+			.nickname_account(&self.block_id(at), nickname.into_bytes())
+			.map_err(runtime_err)
+	}
+
+	fn linked_account(&self, address: Address, at: Option<B::Hash>) -> Result<Option<AccountId>> {
+		let address = LinkedAddress::try_from(address).map_err(invalid_params)?;
+		self.client.runtime_api()
+			.linked_account(&self.block_id(at), address)
+			.map_err(runtime_err)
+	}
+
+	fn identity(&self, account: AccountId, at: Option<B::Hash>) -> Result<Option<Identity>> {
+		let identity = self.client.runtime_api()
+			.identity(&self.block_id(at), account)
+			.map_err(runtime_err)?;
+		Ok(identity.map(Into::into))
+	}
+
+	fn ethereum_identity(&self, address: H160, at: Option<B::Hash>) -> Result<Option<Identity>> {
+		let id = self.block_id(at);
+		let api = self.client.runtime_api();
+		let account = api.ethereum_account(&id, address).map_err(runtime_err)?;
+		let identity = api.identity(&id, account).map_err(runtime_err)?;
+		Ok(identity.map(Into::into))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use rustc_hex::FromHex;
+
+	#[test]
+	fn other_chain_addresses_round_trip_through_their_text_form() {
+		// Hash160 of the genesis coinbase key of Bitcoin.
+		let mut hash = [0u8; 20];
+		hash.copy_from_slice(&"62e907b15cbf27d5425399ebf6f0fb50ebb88f18".from_hex::<Vec<u8>>().unwrap());
+		let bitcoin = Address::from(LinkedAddress::Bitcoin(hash));
+		assert_eq!(bitcoin, Address::Bitcoin { address: "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa".into() });
+
+		for address in vec![
+			LinkedAddress::Bitcoin(hash),
+			LinkedAddress::Tron(H160::repeat_byte(7)),
+			LinkedAddress::Solana([8; 32]),
+			LinkedAddress::Polkadot([9; 32]),
+		] {
+			assert_eq!(LinkedAddress::try_from(Address::from(address.clone())), Ok(address));
+		}
+	}
+
+	#[test]
+	fn rejects_addresses_of_another_version_or_checksum() {
+		let tron = match Address::from(LinkedAddress::Tron(H160::repeat_byte(7))) {
+			Address::Tron { address } => address,
+			_ => unreachable!(),
+		};
+		assert!(tron.starts_with('T'));
+		assert!(LinkedAddress::try_from(Address::Bitcoin { address: tron }).is_err());
+
+		let mut corrupted = "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa".to_string();
+		corrupted.replace_range(33.., "b");
+		assert!(LinkedAddress::try_from(Address::Bitcoin { address: corrupted }).is_err());
+	}
+}
@@ -0,0 +1,111 @@
+// This file is part of Mathchain.
+
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! `assetFeePayment_*` RPC, quoting fees in the asset a transaction pays them in.
+
+use std::{sync::Arc, marker::PhantomData};
+use codec::{Codec, Decode};
+use jsonrpc_core::Result;
+use jsonrpc_derive::rpc;
+use serde::Serialize;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_core::Bytes;
+use sp_runtime::{generic::BlockId, traits::Block as BlockT};
+use mathchain_rpc_primitives::asset_fee_payment::{self as primitives, AssetFeePaymentRuntimeApi};
+
+use crate::internal_err;
+
+/// Fee of a transaction which depends on its weight and length, in units of an asset.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InclusionFee<AssetBalance> {
+	pub base_fee: AssetBalance,
+	pub len_fee: AssetBalance,
+	pub adjusted_weight_fee: AssetBalance,
+}
+
+/// Fee of a transaction in the asset it pays its fee in.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FeeDetails<AssetId, AssetBalance> {
+	pub asset: AssetId,
+	pub inclusion_fee: Option<InclusionFee<AssetBalance>>,
+	pub tip: AssetBalance,
+}
+
+impl<I, B> From<primitives::AssetFeeDetails<I, B>> for FeeDetails<I, B> {
+	fn from(details: primitives::AssetFeeDetails<I, B>) -> Self {
+		FeeDetails {
+			asset: details.asset,
+			inclusion_fee: details.inclusion_fee.map(|fee| InclusionFee {
+				base_fee: fee.base_fee,
+				len_fee: fee.len_fee,
+				adjusted_weight_fee: fee.adjusted_weight_fee,
+			}),
+			tip: details.tip,
+		}
+	}
+}
+
+#[rpc(server)]
+pub trait AssetFeePaymentApi<BlockHash, AssetId, AssetBalance> {
+	/// Returns the fee of an extrinsic in the asset it pays its fee in, none if it pays in MATH.
+	#[rpc(name = "assetFeePayment_queryFeeDetails")]
+	fn query_fee_details(
+		&self,
+		encoded_xt: Bytes,
+		at: Option<BlockHash>,
+	) -> Result<Option<FeeDetails<AssetId, AssetBalance>>>;
+}
+
+pub struct AssetFeePayment<B, C> {
+	client: Arc<C>,
+	_marker: PhantomData<B>,
+}
+
+impl<B, C> AssetFeePayment<B, C> {
+	pub fn new(client: Arc<C>) -> Self {
+		Self { client, _marker: PhantomData }
+	}
+}
+
+impl<B, C, AssetId, AssetBalance> AssetFeePaymentApi<B::Hash, AssetId, AssetBalance>
+	for AssetFeePayment<B, C>
+where
+	B: BlockT,
+	C: ProvideRuntimeApi<B> + HeaderBackend<B> + Send + Sync + 'static,
+	C::Api: AssetFeePaymentRuntimeApi<B, AssetId, AssetBalance>,
+	AssetId: Codec,
+	AssetBalance: Codec,
+{
+	fn query_fee_details(
+		&self,
+		encoded_xt: Bytes,
+		at: Option<B::Hash>,
+	) -> Result<Option<FeeDetails<AssetId, AssetBalance>>> {
+		let id = BlockId::Hash(at.unwrap_or_else(|| self.client.info().best_hash));
+		let len = encoded_xt.len() as u32;
+		let uxt = B::Extrinsic::decode(&mut &*encoded_xt)
+			.map_err(|err| jsonrpc_core::Error::invalid_params(format!("invalid extrinsic: {:?}", err)))?;
+
+		self.client.runtime_api()
+			.query_fee_details(&id, uxt, len)
+			.map(|details| details.map(Into::into))
+			.map_err(|err| internal_err(format!("call runtime failed: {:?}", err)))
+	}
+}
@@ -0,0 +1,398 @@
+// This file is part of Mathchain.
+
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Geth compatible `debug_*` tracing RPC.
+//!
+//! Traces are produced by re-executing the block on top of its parent through
+//! the `DebugRuntimeApi`, which only returns traces when the runtime was built
+//! with the `evm-tracing` feature.
+
+use std::{sync::Arc, collections::BTreeMap};
+use jsonrpc_core::Result;
+use jsonrpc_derive::rpc;
+use serde::{Serialize, Deserialize};
+use sp_api::{ProvideRuntimeApi, Core};
+use sp_blockchain::HeaderBackend;
+use sp_core::{H160, H256, U256};
+use sp_runtime::{generic::BlockId, traits::{Block as BlockT, Header as HeaderT}};
+use sc_client_api::BlockBackend;
+use sc_rpc_api::DenyUnsafe;
+use fc_rpc_core::types::{BlockNumber, Bytes};
+use fp_rpc::EthereumRuntimeRPCApi;
+use mathchain_rpc_primitives::debug::{
+	DebugRuntimeApi, TraceType, TransactionTrace, RawStepLog, Call, CallType, CallResult,
+};
+
+use crate::{internal_err, substrate_hash, OverrideHandle};
+
+/// Options of `debug_traceTransaction` and `debug_traceBlockByNumber`.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TraceParams {
+	pub disable_storage: Option<bool>,
+	pub disable_memory: Option<bool>,
+	pub disable_stack: Option<bool>,
+	/// Javascript tracers are not supported, only `callTracer` is recognised.
+	pub tracer: Option<String>,
+	pub timeout: Option<String>,
+}
+
+/// A step of the Geth struct logger.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StructLog {
+	pub depth: u64,
+	pub gas: u64,
+	pub gas_cost: u64,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub memory: Option<Vec<String>>,
+	pub op: String,
+	pub pc: u64,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub stack: Option<Vec<String>>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub storage: Option<BTreeMap<String, String>>,
+}
+
+/// A call frame of the Geth `callTracer`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallTrace {
+	#[serde(rename = "type")]
+	pub call_type: String,
+	pub from: H160,
+	pub to: H160,
+	pub value: U256,
+	pub gas: U256,
+	pub gas_used: U256,
+	pub input: Bytes,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub output: Option<Bytes>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub error: Option<String>,
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	pub calls: Vec<CallTrace>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum TraceResponse {
+	#[serde(rename_all = "camelCase")]
+	StructLogs {
+		gas: u64,
+		return_value: Bytes,
+		struct_logs: Vec<StructLog>,
+	},
+	CallTracer(CallTrace),
+}
+
+/// Trace of a single transaction of a block, as returned by Geth.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct BlockTraceResponse {
+	pub result: TraceResponse,
+}
+
+#[rpc(server)]
+pub trait DebugApi {
+	/// Re-executes a transaction and returns its trace.
+	#[rpc(name = "debug_traceTransaction")]
+	fn trace_transaction(
+		&self,
+		transaction_hash: H256,
+		params: Option<TraceParams>,
+	) -> Result<TraceResponse>;
+
+	/// Re-executes all the transactions of a block and returns their traces.
+	#[rpc(name = "debug_traceBlockByNumber")]
+	fn trace_block_by_number(
+		&self,
+		number: BlockNumber,
+		params: Option<TraceParams>,
+	) -> Result<Vec<BlockTraceResponse>>;
+}
+
+pub struct Debug<B: BlockT, C> {
+	client: Arc<C>,
+	backend: Arc<fc_db::Backend<B>>,
+	overrides: Arc<OverrideHandle<B>>,
+	deny_unsafe: DenyUnsafe,
+}
+
+impl<B: BlockT, C> Debug<B, C> {
+	pub fn new(
+		client: Arc<C>,
+		backend: Arc<fc_db::Backend<B>>,
+		overrides: Arc<OverrideHandle<B>>,
+		deny_unsafe: DenyUnsafe,
+	) -> Self {
+		Self { client, backend, overrides, deny_unsafe }
+	}
+}
+
+impl<B, C> Debug<B, C> where
+	B: BlockT<Hash=H256> + Send + Sync + 'static,
+	C: ProvideRuntimeApi<B> + HeaderBackend<B> + BlockBackend<B> + Send + Sync + 'static,
+	C::Api: DebugRuntimeApi<B> + EthereumRuntimeRPCApi<B>,
+{
+	fn trace_type(params: Option<TraceParams>) -> Result<TraceType> {
+		let params = params.unwrap_or_default();
+		match params.tracer.as_deref() {
+			None => Ok(TraceType::Raw {
+				disable_storage: params.disable_storage.unwrap_or(false),
+				disable_memory: params.disable_memory.unwrap_or(false),
+				disable_stack: params.disable_stack.unwrap_or(false),
+			}),
+			Some("callTracer") => Ok(TraceType::CallList),
+			Some(tracer) => Err(internal_err(format!("tracer {} is not supported", tracer))),
+		}
+	}
+
+	fn is_canon(&self, hash: B::Hash) -> bool {
+		if let Ok(Some(header)) = self.client.header(BlockId::Hash(hash)) {
+			if let Ok(Some(canon_hash)) = self.client.hash(*header.number()) {
+				return canon_hash == hash;
+			}
+		}
+		false
+	}
+
+	/// Substrate block and Ethereum index of a canonical transaction.
+	fn transaction_position(&self, transaction_hash: H256) -> Result<(B::Hash, usize)> {
+		let metadata = self.backend.mapping()
+			.transaction_metadata(&transaction_hash)
+			.map_err(|err| internal_err(format!("fetch aux store failed: {:?}", err)))?;
+
+		metadata.into_iter()
+			.find(|meta| self.is_canon(meta.block_hash))
+			.map(|meta| (meta.block_hash, meta.ethereum_index as usize))
+			.ok_or_else(|| internal_err(format!("transaction {:?} not found", transaction_hash)))
+	}
+}
+
+impl<B, C> DebugApi for Debug<B, C> where
+	B: BlockT<Hash=H256> + Send + Sync + 'static,
+	C: ProvideRuntimeApi<B> + HeaderBackend<B> + BlockBackend<B> + Send + Sync + 'static,
+	C::Api: DebugRuntimeApi<B> + EthereumRuntimeRPCApi<B>,
+{
+	fn trace_transaction(
+		&self,
+		transaction_hash: H256,
+		params: Option<TraceParams>,
+	) -> Result<TraceResponse> {
+		self.deny_unsafe.check_if_safe()?;
+		let trace_type = Self::trace_type(params)?;
+
+		let (hash, index) = self.transaction_position(transaction_hash)?;
+		let block = self.overrides.current_block(&*self.client, &BlockId::Hash(hash))
+			.map_err(|err| internal_err(format!("fetch ethereum block failed: {}", err)))?
+			.ok_or_else(|| internal_err(format!("ethereum block {:?} not found", hash)))?;
+		let transaction = block.transactions.get(index).cloned()
+			.ok_or_else(|| internal_err(format!("transaction {:?} not found", transaction_hash)))?;
+
+		let trace = replay_block(&*self.client, hash, |api, parent_id, extrinsics| {
+			api.trace_transaction(parent_id, extrinsics, &transaction, trace_type)
+				.map_err(|err| internal_err(format!("call runtime failed: {:?}", err)))?
+				.map_err(|err| internal_err(format!("tracing failed: {:?}", err)))
+		})?;
+
+		trace_response(trace)
+	}
+
+	fn trace_block_by_number(
+		&self,
+		number: BlockNumber,
+		params: Option<TraceParams>,
+	) -> Result<Vec<BlockTraceResponse>> {
+		self.deny_unsafe.check_if_safe()?;
+		let trace_type = Self::trace_type(params)?;
+
+		let hash = substrate_hash(&*self.client, &self.backend, number)?;
+		let traces = replay_block(&*self.client, hash, |api, parent_id, extrinsics| {
+			api.trace_block(parent_id, extrinsics, trace_type)
+				.map_err(|err| internal_err(format!("call runtime failed: {:?}", err)))?
+				.map_err(|err| internal_err(format!("tracing failed: {:?}", err)))
+		})?;
+
+		traces.into_iter()
+			.map(|trace| Ok(BlockTraceResponse { result: trace_response(trace)? }))
+			.collect()
+	}
+}
+
+/// Initialize the block `hash` on top of its parent and hand its extrinsics to `f`.
+pub(crate) fn replay_block<B, C, R, F>(client: &C, hash: B::Hash, f: F) -> Result<R> where
+	B: BlockT,
+	C: ProvideRuntimeApi<B> + HeaderBackend<B> + BlockBackend<B>,
+	C::Api: DebugRuntimeApi<B>,
+	F: FnOnce(&sp_api::ApiRef<C::Api>, &BlockId<B>, Vec<B::Extrinsic>) -> Result<R>,
+{
+	let id = BlockId::Hash(hash);
+	let header = client.header(id)
+		.map_err(|err| internal_err(format!("fetch header failed: {:?}", err)))?
+		.ok_or_else(|| internal_err(format!("block {:?} not found", hash)))?;
+	let extrinsics = client.block_body(&id)
+		.map_err(|err| internal_err(format!("fetch block body failed: {:?}", err)))?
+		.ok_or_else(|| internal_err(format!("block {:?} has no body", hash)))?;
+	let parent_id = BlockId::Hash(*header.parent_hash());
+
+	let api = client.runtime_api();
+	api.initialize_block(&parent_id, &header)
+		.map_err(|err| internal_err(format!("runtime block initialization failed: {:?}", err)))?;
+
+	f(&api, &parent_id, extrinsics)
+}
+
+fn trace_response(trace: TransactionTrace) -> Result<TraceResponse> {
+	match trace {
+		TransactionTrace::Raw { gas, return_value, step_logs } => Ok(TraceResponse::StructLogs {
+			gas: gas.low_u64(),
+			return_value: Bytes(return_value),
+			struct_logs: step_logs.into_iter().map(struct_log).collect(),
+		}),
+		TransactionTrace::CallList(calls) => call_tree(calls)
+			.map(TraceResponse::CallTracer)
+			.ok_or_else(|| internal_err("malformed call trace")),
+	}
+}
+
+fn struct_log(step: RawStepLog) -> StructLog {
+	StructLog {
+		depth: step.depth.low_u64(),
+		gas: step.gas.low_u64(),
+		gas_cost: step.gas_cost.low_u64(),
+		memory: step.memory.map(|memory| memory.iter().map(|word| format!("{:x}", word)).collect()),
+		op: String::from_utf8_lossy(&step.op).into_owned(),
+		pc: step.pc.low_u64(),
+		stack: step.stack.map(|stack| stack.iter().map(|item| format!("{:x}", item)).collect()),
+		storage: step.storage.map(|storage| storage.iter()
+			.map(|(key, value)| (format!("{:x}", key), format!("{:x}", value)))
+			.collect()
+		),
+	}
+}
+
+fn call_frame(call: Call) -> CallTrace {
+	let call_type = match call.call_type {
+		CallType::Call => "CALL",
+		CallType::CallCode => "CALLCODE",
+		CallType::DelegateCall => "DELEGATECALL",
+		CallType::StaticCall => "STATICCALL",
+		CallType::Create => "CREATE",
+		CallType::SelfDestruct => "SELFDESTRUCT",
+	};
+	let (output, error) = match call.result {
+		CallResult::Output(output) => (Some(Bytes(output)), None),
+		CallResult::Error(error) => (None, Some(String::from_utf8_lossy(&error).into_owned())),
+	};
+
+	CallTrace {
+		call_type: call_type.into(),
+		from: call.from,
+		to: call.to,
+		value: call.value,
+		gas: call.gas,
+		gas_used: call.gas_used,
+		input: Bytes(call.input),
+		output,
+		error,
+		calls: Vec::new(),
+	}
+}
+
+/// Nest a list of calls under the root call, by their trace addresses.
+///
+/// `None` if a call has no parent in the list.
+fn call_tree(calls: Vec<Call>) -> Option<CallTrace> {
+	if calls.is_empty() {
+		return Some(call_frame(Call {
+			call_type: CallType::Call,
+			from: H160::default(),
+			to: H160::default(),
+			value: U256::zero(),
+			gas: U256::zero(),
+			gas_used: U256::zero(),
+			input: Vec::new(),
+			result: CallResult::Output(Vec::new()),
+			trace_address: Vec::new(),
+		}));
+	}
+
+	let mut frames = calls.into_iter()
+		.map(|call| (call.trace_address.clone(), call_frame(call)))
+		.collect::<BTreeMap<_, _>>();
+
+	// Deepest and last calls first: every call is moved into its parent before the
+	// parent itself is moved, and the subcalls of a parent come in reverse order.
+	let addresses = frames.keys().rev().cloned().collect::<Vec<_>>();
+	for address in addresses {
+		let parent_address = match address.split_last() {
+			Some((_, parent_address)) => parent_address.to_vec(),
+			None => continue,
+		};
+		let frame = frames.remove(&address)?;
+		frames.get_mut(&parent_address)?.calls.insert(0, frame);
+	}
+
+	frames.remove(&Vec::new())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn call(trace_address: Vec<u32>) -> Call {
+		Call {
+			call_type: CallType::Call,
+			from: H160::default(),
+			to: H160::repeat_byte(trace_address.len() as u8),
+			value: U256::zero(),
+			gas: U256::from(trace_address.iter().map(|i| *i as u64 + 1).sum::<u64>()),
+			gas_used: U256::zero(),
+			input: Vec::new(),
+			result: CallResult::Output(Vec::new()),
+			trace_address,
+		}
+	}
+
+	#[test]
+	fn calls_are_nested_under_their_parents() {
+		let tree = call_tree(vec![
+			call(vec![]),
+			call(vec![0]),
+			call(vec![0, 0]),
+			call(vec![1]),
+			call(vec![1, 0]),
+			call(vec![1, 1]),
+		]).unwrap();
+
+		assert_eq!(tree.calls.len(), 2);
+		assert_eq!(tree.calls[0].calls.len(), 1);
+		assert_eq!(tree.calls[1].calls.len(), 2);
+		assert_eq!(tree.calls[1].calls[1].gas, U256::from(4));
+	}
+
+	#[test]
+	fn call_without_parent_is_rejected() {
+		assert!(call_tree(vec![call(vec![]), call(vec![2, 0])]).is_none());
+	}
+
+	#[test]
+	fn no_calls_give_an_empty_root() {
+		let tree = call_tree(Vec::new()).unwrap();
+		assert!(tree.calls.is_empty());
+	}
+}
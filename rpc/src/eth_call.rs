@@ -0,0 +1,104 @@
+// This file is part of Mathchain.
+
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! `eth_call` with a gas cap.
+//!
+//! Requests without a gas limit, or above the cap, are executed with the cap,
+//! so that a public node cannot be made to run arbitrarily long calls.
+
+use std::{sync::Arc, marker::PhantomData};
+use jsonrpc_core::Result;
+use jsonrpc_derive::rpc;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_core::{H256, U256};
+use sp_runtime::{generic::BlockId, traits::Block as BlockT};
+use fc_rpc_core::types::{BlockNumber, Bytes, CallRequest};
+use fp_rpc::EthereumRuntimeRPCApi;
+
+use crate::{internal_err, substrate_hash, eth_light::error_on_execution_failure};
+
+#[rpc(server)]
+pub trait EthCallApi {
+	/// Executes a new message call immediately, without creating a transaction.
+	#[rpc(name = "eth_call")]
+	fn call(&self, request: CallRequest, number: Option<BlockNumber>) -> Result<Bytes>;
+}
+
+pub struct EthCall<B: BlockT, C> {
+	client: Arc<C>,
+	frontier_backend: Arc<fc_db::Backend<B>>,
+	gas_cap: u64,
+	_marker: PhantomData<B>,
+}
+
+impl<B: BlockT, C> EthCall<B, C> {
+	pub fn new(client: Arc<C>, frontier_backend: Arc<fc_db::Backend<B>>, gas_cap: u64) -> Self {
+		Self { client, frontier_backend, gas_cap, _marker: PhantomData }
+	}
+}
+
+impl<B, C> EthCallApi for EthCall<B, C> where
+	B: BlockT<Hash=H256> + Send + Sync + 'static,
+	C: ProvideRuntimeApi<B> + HeaderBackend<B> + Send + Sync + 'static,
+	C::Api: EthereumRuntimeRPCApi<B>,
+{
+	fn call(&self, request: CallRequest, number: Option<BlockNumber>) -> Result<Bytes> {
+		let hash = substrate_hash(
+			&*self.client,
+			&self.frontier_backend,
+			number.unwrap_or(BlockNumber::Latest),
+		)?;
+		let id = BlockId::Hash(hash);
+
+		let CallRequest {
+			from,
+			to,
+			gas_price,
+			gas,
+			value,
+			data,
+			nonce
+		} = request;
+
+		let gas_limit = gas.unwrap_or(U256::from(self.gas_cap)).min(U256::from(self.gas_cap));
+		let data = data.map(|d| d.0).unwrap_or_default();
+		let from = from.unwrap_or_default();
+		let value = value.unwrap_or_default();
+
+		match to {
+			Some(to) => {
+				let info = self.client.runtime_api()
+					.call(&id, from, to, data, value, gas_limit, gas_price, nonce, false)
+					.map_err(|err| internal_err(format!("runtime error: {:?}", err)))?
+					.map_err(|err| internal_err(format!("execution fatal: {:?}", err)))?;
+
+				error_on_execution_failure(&info.exit_reason, &info.value)?;
+				Ok(Bytes(info.value))
+			},
+			None => {
+				let info = self.client.runtime_api()
+					.create(&id, from, data, value, gas_limit, gas_price, nonce, false)
+					.map_err(|err| internal_err(format!("runtime error: {:?}", err)))?
+					.map_err(|err| internal_err(format!("execution fatal: {:?}", err)))?;
+
+				error_on_execution_failure(&info.exit_reason, &[])?;
+				Ok(Bytes(info.value[..].to_vec()))
+			},
+		}
+	}
+}
@@ -0,0 +1,430 @@
+// This file is part of Mathchain.
+
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Ethereum compatible RPC for light clients.
+//!
+//! A light client has no state of its own, so every call is answered by
+//! fetching a proof from a full peer through the `Fetcher`: execution proofs
+//! for `EthereumRuntimeRPCApi` calls and storage proofs for raw EVM storage.
+
+use std::sync::Arc;
+use codec::{Encode, Decode};
+use futures::{FutureExt, TryFutureExt, future::ready};
+use jsonrpc_core::{BoxFuture, Result, Value, futures::Future as _};
+use jsonrpc_derive::rpc;
+use sha3::{Digest, Keccak256};
+use sp_core::{H160, H256, U256, hashing::{twox_128, blake2_128}};
+use sp_runtime::{
+	generic::BlockId,
+	traits::{Block as BlockT, UniqueSaturatedInto},
+	transaction_validity::TransactionSource,
+	DispatchError,
+};
+use sp_blockchain::{HeaderBackend, Error as ClientError};
+use sp_transaction_pool::TransactionPool;
+use sc_client_api::light::{
+	future_header, RemoteBlockchain, Fetcher, RemoteCallRequest, RemoteReadRequest,
+};
+use sc_network::{NetworkService, ExHashT};
+use fc_rpc_core::types::{BlockNumber, Bytes, CallRequest};
+use fp_rpc::ConvertTransaction;
+use pallet_evm::{Account as EVMAccount, CallInfo, CreateInfo, ExitReason};
+use rustc_hex::ToHex;
+
+use crate::internal_err;
+
+/// Ethereum RPC methods available on a light client.
+#[rpc(server)]
+pub trait EthLightApi {
+	/// Returns the chain id.
+	#[rpc(name = "eth_chainId")]
+	fn chain_id(&self) -> BoxFuture<U256>;
+
+	/// Returns the highest block number known to the light client.
+	#[rpc(name = "eth_blockNumber")]
+	fn block_number(&self) -> Result<U256>;
+
+	/// Returns the balance of the given account.
+	#[rpc(name = "eth_getBalance")]
+	fn balance(&self, address: H160, number: Option<BlockNumber>) -> BoxFuture<U256>;
+
+	/// Returns the number of transactions sent from the given address.
+	#[rpc(name = "eth_getTransactionCount")]
+	fn transaction_count(&self, address: H160, number: Option<BlockNumber>) -> BoxFuture<U256>;
+
+	/// Returns the code at the given address.
+	#[rpc(name = "eth_getCode")]
+	fn code_at(&self, address: H160, number: Option<BlockNumber>) -> BoxFuture<Bytes>;
+
+	/// Returns the content of the storage at the given address.
+	#[rpc(name = "eth_getStorageAt")]
+	fn storage_at(&self, address: H160, index: U256, number: Option<BlockNumber>) -> BoxFuture<H256>;
+
+	/// Executes a new message call immediately without creating a transaction.
+	#[rpc(name = "eth_call")]
+	fn call(&self, request: CallRequest, number: Option<BlockNumber>) -> BoxFuture<Bytes>;
+
+	/// Sends a signed transaction to the network through the light transaction pool.
+	#[rpc(name = "eth_sendRawTransaction")]
+	fn send_raw_transaction(&self, bytes: Bytes) -> BoxFuture<H256>;
+
+	/// Returns the protocol version, i.e. the chain id.
+	#[rpc(name = "net_version")]
+	fn net_version(&self) -> BoxFuture<String>;
+
+	/// Returns the number of connected peers.
+	#[rpc(name = "net_peerCount")]
+	fn net_peer_count(&self) -> Result<String>;
+
+	/// Returns true if the client is actively listening for network connections.
+	#[rpc(name = "net_listening")]
+	fn net_listening(&self) -> Result<bool>;
+
+	/// Returns the current client version.
+	#[rpc(name = "web3_clientVersion")]
+	fn client_version(&self) -> BoxFuture<String>;
+
+	/// Returns the Keccak-256 of the given data.
+	#[rpc(name = "web3_sha3")]
+	fn sha3(&self, input: Bytes) -> Result<H256>;
+}
+
+/// Ethereum RPC handler for light clients.
+pub struct EthLight<B: BlockT, C, F, P, CT, H: ExHashT> {
+	client: Arc<C>,
+	remote_blockchain: Arc<dyn RemoteBlockchain<B>>,
+	fetcher: Arc<F>,
+	pool: Arc<P>,
+	network: Arc<NetworkService<B, H>>,
+	convert_transaction: CT,
+}
+
+impl<B: BlockT, C, F, P, CT, H: ExHashT> EthLight<B, C, F, P, CT, H> {
+	pub fn new(
+		client: Arc<C>,
+		remote_blockchain: Arc<dyn RemoteBlockchain<B>>,
+		fetcher: Arc<F>,
+		pool: Arc<P>,
+		network: Arc<NetworkService<B, H>>,
+		convert_transaction: CT,
+	) -> Self {
+		Self {
+			client,
+			remote_blockchain,
+			fetcher,
+			pool,
+			network,
+			convert_transaction,
+		}
+	}
+}
+
+impl<B, C, F, P, CT, H: ExHashT> EthLight<B, C, F, P, CT, H> where
+	B: BlockT<Hash=H256> + Send + Sync + 'static,
+	C: HeaderBackend<B> + Send + Sync + 'static,
+	F: Fetcher<B> + 'static,
+{
+	/// Resolve the requested block into a Substrate block hash known to the light client.
+	///
+	/// Ethereum block hashes are only indexed by full nodes, so they cannot be used here.
+	fn block_hash(&self, number: Option<BlockNumber>) -> Result<B::Hash> {
+		match number.unwrap_or(BlockNumber::Latest) {
+			BlockNumber::Latest | BlockNumber::Pending => Ok(self.client.info().best_hash),
+			BlockNumber::Earliest => Ok(self.client.info().genesis_hash),
+			BlockNumber::Num(number) => self.client
+				.hash(number.unique_saturated_into())
+				.map_err(|err| internal_err(format!("fetch block hash failed: {:?}", err)))?
+				.ok_or_else(|| internal_err(format!("block {} not found", number))),
+			BlockNumber::Hash { .. } => Err(internal_err(
+				"querying by Ethereum block hash is not supported on light clients"
+			)),
+		}
+	}
+
+	/// Fetch the header of `hash` from the network.
+	fn remote_header(
+		&self,
+		hash: B::Hash,
+	) -> impl futures::Future<Output = std::result::Result<B::Header, ClientError>> {
+		future_header(&*self.remote_blockchain, &*self.fetcher, BlockId::Hash(hash))
+			.and_then(move |header| ready(
+				header.ok_or_else(|| ClientError::UnknownBlock(format!("{}", hash)))
+			))
+	}
+
+	/// Execute a runtime API method on a full peer and check the returned execution proof.
+	fn remote_call<R: Decode + Send + 'static>(
+		&self,
+		hash: B::Hash,
+		method: &'static str,
+		call_data: Vec<u8>,
+	) -> BoxFuture<R> {
+		let fetcher = self.fetcher.clone();
+		let future_result = self.remote_header(hash)
+			.and_then(move |header| fetcher.remote_call(RemoteCallRequest {
+				block: hash,
+				header,
+				method: method.into(),
+				call_data,
+				retry_count: None,
+			}))
+			.and_then(move |result| ready(
+				R::decode(&mut &result[..]).map_err(|e| ClientError::CallResultDecode(method, e))
+			))
+			.map_err(move |err| internal_err(format!("remote call {} failed: {:?}", method, err)));
+
+		Box::new(future_result.boxed().compat())
+	}
+
+	/// Read a single storage value from a full peer and check the returned storage proof.
+	fn remote_read(&self, hash: B::Hash, key: Vec<u8>) -> BoxFuture<Option<Vec<u8>>> {
+		let fetcher = self.fetcher.clone();
+		let future_result = self.remote_header(hash)
+			.and_then(move |header| {
+				let request = RemoteReadRequest {
+					block: hash,
+					header,
+					keys: vec![key.clone()],
+					retry_count: None,
+				};
+				fetcher.remote_read(request)
+					.map_ok(move |mut values| values.remove(&key).flatten())
+			})
+			.map_err(|err| internal_err(format!("remote read failed: {:?}", err)));
+
+		Box::new(future_result.boxed().compat())
+	}
+}
+
+/// Storage key of `pallet_evm::AccountCodes`.
+fn account_code_key(address: H160) -> Vec<u8> {
+	let mut key = storage_prefix(b"EVM", b"AccountCodes");
+	key.extend(blake2_128_concat(address.as_bytes()));
+	key
+}
+
+/// Storage key of `pallet_evm::AccountStorages`.
+fn account_storage_key(address: H160, index: H256) -> Vec<u8> {
+	let mut key = storage_prefix(b"EVM", b"AccountStorages");
+	key.extend(blake2_128_concat(address.as_bytes()));
+	key.extend(blake2_128_concat(index.as_bytes()));
+	key
+}
+
+fn storage_prefix(module: &[u8], storage: &[u8]) -> Vec<u8> {
+	let mut key = twox_128(module).to_vec();
+	key.extend(&twox_128(storage));
+	key
+}
+
+fn blake2_128_concat(data: &[u8]) -> Vec<u8> {
+	let mut key = blake2_128(data).to_vec();
+	key.extend(data);
+	key
+}
+
+pub(crate) fn error_on_execution_failure(reason: &ExitReason, data: &[u8]) -> Result<()> {
+	match reason {
+		ExitReason::Succeed(_) => Ok(()),
+		reason => Err(jsonrpc_core::Error {
+			code: jsonrpc_core::ErrorCode::InternalError,
+			message: format!("execution failed: {:?}", reason),
+			data: Some(Value::String(format!("0x{}", data.to_hex::<String>()))),
+		}),
+	}
+}
+
+impl<B, C, F, P, CT, H: ExHashT> EthLightApi for EthLight<B, C, F, P, CT, H> where
+	B: BlockT<Hash=H256> + Send + Sync + 'static,
+	C: HeaderBackend<B> + Send + Sync + 'static,
+	F: Fetcher<B> + 'static,
+	P: TransactionPool<Block=B> + Send + Sync + 'static,
+	CT: ConvertTransaction<<B as BlockT>::Extrinsic> + Send + Sync + 'static,
+{
+	fn chain_id(&self) -> BoxFuture<U256> {
+		let hash = self.client.info().best_hash;
+		Box::new(
+			self.remote_call::<u64>(hash, "EthereumRuntimeRPCApi_chain_id", Vec::new())
+				.map(U256::from)
+		)
+	}
+
+	fn block_number(&self) -> Result<U256> {
+		Ok(U256::from(UniqueSaturatedInto::<u128>::unique_saturated_into(
+			self.client.info().best_number
+		)))
+	}
+
+	fn balance(&self, address: H160, number: Option<BlockNumber>) -> BoxFuture<U256> {
+		let hash = match self.block_hash(number) {
+			Ok(hash) => hash,
+			Err(err) => return Box::new(futures::future::err(err).compat()),
+		};
+		// The H160 to account mapping is runtime defined, so go through the runtime API.
+		Box::new(
+			self.remote_call::<EVMAccount>(hash, "EthereumRuntimeRPCApi_account_basic", address.encode())
+				.map(|account| account.balance)
+		)
+	}
+
+	fn transaction_count(&self, address: H160, number: Option<BlockNumber>) -> BoxFuture<U256> {
+		let hash = match self.block_hash(number) {
+			Ok(hash) => hash,
+			Err(err) => return Box::new(futures::future::err(err).compat()),
+		};
+		Box::new(
+			self.remote_call::<EVMAccount>(hash, "EthereumRuntimeRPCApi_account_basic", address.encode())
+				.map(|account| account.nonce)
+		)
+	}
+
+	fn code_at(&self, address: H160, number: Option<BlockNumber>) -> BoxFuture<Bytes> {
+		let hash = match self.block_hash(number) {
+			Ok(hash) => hash,
+			Err(err) => return Box::new(futures::future::err(err).compat()),
+		};
+		Box::new(
+			self.remote_read(hash, account_code_key(address))
+				.and_then(|value| match value {
+					Some(value) => Vec::<u8>::decode(&mut &value[..])
+						.map(Bytes)
+						.map_err(|err| internal_err(format!("decode code failed: {:?}", err))),
+					None => Ok(Bytes(Vec::new())),
+				})
+		)
+	}
+
+	fn storage_at(&self, address: H160, index: U256, number: Option<BlockNumber>) -> BoxFuture<H256> {
+		let hash = match self.block_hash(number) {
+			Ok(hash) => hash,
+			Err(err) => return Box::new(futures::future::err(err).compat()),
+		};
+		let mut tmp = [0u8; 32];
+		index.to_big_endian(&mut tmp);
+		Box::new(
+			self.remote_read(hash, account_storage_key(address, H256::from(tmp)))
+				.and_then(|value| match value {
+					Some(value) => H256::decode(&mut &value[..])
+						.map_err(|err| internal_err(format!("decode storage failed: {:?}", err))),
+					None => Ok(H256::default()),
+				})
+		)
+	}
+
+	fn call(&self, request: CallRequest, number: Option<BlockNumber>) -> BoxFuture<Bytes> {
+		let hash = match self.block_hash(number) {
+			Ok(hash) => hash,
+			Err(err) => return Box::new(futures::future::err(err).compat()),
+		};
+
+		let CallRequest {
+			from,
+			to,
+			gas_price,
+			gas,
+			value,
+			data,
+			nonce
+		} = request;
+
+		let gas_limit = gas.unwrap_or(U256::from(u64::max_value()));
+		let data = data.map(|d| d.0).unwrap_or_default();
+		let from = from.unwrap_or_default();
+		let value = value.unwrap_or_default();
+
+		match to {
+			Some(to) => Box::new(
+				self.remote_call::<std::result::Result<CallInfo, DispatchError>>(
+					hash,
+					"EthereumRuntimeRPCApi_call",
+					(from, to, data, value, gas_limit, gas_price, nonce, false).encode(),
+				).and_then(|info| {
+					let info = info.map_err(|err| internal_err(format!("runtime error: {:?}", err)))?;
+					error_on_execution_failure(&info.exit_reason, &info.value)?;
+					Ok(Bytes(info.value))
+				})
+			),
+			None => Box::new(
+				self.remote_call::<std::result::Result<CreateInfo, DispatchError>>(
+					hash,
+					"EthereumRuntimeRPCApi_create",
+					(from, data, value, gas_limit, gas_price, nonce, false).encode(),
+				).and_then(|info| {
+					let info = info.map_err(|err| internal_err(format!("runtime error: {:?}", err)))?;
+					error_on_execution_failure(&info.exit_reason, &[])?;
+					Ok(Bytes(info.value[..].to_vec()))
+				})
+			),
+		}
+	}
+
+	fn send_raw_transaction(&self, bytes: Bytes) -> BoxFuture<H256> {
+		let transaction = match rlp::decode::<ethereum::Transaction>(&bytes.0[..]) {
+			Ok(transaction) => transaction,
+			Err(_) => return Box::new(
+				futures::future::err(internal_err("decode transaction failed")).compat()
+			),
+		};
+		let transaction_hash = H256::from_slice(
+			Keccak256::digest(&rlp::encode(&transaction)).as_slice()
+		);
+		let hash = self.client.info().best_hash;
+		Box::new(
+			self.pool
+				.submit_one(
+					&BlockId::hash(hash),
+					TransactionSource::Local,
+					self.convert_transaction.convert_transaction(transaction),
+				)
+				.map_ok(move |_| transaction_hash)
+				.map_err(|err| internal_err(format!("submit transaction to pool failed: {:?}", err)))
+				.boxed()
+				.compat()
+		)
+	}
+
+	fn net_version(&self) -> BoxFuture<String> {
+		Box::new(self.chain_id().map(|chain_id| chain_id.to_string()))
+	}
+
+	fn net_peer_count(&self) -> Result<String> {
+		Ok(self.network.num_connected().to_string())
+	}
+
+	fn net_listening(&self) -> Result<bool> {
+		Ok(true)
+	}
+
+	fn client_version(&self) -> BoxFuture<String> {
+		let hash = self.client.info().best_hash;
+		Box::new(
+			self.remote_call::<sp_version::RuntimeVersion>(hash, "Core_version", Vec::new())
+				.map(|version| format!(
+					"{spec_name}/v{spec_version}.{impl_version}/{pkg_name}-{pkg_version}/light",
+					spec_name = version.spec_name,
+					spec_version = version.spec_version,
+					impl_version = version.impl_version,
+					pkg_name = env!("CARGO_PKG_NAME"),
+					pkg_version = env!("CARGO_PKG_VERSION"),
+				))
+		)
+	}
+
+	fn sha3(&self, input: Bytes) -> Result<H256> {
+		Ok(H256::from_slice(Keccak256::digest(&input.into_vec()).as_slice()))
+	}
+}
@@ -0,0 +1,304 @@
+// This file is part of Mathchain.
+
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! `eth_getLogs` answered from the log index of the Frontier DB.
+//!
+//! `LogIndexTask::sync_task` indexes the logs of every canonical block by
+//! emitter address and topic, so that only the blocks that may hold matching
+//! logs are read, instead of scanning the whole requested range.
+
+use std::{sync::Arc, marker::PhantomData};
+use futures::StreamExt;
+use jsonrpc_core::Result;
+use jsonrpc_derive::rpc;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_core::{H160, H256, U256};
+use sp_runtime::{generic::BlockId, traits::{Block as BlockT, Header as HeaderT, UniqueSaturatedInto}};
+use sc_client_api::BlockchainEvents;
+use fc_rpc_core::types::{BlockNumber, Bytes, Filter, Log, VariadicValue};
+use fp_rpc::EthereumRuntimeRPCApi;
+use mathchain_db::{BlockLogs, TransactionLog};
+
+use crate::{internal_err, block_number, OverrideHandle};
+
+/// Blocks indexed at most for each import notification while catching up.
+const MAX_BLOCKS_PER_SYNC: u64 = 256;
+
+/// Blocks checked at most for retracted logs after a re-org.
+const MAX_REORG_DEPTH: u64 = 256;
+
+#[rpc(server)]
+pub trait EthLogsApi {
+	/// Returns the logs matching the given filter.
+	#[rpc(name = "eth_getLogs")]
+	fn logs(&self, filter: Filter) -> Result<Vec<Log>>;
+}
+
+pub struct EthLogs<B: BlockT, C> {
+	client: Arc<C>,
+	frontier_backend: Arc<fc_db::Backend<B>>,
+	backend: Arc<mathchain_db::Backend<B>>,
+	overrides: Arc<OverrideHandle<B>>,
+	max_block_range: u64,
+	max_results: usize,
+}
+
+impl<B: BlockT, C> EthLogs<B, C> {
+	pub fn new(
+		client: Arc<C>,
+		frontier_backend: Arc<fc_db::Backend<B>>,
+		backend: Arc<mathchain_db::Backend<B>>,
+		overrides: Arc<OverrideHandle<B>>,
+		max_block_range: u64,
+		max_results: usize,
+	) -> Self {
+		Self { client, frontier_backend, backend, overrides, max_block_range, max_results }
+	}
+}
+
+impl<B, C> EthLogs<B, C> where
+	B: BlockT<Hash=H256> + Send + Sync + 'static,
+	C: ProvideRuntimeApi<B> + HeaderBackend<B> + Send + Sync + 'static,
+	C::Api: EthereumRuntimeRPCApi<B>,
+{
+	/// Logs of the canonical block at `number`, from the index when it is up to date.
+	fn block_logs(&self, number: u64) -> Result<BlockLogs<B::Hash>> {
+		let canon_hash = self.client.hash(number.unique_saturated_into())
+			.map_err(|err| internal_err(format!("fetch block hash failed: {:?}", err)))?;
+
+		let indexed = self.backend.logs()
+			.block_logs(number)
+			.map_err(|err| internal_err(format!("fetch logs failed: {}", err)))?;
+		match indexed {
+			Some(logs) if Some(logs.block_hash) == canon_hash => Ok(logs),
+			_ => read_block_logs(&*self.client, &self.overrides, number).map_err(internal_err),
+		}
+	}
+}
+
+impl<B, C> EthLogsApi for EthLogs<B, C> where
+	B: BlockT<Hash=H256> + Send + Sync + 'static,
+	C: ProvideRuntimeApi<B> + HeaderBackend<B> + Send + Sync + 'static,
+	C::Api: EthereumRuntimeRPCApi<B>,
+{
+	fn logs(&self, filter: Filter) -> Result<Vec<Log>> {
+		let best = UniqueSaturatedInto::<u64>::unique_saturated_into(self.client.info().best_number);
+		let (from, to) = match filter.block_hash {
+			Some(hash) => {
+				let number = block_number(
+					&*self.client,
+					&self.frontier_backend,
+					Some(BlockNumber::Hash { hash, require_canonical: false }),
+				)?;
+				(number, number)
+			},
+			None => (
+				block_number(&*self.client, &self.frontier_backend, filter.from_block)?,
+				block_number(&*self.client, &self.frontier_backend, filter.to_block)?.min(best),
+			),
+		};
+		if to < from {
+			return Ok(Vec::new());
+		}
+		if to - from >= self.max_block_range {
+			return Err(internal_err(format!(
+				"block range is too wide (maximum {})", self.max_block_range
+			)));
+		}
+
+		let addresses = match filter.address {
+			Some(VariadicValue::Single(address)) => vec![address],
+			Some(VariadicValue::Multiple(addresses)) => addresses,
+			_ => Vec::new(),
+		};
+		let topics = filter.topics.map(filter_topics).unwrap_or_default();
+
+		// Blocks past the index, or retracted and not indexed again yet, are read
+		// from the runtime directly.
+		let synced = canonical_synced(&*self.client, &self.backend)
+			.map_err(|err| internal_err(format!("fetch logs failed: {}", err)))?
+			.unwrap_or(0);
+		let mut candidates = if from <= synced {
+			self.backend.logs()
+				.candidate_blocks(from, to.min(synced), &addresses, &topics)
+				.map_err(|err| internal_err(format!("fetch log index failed: {}", err)))?
+		} else {
+			Vec::new()
+		};
+		candidates.extend(from.max(synced + 1)..=to);
+
+		let mut logs = Vec::new();
+		for number in candidates {
+			let BlockLogs { ethereum_block_hash, logs: block_logs, .. } = self.block_logs(number)?;
+
+			for TransactionLog { transaction_hash, transaction_index, transaction_log_index, log_index, log } in block_logs {
+				if !matches(&log, &addresses, &topics) {
+					continue;
+				}
+				if logs.len() >= self.max_results {
+					return Err(internal_err(format!(
+						"query returned more than {} results", self.max_results
+					)));
+				}
+				logs.push(Log {
+					address: log.address,
+					topics: log.topics,
+					data: Bytes(log.data),
+					block_hash: Some(ethereum_block_hash),
+					block_number: Some(U256::from(number)),
+					transaction_hash: Some(transaction_hash),
+					transaction_index: Some(U256::from(transaction_index)),
+					log_index: Some(U256::from(log_index)),
+					transaction_log_index: Some(U256::from(transaction_log_index)),
+					removed: false,
+				});
+			}
+		}
+
+		Ok(logs)
+	}
+}
+
+/// Accepted topics at each position, an empty set accepting anything.
+fn filter_topics(topics: VariadicValue<Option<VariadicValue<Option<H256>>>>) -> Vec<Vec<H256>> {
+	let position = |topic: Option<VariadicValue<Option<H256>>>| match topic {
+		Some(VariadicValue::Single(Some(topic))) => vec![topic],
+		// A `null` alternative turns the position into a wildcard.
+		Some(VariadicValue::Multiple(topics)) if topics.iter().all(Option::is_some) =>
+			topics.into_iter().flatten().collect(),
+		_ => Vec::new(),
+	};
+
+	match topics {
+		VariadicValue::Single(topic) => vec![position(topic)],
+		VariadicValue::Multiple(topics) => topics.into_iter().map(position).collect(),
+		VariadicValue::Null => Vec::new(),
+	}
+}
+
+fn matches(log: &ethereum::Log, addresses: &[H160], topics: &[Vec<H256>]) -> bool {
+	if !addresses.is_empty() && !addresses.contains(&log.address) {
+		return false;
+	}
+	if topics.len() > log.topics.len() {
+		return false;
+	}
+	topics.iter()
+		.zip(log.topics.iter())
+		.all(|(accepted, topic)| accepted.is_empty() || accepted.contains(topic))
+}
+
+/// Highest block up to which the log index only holds canonical blocks.
+fn canonical_synced<B, C>(
+	client: &C,
+	backend: &mathchain_db::Backend<B>,
+) -> std::result::Result<Option<u64>, String> where
+	B: BlockT,
+	C: HeaderBackend<B>,
+{
+	backend.logs().canonical_synced(
+		|number| client.hash(number.unique_saturated_into()).map_err(|err| format!("{:?}", err)),
+		MAX_REORG_DEPTH,
+	)
+}
+
+/// Logs of the canonical block at `number`, read with the storage schema of the block.
+fn read_block_logs<B, C>(
+	client: &C,
+	overrides: &OverrideHandle<B>,
+	number: u64,
+) -> std::result::Result<BlockLogs<B::Hash>, String> where
+	B: BlockT<Hash=H256> + Send + Sync + 'static,
+	C: ProvideRuntimeApi<B> + HeaderBackend<B>,
+	C::Api: EthereumRuntimeRPCApi<B>,
+{
+	let hash = client.hash(number.unique_saturated_into())
+		.map_err(|err| format!("{:?}", err))?
+		.ok_or_else(|| format!("block #{} not found", number))?;
+	let id = BlockId::Hash(hash);
+
+	let ethereum_block_hash = overrides.current_block(client, &id)?
+		.map(|block| block.header.hash())
+		.unwrap_or_default();
+	let statuses = overrides.current_transaction_statuses(client, &id)?.unwrap_or_default();
+
+	let mut logs = Vec::new();
+	for status in statuses {
+		for (transaction_log_index, log) in status.logs.into_iter().enumerate() {
+			let log_index = logs.len() as u32;
+			logs.push(TransactionLog {
+				transaction_hash: status.transaction_hash,
+				transaction_index: status.transaction_index,
+				transaction_log_index: transaction_log_index as u32,
+				log_index,
+				log,
+			});
+		}
+	}
+
+	Ok(BlockLogs { block_hash: hash, ethereum_block_hash, logs })
+}
+
+pub struct LogIndexTask<B, C>(PhantomData<(B, C)>);
+
+impl<B, C> LogIndexTask<B, C> where
+	B: BlockT<Hash=H256> + Send + Sync + 'static,
+	C: ProvideRuntimeApi<B> + HeaderBackend<B> + BlockchainEvents<B>,
+	C: Send + Sync + 'static,
+	C::Api: EthereumRuntimeRPCApi<B>,
+{
+	/// Index the logs of every new canonical block into the MathChain columns.
+	pub async fn sync_task(
+		client: Arc<C>,
+		backend: Arc<mathchain_db::Backend<B>>,
+		overrides: Arc<OverrideHandle<B>>,
+	) {
+		let mut notifications = client.import_notification_stream();
+
+		while let Some(notification) = notifications.next().await {
+			if !notification.is_new_best {
+				continue;
+			}
+
+			let best = UniqueSaturatedInto::<u64>::unique_saturated_into(*notification.header.number());
+			if let Err(err) = Self::sync_to(&*client, &*backend, &overrides, best) {
+				log::warn!(
+					target: "log-index",
+					"Failed to index logs up to block #{}: {}", best, err,
+				);
+			}
+		}
+	}
+
+	fn sync_to(
+		client: &C,
+		backend: &mathchain_db::Backend<B>,
+		overrides: &OverrideHandle<B>,
+		best: u64,
+	) -> std::result::Result<(), String> {
+		// Blocks retracted by a re-org have to be indexed again.
+		let synced = canonical_synced(client, backend)?;
+		let from = synced.map_or(0, |synced| synced + 1).min(best);
+
+		for number in from..=best.min(from + MAX_BLOCKS_PER_SYNC) {
+			backend.logs().write_block_logs(number, read_block_logs(client, overrides, number)?)?;
+		}
+
+		Ok(())
+	}
+}
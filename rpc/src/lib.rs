@@ -0,0 +1,109 @@
+// This file is part of Mathchain.
+
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! MathChain specific RPC extensions.
+
+mod account_service;
+mod asset_fee_payment;
+mod debug;
+mod eth_call;
+mod eth_light;
+mod eth_logs;
+mod overrides;
+mod recovery;
+mod secretstore;
+mod signer;
+mod trace;
+mod txpool;
+
+pub use crate::account_service::{AccountService, AccountServiceApi};
+pub use crate::asset_fee_payment::{AssetFeePayment, AssetFeePaymentApi};
+pub use crate::debug::{Debug, DebugApi, TraceParams};
+pub use crate::eth_call::{EthCall, EthCallApi};
+pub use crate::eth_light::{EthLight, EthLightApi};
+pub use crate::eth_logs::{EthLogs, EthLogsApi, LogIndexTask};
+pub use crate::overrides::{OverrideHandle, SCHEMAS, onchain_storage_schema, schema_overrides};
+pub use crate::recovery::{Recovery, RecoveryApi};
+pub use crate::secretstore::{SecretStore, SecretStoreApi};
+pub use crate::signer::{EthKeystoreSigner, EthSign, EthSignApi, KEY_TYPE as ETH_SIGNER_KEY_TYPE};
+pub use crate::trace::{Trace, TraceApi, TraceFilter, TraceTask};
+pub use crate::txpool::{TxPool, TxPoolApi};
+
+use jsonrpc_core::{Error, ErrorCode, Result};
+use sp_blockchain::HeaderBackend;
+use sp_core::H256;
+use sp_runtime::{generic::BlockId, traits::{Block as BlockT, Header as HeaderT, UniqueSaturatedInto}};
+use fc_rpc_core::types::BlockNumber;
+
+/// Build an internal JSON-RPC error out of any message.
+pub fn internal_err<T: ToString>(message: T) -> Error {
+	Error {
+		code: ErrorCode::InternalError,
+		message: message.to_string(),
+		data: None,
+	}
+}
+
+/// Hash of the Substrate block designated by `number`.
+pub(crate) fn substrate_hash<B, C>(
+	client: &C,
+	frontier_backend: &fc_db::Backend<B>,
+	number: BlockNumber,
+) -> Result<B::Hash> where
+	B: BlockT<Hash=H256>,
+	C: HeaderBackend<B>,
+{
+	match number {
+		BlockNumber::Latest | BlockNumber::Pending => Ok(client.info().best_hash),
+		BlockNumber::Earliest => Ok(client.info().genesis_hash),
+		BlockNumber::Num(number) => client
+			.hash(number.unique_saturated_into())
+			.map_err(|err| internal_err(format!("fetch block hash failed: {:?}", err)))?
+			.ok_or_else(|| internal_err(format!("block {} not found", number))),
+		BlockNumber::Hash { hash, .. } => frontier_backend.mapping()
+			.block_hash(&hash)
+			.map_err(|err| internal_err(format!("fetch aux store failed: {:?}", err)))?
+			.ok_or_else(|| internal_err(format!("block {:?} not found", hash))),
+	}
+}
+
+/// Number of the Substrate block designated by `number`, the best block by default.
+pub(crate) fn block_number<B, C>(
+	client: &C,
+	frontier_backend: &fc_db::Backend<B>,
+	number: Option<BlockNumber>,
+) -> Result<u64> where
+	B: BlockT<Hash=H256>,
+	C: HeaderBackend<B>,
+{
+	match number.unwrap_or(BlockNumber::Latest) {
+		BlockNumber::Latest | BlockNumber::Pending =>
+			Ok(UniqueSaturatedInto::<u64>::unique_saturated_into(client.info().best_number)),
+		BlockNumber::Earliest => Ok(0),
+		BlockNumber::Num(number) => Ok(number),
+		BlockNumber::Hash { hash, .. } => {
+			let substrate_hash = frontier_backend.mapping()
+				.block_hash(&hash)
+				.map_err(|err| internal_err(format!("fetch aux store failed: {:?}", err)))?
+				.ok_or_else(|| internal_err(format!("block {:?} not found", hash)))?;
+			let header = client.header(BlockId::Hash(substrate_hash))
+				.map_err(|err| internal_err(format!("fetch header failed: {:?}", err)))?
+				.ok_or_else(|| internal_err(format!("block {:?} not found", hash)))?;
+			Ok(UniqueSaturatedInto::<u64>::unique_saturated_into(*header.number()))
+		},
+	}
+}
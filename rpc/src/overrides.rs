@@ -0,0 +1,234 @@
+// This file is part of Mathchain.
+
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Ethereum data read with the storage schema of each block.
+//!
+//! `pallet_ethereum` stores the schema of its storage under `:ethereum_schema`,
+//! so a block is read with the override of the schema it was written with, even
+//! after the schema of the chain was upgraded. Blocks of a schema without an
+//! override are read through the runtime API, at the runtime of the block.
+//!
+//! To support a new schema, add it to `SCHEMAS` and to `schema_override`.
+
+use std::{collections::BTreeMap, sync::Arc};
+use codec::Decode;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_core::{H256, storage::StorageKey};
+use sp_runtime::{generic::BlockId, traits::{BlakeTwo256, Block as BlockT}};
+use sc_client_api::backend::{Backend, StateBackend, StorageProvider};
+use fc_rpc::{StorageOverride, SchemaV1Override};
+use fp_rpc::{EthereumRuntimeRPCApi, TransactionStatus};
+use pallet_ethereum::{EthereumStorageSchema, PALLET_ETHEREUM_SCHEMA};
+
+/// Storage schemas of `pallet_ethereum` this node can read.
+pub const SCHEMAS: &[EthereumStorageSchema] = &[
+	EthereumStorageSchema::Undefined,
+	EthereumStorageSchema::V1,
+];
+
+/// Storage override of `schema`, `None` to read it through the runtime API.
+fn schema_override<B, C, BE>(
+	client: Arc<C>,
+	schema: EthereumStorageSchema,
+) -> Option<Box<dyn StorageOverride<B> + Send + Sync>> where
+	B: BlockT<Hash=H256> + Send + Sync + 'static,
+	C: ProvideRuntimeApi<B> + StorageProvider<B, BE> + HeaderBackend<B> + Send + Sync + 'static,
+	C::Api: EthereumRuntimeRPCApi<B>,
+	BE: Backend<B> + 'static,
+	BE::State: StateBackend<BlakeTwo256>,
+{
+	match schema {
+		// Written before the schema was recorded, the layout is not known.
+		EthereumStorageSchema::Undefined => None,
+		EthereumStorageSchema::V1 => Some(Box::new(SchemaV1Override::new(client))),
+	}
+}
+
+/// Storage overrides of every schema in `SCHEMAS` which has one.
+pub fn schema_overrides<B, C, BE>(
+	client: Arc<C>,
+) -> BTreeMap<EthereumStorageSchema, Box<dyn StorageOverride<B> + Send + Sync>> where
+	B: BlockT<Hash=H256> + Send + Sync + 'static,
+	C: ProvideRuntimeApi<B> + StorageProvider<B, BE> + HeaderBackend<B> + Send + Sync + 'static,
+	C::Api: EthereumRuntimeRPCApi<B>,
+	BE: Backend<B> + 'static,
+	BE::State: StateBackend<BlakeTwo256>,
+{
+	SCHEMAS.iter()
+		.filter_map(|schema| schema_override(client.clone(), *schema).map(|o| (*schema, o)))
+		.collect()
+}
+
+/// Storage schema of `pallet_ethereum` at `id`.
+pub fn onchain_storage_schema<B, C, BE>(client: &C, id: &BlockId<B>) -> EthereumStorageSchema where
+	B: BlockT,
+	C: StorageProvider<B, BE>,
+	BE: Backend<B>,
+{
+	client.storage(id, &StorageKey(PALLET_ETHEREUM_SCHEMA.to_vec()))
+		.ok()
+		.flatten()
+		.and_then(|data| EthereumStorageSchema::decode(&mut &data.0[..]).ok())
+		.unwrap_or(EthereumStorageSchema::Undefined)
+}
+
+/// Storage overrides, with the detection of the schema of a block.
+///
+/// `S` is the storage schema, generic so that tests can serve schemas
+/// `pallet_ethereum` does not have yet.
+pub struct OverrideHandle<B: BlockT, S = EthereumStorageSchema> {
+	overrides: BTreeMap<S, Box<dyn StorageOverride<B> + Send + Sync>>,
+	schema_at: Box<dyn Fn(&BlockId<B>) -> S + Send + Sync>,
+}
+
+impl<B: BlockT<Hash=H256> + Send + Sync + 'static> OverrideHandle<B> {
+	pub fn new<C, BE>(client: Arc<C>) -> Self where
+		C: ProvideRuntimeApi<B> + StorageProvider<B, BE> + HeaderBackend<B> + Send + Sync + 'static,
+		C::Api: EthereumRuntimeRPCApi<B>,
+		BE: Backend<B> + 'static,
+		BE::State: StateBackend<BlakeTwo256>,
+	{
+		Self {
+			overrides: schema_overrides(client.clone()),
+			schema_at: Box::new(move |id| onchain_storage_schema::<B, C, BE>(&*client, id)),
+		}
+	}
+}
+
+impl<B: BlockT<Hash=H256> + Send + Sync + 'static, S: Ord> OverrideHandle<B, S> {
+	/// Storage override of the schema at `id`, `None` if it has none.
+	pub fn for_block(&self, id: &BlockId<B>) -> Option<&(dyn StorageOverride<B> + Send + Sync)> {
+		self.overrides.get(&(self.schema_at)(id)).map(|o| &**o)
+	}
+
+	/// Ethereum block at `id`.
+	pub fn current_block<C>(&self, client: &C, id: &BlockId<B>) -> Result<Option<ethereum::Block>, String> where
+		C: ProvideRuntimeApi<B>,
+		C::Api: EthereumRuntimeRPCApi<B>,
+	{
+		match self.for_block(id) {
+			Some(storage) => Ok(storage.current_block(id)),
+			None => client.runtime_api().current_block(id).map_err(|err| format!("{:?}", err)),
+		}
+	}
+
+	/// Statuses of the Ethereum transactions at `id`.
+	pub fn current_transaction_statuses<C>(
+		&self,
+		client: &C,
+		id: &BlockId<B>,
+	) -> Result<Option<Vec<TransactionStatus>>, String> where
+		C: ProvideRuntimeApi<B>,
+		C::Api: EthereumRuntimeRPCApi<B>,
+	{
+		match self.for_block(id) {
+			Some(storage) => Ok(storage.current_transaction_statuses(id)),
+			None => client.runtime_api().current_transaction_statuses(id).map_err(|err| format!("{:?}", err)),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_core::{H160, U256};
+	use sp_runtime::testing::{Block as TestBlock, ExtrinsicWrapper};
+
+	type Block = TestBlock<ExtrinsicWrapper<()>>;
+
+	/// Block at which the schema is upgraded.
+	const UPGRADE: u64 = 10;
+
+	#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+	enum Schema {
+		Undefined,
+		V1,
+		V2,
+	}
+
+	/// Override which tags the statuses it serves with its schema.
+	struct Override(Schema);
+
+	impl Override {
+		fn tag(&self) -> H256 {
+			H256::repeat_byte(self.0 as u8)
+		}
+	}
+
+	impl StorageOverride<Block> for Override {
+		fn account_code_at(&self, _: &BlockId<Block>, _: H160) -> Option<Vec<u8>> {
+			None
+		}
+
+		fn storage_at(&self, _: &BlockId<Block>, _: H160, _: U256) -> Option<H256> {
+			None
+		}
+
+		fn current_block(&self, _: &BlockId<Block>) -> Option<ethereum::Block> {
+			None
+		}
+
+		fn current_receipts(&self, _: &BlockId<Block>) -> Option<Vec<ethereum::Receipt>> {
+			None
+		}
+
+		fn current_transaction_statuses(&self, _: &BlockId<Block>) -> Option<Vec<TransactionStatus>> {
+			Some(vec![TransactionStatus { transaction_hash: self.tag(), ..Default::default() }])
+		}
+	}
+
+	/// Blocks are written with `Undefined` up to 5, with `V1` up to the upgrade and
+	/// with `V2` from it.
+	fn handle() -> OverrideHandle<Block, Schema> {
+		let overrides = vec![Schema::V1, Schema::V2].into_iter()
+			.map(|schema| (schema, Box::new(Override(schema)) as Box<dyn StorageOverride<Block> + Send + Sync>))
+			.collect();
+		OverrideHandle {
+			overrides,
+			schema_at: Box::new(|id| match id {
+				BlockId::Number(number) if *number < 5 => Schema::Undefined,
+				BlockId::Number(number) if *number < UPGRADE => Schema::V1,
+				_ => Schema::V2,
+			}),
+		}
+	}
+
+	fn served_by(handle: &OverrideHandle<Block, Schema>, number: u64) -> Option<H256> {
+		let id = BlockId::Number(number);
+		handle.for_block(&id)
+			.and_then(|storage| storage.current_transaction_statuses(&id))
+			.map(|statuses| statuses[0].transaction_hash)
+	}
+
+	#[test]
+	fn serves_blocks_with_the_override_of_their_schema() {
+		let handle = handle();
+
+		assert_eq!(served_by(&handle, UPGRADE - 1), Some(Override(Schema::V1).tag()));
+		assert_eq!(served_by(&handle, UPGRADE), Some(Override(Schema::V2).tag()));
+		assert_eq!(served_by(&handle, UPGRADE + 1), Some(Override(Schema::V2).tag()));
+	}
+
+	#[test]
+	fn leaves_schemas_without_override_to_the_runtime_api() {
+		let handle = handle();
+
+		assert!(handle.for_block(&BlockId::Number(0)).is_none());
+		assert!(handle.for_block(&BlockId::Number(5)).is_some());
+	}
+}
@@ -0,0 +1,258 @@
+// This file is part of Mathchain.
+
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! `recovery_*` RPC, exposing the recovery status of accounts.
+//!
+//! `recovery_subscribeInitiated` notifies the recoveries initiated against a set of
+//! watched accounts, by comparing their active recoveries on every new best block.
+
+use std::{sync::Arc, marker::PhantomData};
+use codec::Codec;
+use futures::{StreamExt, FutureExt, SinkExt, future::ready};
+use jsonrpc_core::Result;
+use jsonrpc_derive::rpc;
+use jsonrpc_pubsub::{typed::Subscriber, SubscriptionId, manager::SubscriptionManager};
+use log::warn;
+use serde::{Serialize, de::DeserializeOwned};
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_runtime::{generic::BlockId, traits::Block as BlockT};
+use sc_client_api::BlockchainEvents;
+use mathchain_rpc_primitives::recovery::{self as primitives, RecoveryRuntimeApi};
+
+use crate::internal_err;
+
+/// Recovery configuration of an account.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecoveryConfig<AccountId, Balance, BlockNumber> {
+	pub friends: Vec<AccountId>,
+	pub threshold: u16,
+	pub delay_period: BlockNumber,
+	pub deposit: Balance,
+}
+
+/// Recovery of an account initiated by a rescuer.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActiveRecovery<AccountId, Balance, BlockNumber> {
+	pub rescuer: AccountId,
+	pub created: BlockNumber,
+	pub delay_elapses_at: BlockNumber,
+	pub vouched: Vec<AccountId>,
+	pub deposit: Balance,
+}
+
+/// Recovery status of an account.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecoveryStatus<AccountId, Balance, BlockNumber> {
+	pub config: Option<RecoveryConfig<AccountId, Balance, BlockNumber>>,
+	pub active_recoveries: Vec<ActiveRecovery<AccountId, Balance, BlockNumber>>,
+	pub recovered: Option<AccountId>,
+}
+
+/// Recovery initiated against a watched account.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecoveryInitiated<AccountId, BlockNumber> {
+	pub account: AccountId,
+	pub rescuer: AccountId,
+	pub created: BlockNumber,
+	pub delay_elapses_at: BlockNumber,
+}
+
+impl<A, B, N> From<primitives::RecoveryStatus<A, B, N>> for RecoveryStatus<A, B, N> {
+	fn from(status: primitives::RecoveryStatus<A, B, N>) -> Self {
+		RecoveryStatus {
+			config: status.config.map(|config| RecoveryConfig {
+				friends: config.friends,
+				threshold: config.threshold,
+				delay_period: config.delay_period,
+				deposit: config.deposit,
+			}),
+			active_recoveries: status.active_recoveries.into_iter()
+				.map(|recovery| ActiveRecovery {
+					rescuer: recovery.rescuer,
+					created: recovery.created,
+					delay_elapses_at: recovery.delay_elapses_at,
+					vouched: recovery.vouched,
+					deposit: recovery.deposit,
+				})
+				.collect(),
+			recovered: status.recovered,
+		}
+	}
+}
+
+#[rpc(server)]
+pub trait RecoveryApi<BlockHash, AccountId, Balance, BlockNumber> {
+	type Metadata;
+
+	/// Returns the recovery configuration of an account, and the recoveries initiated against it.
+	#[rpc(name = "recovery_status")]
+	fn status(
+		&self,
+		account: AccountId,
+		at: Option<BlockHash>,
+	) -> Result<RecoveryStatus<AccountId, Balance, BlockNumber>>;
+
+	/// Notifies the recoveries initiated against the given accounts.
+	#[pubsub(subscription = "recovery_initiated", subscribe, name = "recovery_subscribeInitiated")]
+	fn subscribe_initiated(
+		&self,
+		metadata: Self::Metadata,
+		subscriber: Subscriber<RecoveryInitiated<AccountId, BlockNumber>>,
+		accounts: Vec<AccountId>,
+	);
+
+	/// Cancels a `recovery_subscribeInitiated` subscription.
+	#[pubsub(subscription = "recovery_initiated", unsubscribe, name = "recovery_unsubscribeInitiated")]
+	fn unsubscribe_initiated(
+		&self,
+		metadata: Option<Self::Metadata>,
+		id: SubscriptionId,
+	) -> Result<bool>;
+}
+
+pub struct Recovery<B, C> {
+	client: Arc<C>,
+	subscriptions: SubscriptionManager,
+	_marker: PhantomData<B>,
+}
+
+impl<B, C> Recovery<B, C> {
+	pub fn new(client: Arc<C>, subscriptions: SubscriptionManager) -> Self {
+		Self { client, subscriptions, _marker: PhantomData }
+	}
+}
+
+fn runtime_err<E: std::fmt::Debug>(err: E) -> jsonrpc_core::Error {
+	internal_err(format!("call runtime failed: {:?}", err))
+}
+
+/// Active recoveries of the watched accounts, as `(account, recovery)`.
+fn active_recoveries<B, C, AccountId, Balance, BlockNumber>(
+	client: &C,
+	id: &BlockId<B>,
+	accounts: &[AccountId],
+) -> Vec<(AccountId, primitives::ActiveRecovery<AccountId, Balance, BlockNumber>)> where
+	B: BlockT,
+	C: ProvideRuntimeApi<B>,
+	C::Api: RecoveryRuntimeApi<B, AccountId, Balance, BlockNumber>,
+	AccountId: Codec + Clone,
+	Balance: Codec,
+	BlockNumber: Codec,
+{
+	let api = client.runtime_api();
+	accounts.iter()
+		.filter_map(|account| match api.recovery_status(id, account.clone()) {
+			Ok(status) => Some(status.active_recoveries.into_iter().map(move |recovery| (account.clone(), recovery))),
+			Err(err) => {
+				warn!(target: "recovery", "Failed to read the recovery status at {:?}: {:?}", id, err);
+				None
+			},
+		})
+		.flatten()
+		.collect()
+}
+
+impl<B, C, AccountId, Balance, BlockNumber> RecoveryApi<B::Hash, AccountId, Balance, BlockNumber>
+	for Recovery<B, C>
+where
+	B: BlockT,
+	C: ProvideRuntimeApi<B> + HeaderBackend<B> + BlockchainEvents<B> + Send + Sync + 'static,
+	C::Api: RecoveryRuntimeApi<B, AccountId, Balance, BlockNumber>,
+	AccountId: Codec + Clone + PartialEq + Serialize + DeserializeOwned + Send + Sync + 'static,
+	Balance: Codec + Serialize + Send + Sync + 'static,
+	BlockNumber: Codec + Serialize + Send + Sync + 'static,
+{
+	type Metadata = sc_rpc::Metadata;
+
+	fn status(
+		&self,
+		account: AccountId,
+		at: Option<B::Hash>,
+	) -> Result<RecoveryStatus<AccountId, Balance, BlockNumber>> {
+		let id = BlockId::Hash(at.unwrap_or_else(|| self.client.info().best_hash));
+		self.client.runtime_api()
+			.recovery_status(&id, account)
+			.map(Into::into)
+			.map_err(runtime_err)
+	}
+
+	fn subscribe_initiated(
+		&self,
+		_metadata: Self::Metadata,
+		subscriber: Subscriber<RecoveryInitiated<AccountId, BlockNumber>>,
+		accounts: Vec<AccountId>,
+	) {
+		let client = self.client.clone();
+		// Recoveries already active when subscribing are not notified.
+		let mut known: Vec<(AccountId, AccountId)> = active_recoveries::<_, _, _, Balance, BlockNumber>(
+			&*client,
+			&BlockId::Hash(client.info().best_hash),
+			&accounts,
+		)
+			.into_iter()
+			.map(|(account, recovery)| (account, recovery.rescuer))
+			.collect();
+
+		let stream = client.import_notification_stream()
+			.filter(|notification| ready(notification.is_new_best))
+			.map(move |notification| {
+				let active = active_recoveries::<_, _, _, Balance, BlockNumber>(
+					&*client,
+					&BlockId::Hash(notification.hash),
+					&accounts,
+				);
+				let active_rescuers: Vec<(AccountId, AccountId)> = active.iter()
+					.map(|(account, recovery)| (account.clone(), recovery.rescuer.clone()))
+					.collect();
+				let initiated: Vec<_> = active.into_iter()
+					.filter(|(account, recovery)| {
+						!known.iter().any(|(a, rescuer)| a == account && rescuer == &recovery.rescuer)
+					})
+					.map(|(account, recovery)| RecoveryInitiated {
+						account,
+						rescuer: recovery.rescuer,
+						created: recovery.created,
+						delay_elapses_at: recovery.delay_elapses_at,
+					})
+					.collect();
+				// Closed recoveries are forgotten, so a new initiation is notified again.
+				known = active_rescuers;
+				futures::stream::iter(initiated)
+			})
+			.flatten()
+			.map(|initiated| Ok::<_, ()>(Ok::<_, jsonrpc_core::Error>(initiated)));
+
+		self.subscriptions.add(subscriber, |sink| {
+			stream
+				.forward(sink.sink_map_err(|e| warn!(target: "recovery", "Error sending notifications: {:?}", e)))
+				.map(|_| ())
+		});
+	}
+
+	fn unsubscribe_initiated(
+		&self,
+		_metadata: Option<Self::Metadata>,
+		id: SubscriptionId,
+	) -> Result<bool> {
+		Ok(self.subscriptions.cancel(id))
+	}
+}
@@ -0,0 +1,208 @@
+// This file is part of Mathchain.
+
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! `secretstore_*` RPC, exposing the Secret Store runtime APIs.
+
+use std::{sync::Arc, marker::PhantomData};
+use jsonrpc_core::Result;
+use jsonrpc_derive::rpc;
+use serde::Serialize;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_core::H512;
+use sp_runtime::{generic::BlockId, traits::Block as BlockT};
+use secretstore_runtime_primitives::{
+	EntityId, KeyServerId, ServerKeyId,
+	acl_storage::SecretStoreAclApi,
+	key_server_set::SecretStoreKeyServerSetApi,
+	service::{SecretStoreServiceApi, ServiceTask},
+};
+
+use crate::internal_err;
+
+/// Maximum number of tasks returned by a single page.
+const MAX_TASKS_PER_PAGE: u32 = 1024;
+
+/// A key server of the current set.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyServer {
+	pub id: KeyServerId,
+	pub index: u8,
+}
+
+/// A pending Secret Store task.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Task {
+	#[serde(rename_all = "camelCase")]
+	GenerateServerKey {
+		key_id: ServerKeyId,
+		author: EntityId,
+		threshold: u8,
+	},
+	#[serde(rename_all = "camelCase")]
+	RetrieveServerKey {
+		key_id: ServerKeyId,
+	},
+	#[serde(rename_all = "camelCase")]
+	StoreDocumentKey {
+		key_id: ServerKeyId,
+		author: EntityId,
+		common_point: H512,
+		encrypted_point: H512,
+	},
+	#[serde(rename_all = "camelCase")]
+	RetrieveShadowDocumentKeyCommon {
+		key_id: ServerKeyId,
+		requester: EntityId,
+	},
+	#[serde(rename_all = "camelCase")]
+	RetrieveShadowDocumentKeyPersonal {
+		key_id: ServerKeyId,
+		requester_public: H512,
+	},
+}
+
+impl From<ServiceTask> for Task {
+	fn from(task: ServiceTask) -> Self {
+		match task {
+			ServiceTask::GenerateServerKey(key_id, author, threshold) =>
+				Task::GenerateServerKey { key_id, author, threshold },
+			ServiceTask::RetrieveServerKey(key_id) =>
+				Task::RetrieveServerKey { key_id },
+			ServiceTask::StoreDocumentKey(key_id, author, common_point, encrypted_point) =>
+				Task::StoreDocumentKey { key_id, author, common_point, encrypted_point },
+			ServiceTask::RetrieveShadowDocumentKeyCommon(key_id, requester) =>
+				Task::RetrieveShadowDocumentKeyCommon { key_id, requester },
+			ServiceTask::RetrieveShadowDocumentKeyPersonal(key_id, requester_public) =>
+				Task::RetrieveShadowDocumentKeyPersonal { key_id, requester_public },
+		}
+	}
+}
+
+#[rpc(server)]
+pub trait SecretStoreApi<BlockHash> {
+	/// Returns true if `requester` may access the key `key_id`.
+	#[rpc(name = "secretstore_checkAccess")]
+	fn check_access(&self, requester: EntityId, key_id: ServerKeyId, at: Option<BlockHash>) -> Result<bool>;
+
+	/// Returns the current key server set, with the index of each server.
+	#[rpc(name = "secretstore_keyServers")]
+	fn key_servers(&self, at: Option<BlockHash>) -> Result<Vec<KeyServer>>;
+
+	/// Returns the pending server key generation tasks in `begin..end`.
+	#[rpc(name = "secretstore_serverKeyGenerationTasks")]
+	fn server_key_generation_tasks(&self, begin: u32, end: u32, at: Option<BlockHash>) -> Result<Vec<Task>>;
+
+	/// Returns the pending server key retrieval tasks in `begin..end`.
+	#[rpc(name = "secretstore_serverKeyRetrievalTasks")]
+	fn server_key_retrieval_tasks(&self, begin: u32, end: u32, at: Option<BlockHash>) -> Result<Vec<Task>>;
+
+	/// Returns the pending document key store tasks in `begin..end`.
+	#[rpc(name = "secretstore_documentKeyStoreTasks")]
+	fn document_key_store_tasks(&self, begin: u32, end: u32, at: Option<BlockHash>) -> Result<Vec<Task>>;
+
+	/// Returns the pending document key shadow retrieval tasks in `begin..end`.
+	#[rpc(name = "secretstore_documentKeyShadowRetrievalTasks")]
+	fn document_key_shadow_retrieval_tasks(&self, begin: u32, end: u32, at: Option<BlockHash>) -> Result<Vec<Task>>;
+}
+
+pub struct SecretStore<B, C> {
+	client: Arc<C>,
+	_marker: PhantomData<B>,
+}
+
+impl<B, C> SecretStore<B, C> {
+	pub fn new(client: Arc<C>) -> Self {
+		Self { client, _marker: PhantomData }
+	}
+}
+
+impl<B, C> SecretStore<B, C> where
+	B: BlockT,
+	C: HeaderBackend<B>,
+{
+	fn block_id(&self, at: Option<B::Hash>) -> BlockId<B> {
+		BlockId::Hash(at.unwrap_or_else(|| self.client.info().best_hash))
+	}
+}
+
+fn check_page(begin: u32, end: u32) -> Result<()> {
+	if end < begin {
+		return Err(internal_err("end is lower than begin"));
+	}
+	if end - begin > MAX_TASKS_PER_PAGE {
+		return Err(internal_err(format!("page is too large (maximum {} tasks)", MAX_TASKS_PER_PAGE)));
+	}
+	Ok(())
+}
+
+fn runtime_err<E: std::fmt::Debug>(err: E) -> jsonrpc_core::Error {
+	internal_err(format!("call runtime failed: {:?}", err))
+}
+
+impl<B, C> SecretStoreApi<B::Hash> for SecretStore<B, C> where
+	B: BlockT,
+	C: ProvideRuntimeApi<B> + HeaderBackend<B> + Send + Sync + 'static,
+	C::Api: SecretStoreAclApi<B> + SecretStoreKeyServerSetApi<B> + SecretStoreServiceApi<B>,
+{
+	fn check_access(&self, requester: EntityId, key_id: ServerKeyId, at: Option<B::Hash>) -> Result<bool> {
+		self.client.runtime_api()
+			.check(&self.block_id(at), requester, key_id)
+			.map_err(runtime_err)
+	}
+
+	fn key_servers(&self, at: Option<B::Hash>) -> Result<Vec<KeyServer>> {
+		let key_servers = self.client.runtime_api()
+			.current_set_with_indices(&self.block_id(at))
+			.map_err(runtime_err)?;
+		Ok(key_servers.into_iter().map(|(id, index)| KeyServer { id, index }).collect())
+	}
+
+	fn server_key_generation_tasks(&self, begin: u32, end: u32, at: Option<B::Hash>) -> Result<Vec<Task>> {
+		check_page(begin, end)?;
+		let tasks = self.client.runtime_api()
+			.server_key_generation_tasks(&self.block_id(at), begin, end)
+			.map_err(runtime_err)?;
+		Ok(tasks.into_iter().map(Into::into).collect())
+	}
+
+	fn server_key_retrieval_tasks(&self, begin: u32, end: u32, at: Option<B::Hash>) -> Result<Vec<Task>> {
+		check_page(begin, end)?;
+		let tasks = self.client.runtime_api()
+			.server_key_retrieval_tasks(&self.block_id(at), begin, end)
+			.map_err(runtime_err)?;
+		Ok(tasks.into_iter().map(Into::into).collect())
+	}
+
+	fn document_key_store_tasks(&self, begin: u32, end: u32, at: Option<B::Hash>) -> Result<Vec<Task>> {
+		check_page(begin, end)?;
+		let tasks = self.client.runtime_api()
+			.document_key_store_tasks(&self.block_id(at), begin, end)
+			.map_err(runtime_err)?;
+		Ok(tasks.into_iter().map(Into::into).collect())
+	}
+
+	fn document_key_shadow_retrieval_tasks(&self, begin: u32, end: u32, at: Option<B::Hash>) -> Result<Vec<Task>> {
+		check_page(begin, end)?;
+		let tasks = self.client.runtime_api()
+			.document_key_shadow_retrieval_tasks(&self.block_id(at), begin, end)
+			.map_err(runtime_err)?;
+		Ok(tasks.into_iter().map(Into::into).collect())
+	}
+}
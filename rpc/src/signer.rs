@@ -0,0 +1,159 @@
+// This file is part of Mathchain.
+
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Ethereum signer backed by the node keystore.
+//!
+//! secp256k1 keys inserted with `author_insertKey` under `KEY_TYPE` become
+//! Ethereum accounts of the node: they are listed by `eth_accounts`, used by
+//! `eth_sendTransaction` and can sign messages through `eth_sign` and
+//! `personal_sign`. None of it is available where unsafe RPC calls are denied.
+
+use std::{sync::Arc, convert::TryFrom};
+use jsonrpc_core::Result;
+use jsonrpc_derive::rpc;
+use sha3::{Digest, Keccak256};
+use sp_core::{H160, H256, ecdsa, crypto::KeyTypeId};
+use sp_keystore::SyncCryptoStore;
+use sc_keystore::LocalKeystore;
+use sc_rpc_api::DenyUnsafe;
+use fc_rpc::EthSigner;
+use fc_rpc_core::types::Bytes;
+
+use crate::internal_err;
+
+/// Key type of the Ethereum accounts in the keystore.
+pub const KEY_TYPE: KeyTypeId = KeyTypeId(*b"eths");
+
+mod app {
+	use sp_application_crypto::{app_crypto, ecdsa};
+	app_crypto!(ecdsa, super::KEY_TYPE);
+}
+
+/// Ethereum address of a compressed secp256k1 public key.
+fn public_key_address(public: &ecdsa::Public) -> Option<H160> {
+	let compressed = <[u8; 33]>::try_from(public.as_ref()).ok()?;
+	let public = secp256k1::PublicKey::parse_compressed(&compressed).ok()?;
+	Some(H160::from(H256::from_slice(Keccak256::digest(&public.serialize()[1..]).as_slice())))
+}
+
+#[derive(Clone)]
+pub struct EthKeystoreSigner {
+	keystore: Arc<LocalKeystore>,
+}
+
+impl EthKeystoreSigner {
+	pub fn new(keystore: Arc<LocalKeystore>) -> Self {
+		Self { keystore }
+	}
+
+	fn keys(&self) -> Vec<(H160, ecdsa::Public)> {
+		SyncCryptoStore::ecdsa_public_keys(&*self.keystore, KEY_TYPE)
+			.into_iter()
+			.filter_map(|public| public_key_address(&public).map(|address| (address, public)))
+			.collect()
+	}
+
+	/// Sign a 32 bytes hash with the key of `address`, returning `r`, `s` and the recovery id.
+	fn sign_prehashed(&self, address: &H160, hash: &[u8; 32]) -> Result<(H256, H256, u8)> {
+		let public = self.keys()
+			.into_iter()
+			.find(|(key_address, _)| key_address == address)
+			.map(|(_, public)| public)
+			.ok_or_else(|| internal_err("signer not available"))?;
+		let pair = self.keystore
+			.key_pair::<app::Pair>(&app::Public::from(public))
+			.map_err(|err| internal_err(format!("keystore error: {:?}", err)))?;
+
+		let signature = ecdsa::Pair::from(pair).sign_prehashed(hash);
+		let rsv: &[u8] = signature.as_ref();
+		Ok((H256::from_slice(&rsv[0..32]), H256::from_slice(&rsv[32..64]), rsv[64]))
+	}
+
+	/// Sign `data` as an Ethereum personal message.
+	fn sign_message(&self, address: &H160, data: &[u8]) -> Result<Bytes> {
+		let mut message = format!("\x19Ethereum Signed Message:\n{}", data.len()).into_bytes();
+		message.extend_from_slice(data);
+		let mut hash = [0u8; 32];
+		hash.copy_from_slice(&Keccak256::digest(&message));
+
+		let (r, s, recid) = self.sign_prehashed(address, &hash)?;
+		let mut signature = Vec::with_capacity(65);
+		signature.extend_from_slice(r.as_bytes());
+		signature.extend_from_slice(s.as_bytes());
+		signature.push(27 + recid);
+		Ok(Bytes(signature))
+	}
+}
+
+impl EthSigner for EthKeystoreSigner {
+	fn accounts(&self) -> Vec<H160> {
+		self.keys().into_iter().map(|(address, _)| address).collect()
+	}
+
+	fn sign(&self, message: ethereum::TransactionMessage, address: &H160) -> Result<ethereum::Transaction> {
+		let (r, s, recid) = self.sign_prehashed(address, &message.hash().to_fixed_bytes())?;
+		let v = match message.chain_id {
+			None => 27 + recid as u64,
+			Some(chain_id) => 2 * chain_id + 35 + recid as u64,
+		};
+
+		Ok(ethereum::Transaction {
+			nonce: message.nonce,
+			gas_price: message.gas_price,
+			gas_limit: message.gas_limit,
+			action: message.action,
+			value: message.value,
+			input: message.input.clone(),
+			signature: ethereum::TransactionSignature::new(v, r, s)
+				.ok_or_else(|| internal_err("signer generated invalid signature"))?,
+		})
+	}
+}
+
+#[rpc(server)]
+pub trait EthSignApi {
+	/// Signs `data` as an Ethereum personal message with the key of `address`.
+	#[rpc(name = "eth_sign")]
+	fn eth_sign(&self, address: H160, data: Bytes) -> Result<Bytes>;
+
+	/// Same as `eth_sign`, with the arguments swapped.
+	#[rpc(name = "personal_sign")]
+	fn personal_sign(&self, data: Bytes, address: H160) -> Result<Bytes>;
+}
+
+pub struct EthSign {
+	signer: EthKeystoreSigner,
+	deny_unsafe: DenyUnsafe,
+}
+
+impl EthSign {
+	pub fn new(signer: EthKeystoreSigner, deny_unsafe: DenyUnsafe) -> Self {
+		Self { signer, deny_unsafe }
+	}
+}
+
+impl EthSignApi for EthSign {
+	fn eth_sign(&self, address: H160, data: Bytes) -> Result<Bytes> {
+		self.deny_unsafe.check_if_safe()?;
+		self.signer.sign_message(&address, &data.0)
+	}
+
+	fn personal_sign(&self, data: Bytes, address: H160) -> Result<Bytes> {
+		self.deny_unsafe.check_if_safe()?;
+		self.signer.sign_message(&address, &data.0)
+	}
+}
@@ -0,0 +1,424 @@
+// This file is part of Mathchain.
+
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Parity compatible `trace_filter` and `trace_block` RPC.
+//!
+//! Call traces are recorded for every canonical block by `TraceTask::sync_task`
+//! into the MathChain columns of the Frontier DB, and served from there.
+
+use std::{sync::Arc, marker::PhantomData};
+use futures::StreamExt;
+use jsonrpc_core::Result;
+use jsonrpc_derive::rpc;
+use serde::{Serialize, Deserialize};
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_core::{H160, H256, U256};
+use sp_runtime::{generic::BlockId, traits::{Block as BlockT, Header as HeaderT, UniqueSaturatedInto}};
+use sc_client_api::{BlockBackend, BlockchainEvents};
+use fc_rpc_core::types::{BlockNumber, Bytes};
+use fp_rpc::EthereumRuntimeRPCApi;
+use mathchain_db::{BlockTraces, TransactionTraces};
+use mathchain_rpc_primitives::debug::{
+	DebugRuntimeApi, TraceType, TransactionTrace, Call, CallType, CallResult,
+};
+
+use crate::{internal_err, block_number, debug::replay_block, OverrideHandle};
+
+/// Blocks traced at most for each import notification while catching up.
+const MAX_BLOCKS_PER_SYNC: u64 = 256;
+
+/// Blocks checked at most for retracted traces after a re-org.
+const MAX_REORG_DEPTH: u64 = 256;
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TraceFilter {
+	pub from_block: Option<BlockNumber>,
+	pub to_block: Option<BlockNumber>,
+	pub from_address: Option<Vec<H160>>,
+	pub to_address: Option<Vec<H160>>,
+	/// Number of matching traces to skip.
+	pub after: Option<u32>,
+	/// Maximum number of traces to return.
+	pub count: Option<u32>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase", untagged)]
+pub enum Action {
+	#[serde(rename_all = "camelCase")]
+	Call {
+		call_type: String,
+		from: H160,
+		to: H160,
+		gas: U256,
+		input: Bytes,
+		value: U256,
+	},
+	#[serde(rename_all = "camelCase")]
+	Create {
+		from: H160,
+		gas: U256,
+		init: Bytes,
+		value: U256,
+	},
+	#[serde(rename_all = "camelCase")]
+	Suicide {
+		address: H160,
+		refund_address: H160,
+		balance: U256,
+	},
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum ActionResult {
+	#[serde(rename_all = "camelCase")]
+	Call {
+		gas_used: U256,
+		output: Bytes,
+	},
+	#[serde(rename_all = "camelCase")]
+	Create {
+		address: H160,
+		code: Bytes,
+		gas_used: U256,
+	},
+}
+
+/// A trace located in a block and a transaction.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LocalizedTrace {
+	pub action: Action,
+	pub block_hash: H256,
+	pub block_number: u64,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub result: Option<ActionResult>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub error: Option<String>,
+	pub subtraces: u32,
+	pub trace_address: Vec<u32>,
+	pub transaction_hash: H256,
+	pub transaction_position: u32,
+	#[serde(rename = "type")]
+	pub trace_type: String,
+}
+
+#[rpc(server)]
+pub trait TraceApi {
+	/// Returns the traces matching the given filter.
+	#[rpc(name = "trace_filter")]
+	fn filter(&self, filter: TraceFilter) -> Result<Vec<LocalizedTrace>>;
+
+	/// Returns the traces of all the transactions of a block.
+	#[rpc(name = "trace_block")]
+	fn block_traces(&self, number: BlockNumber) -> Result<Option<Vec<LocalizedTrace>>>;
+}
+
+pub struct Trace<B: BlockT, C> {
+	client: Arc<C>,
+	frontier_backend: Arc<fc_db::Backend<B>>,
+	backend: Arc<mathchain_db::Backend<B>>,
+	max_block_range: u64,
+	max_results: usize,
+}
+
+impl<B: BlockT, C> Trace<B, C> {
+	pub fn new(
+		client: Arc<C>,
+		frontier_backend: Arc<fc_db::Backend<B>>,
+		backend: Arc<mathchain_db::Backend<B>>,
+		max_block_range: u64,
+		max_results: usize,
+	) -> Self {
+		Self { client, frontier_backend, backend, max_block_range, max_results }
+	}
+}
+
+impl<B, C> Trace<B, C> where
+	B: BlockT<Hash=H256> + Send + Sync + 'static,
+	C: HeaderBackend<B> + Send + Sync + 'static,
+{
+	fn load_block_traces(&self, number: u64) -> Result<Option<BlockTraces<B::Hash>>> {
+		self.backend.traces()
+			.block_traces(number)
+			.map_err(|err| internal_err(format!("fetch traces failed: {}", err)))
+	}
+}
+
+impl<B, C> TraceApi for Trace<B, C> where
+	B: BlockT<Hash=H256> + Send + Sync + 'static,
+	C: HeaderBackend<B> + Send + Sync + 'static,
+{
+	fn filter(&self, filter: TraceFilter) -> Result<Vec<LocalizedTrace>> {
+		let from = block_number(&*self.client, &self.frontier_backend, filter.from_block)?;
+		let to = block_number(&*self.client, &self.frontier_backend, filter.to_block)?;
+		if to < from {
+			return Err(internal_err("toBlock is lower than fromBlock"));
+		}
+		if to - from >= self.max_block_range {
+			return Err(internal_err(format!(
+				"block range is too wide (maximum {})", self.max_block_range
+			)));
+		}
+
+		let from_address = filter.from_address.unwrap_or_default();
+		let to_address = filter.to_address.unwrap_or_default();
+		let mut skip = filter.after.unwrap_or(0) as usize;
+		let count = filter.count.map_or(self.max_results, |count| count as usize);
+
+		let mut traces = Vec::new();
+		for number in from..=to {
+			let block_traces = match self.load_block_traces(number)? {
+				Some(block_traces) => block_traces,
+				None => continue,
+			};
+
+			for trace in localize(number, block_traces) {
+				let (trace_from, trace_to) = match &trace.action {
+					Action::Call { from, to, .. } => (*from, Some(*to)),
+					Action::Create { from, .. } => (*from, match &trace.result {
+						Some(ActionResult::Create { address, .. }) => Some(*address),
+						_ => None,
+					}),
+					Action::Suicide { address, refund_address, .. } => (*address, Some(*refund_address)),
+				};
+				if !from_address.is_empty() && !from_address.contains(&trace_from) {
+					continue;
+				}
+				if !to_address.is_empty() && !trace_to.map_or(false, |to| to_address.contains(&to)) {
+					continue;
+				}
+				if skip > 0 {
+					skip -= 1;
+					continue;
+				}
+				if traces.len() >= count.min(self.max_results) {
+					return Ok(traces);
+				}
+				traces.push(trace);
+			}
+		}
+
+		Ok(traces)
+	}
+
+	fn block_traces(&self, number: BlockNumber) -> Result<Option<Vec<LocalizedTrace>>> {
+		let number = block_number(&*self.client, &self.frontier_backend, Some(number))?;
+		Ok(self.load_block_traces(number)?.map(|block_traces| localize(number, block_traces)))
+	}
+}
+
+/// Turn the recorded calls of a block into Parity traces.
+fn localize<Hash>(number: u64, block_traces: BlockTraces<Hash>) -> Vec<LocalizedTrace> {
+	let block_hash = block_traces.ethereum_block_hash;
+	let mut traces = Vec::new();
+
+	for TransactionTraces { transaction_hash, transaction_position, calls } in block_traces.transactions {
+		for call in calls.iter() {
+			let subtraces = calls.iter()
+				.filter(|other| other.trace_address.len() == call.trace_address.len() + 1
+					&& other.trace_address.starts_with(&call.trace_address))
+				.count() as u32;
+			traces.push(localize_call(call.clone(), subtraces, block_hash, number, transaction_hash, transaction_position));
+		}
+	}
+
+	traces
+}
+
+fn localize_call(
+	call: Call,
+	subtraces: u32,
+	block_hash: H256,
+	block_number: u64,
+	transaction_hash: H256,
+	transaction_position: u32,
+) -> LocalizedTrace {
+	let (result, error) = match call.result {
+		CallResult::Output(output) => (Some(output), None),
+		CallResult::Error(error) => (None, Some(String::from_utf8_lossy(&error).into_owned())),
+	};
+
+	let (trace_type, action, result) = match call.call_type {
+		CallType::Create => (
+			"create",
+			Action::Create {
+				from: call.from,
+				gas: call.gas,
+				init: Bytes(call.input),
+				value: call.value,
+			},
+			result.map(|code| ActionResult::Create {
+				address: call.to,
+				code: Bytes(code),
+				gas_used: call.gas_used,
+			}),
+		),
+		CallType::SelfDestruct => (
+			"suicide",
+			Action::Suicide {
+				address: call.from,
+				refund_address: call.to,
+				balance: call.value,
+			},
+			None,
+		),
+		call_type => (
+			"call",
+			Action::Call {
+				call_type: match call_type {
+					CallType::CallCode => "callcode",
+					CallType::DelegateCall => "delegatecall",
+					CallType::StaticCall => "staticcall",
+					_ => "call",
+				}.into(),
+				from: call.from,
+				to: call.to,
+				gas: call.gas,
+				input: Bytes(call.input),
+				value: call.value,
+			},
+			result.map(|output| ActionResult::Call {
+				gas_used: call.gas_used,
+				output: Bytes(output),
+			}),
+		),
+	};
+
+	LocalizedTrace {
+		action,
+		block_hash,
+		block_number,
+		result,
+		error,
+		subtraces,
+		trace_address: call.trace_address,
+		transaction_hash,
+		transaction_position,
+		trace_type: trace_type.into(),
+	}
+}
+
+pub struct TraceTask<B, C>(PhantomData<(B, C)>);
+
+impl<B, C> TraceTask<B, C> where
+	B: BlockT<Hash=H256> + Send + Sync + 'static,
+	C: ProvideRuntimeApi<B> + HeaderBackend<B> + BlockBackend<B> + BlockchainEvents<B>,
+	C: Send + Sync + 'static,
+	C::Api: DebugRuntimeApi<B> + EthereumRuntimeRPCApi<B>,
+{
+	/// Record the call traces of every new canonical block into the MathChain columns.
+	///
+	/// Tracing re-executes blocks, so this should run on a blocking task.
+	pub async fn sync_task(
+		client: Arc<C>,
+		backend: Arc<mathchain_db::Backend<B>>,
+		overrides: Arc<OverrideHandle<B>>,
+	) {
+		let mut notifications = client.import_notification_stream();
+
+		while let Some(notification) = notifications.next().await {
+			if !notification.is_new_best {
+				continue;
+			}
+
+			let best = UniqueSaturatedInto::<u64>::unique_saturated_into(*notification.header.number());
+			if let Err(err) = Self::sync_to(&*client, &*backend, &overrides, best) {
+				log::warn!(
+					target: "trace-sync",
+					"Failed to record call traces up to block #{}: {}", best, err,
+				);
+			}
+		}
+	}
+
+	fn sync_to(
+		client: &C,
+		backend: &mathchain_db::Backend<B>,
+		overrides: &OverrideHandle<B>,
+		best: u64,
+	) -> std::result::Result<(), String> {
+		let synced = backend.meta().traces_synced()?;
+		// The genesis block has no parent to be replayed on top of.
+		let mut from = synced.map_or(1, |synced| synced + 1).min(best).max(1);
+
+		// Blocks retracted by a re-org have to be traced again.
+		while from > 1 && best - from < MAX_REORG_DEPTH {
+			let previous = from - 1;
+			let canon_hash = client.hash(previous.unique_saturated_into())
+				.map_err(|err| format!("{:?}", err))?;
+			match backend.traces().block_traces(previous)? {
+				Some(traces) if Some(traces.block_hash) != canon_hash => from = previous,
+				_ => break,
+			}
+		}
+
+		for number in from..=best.min(from + MAX_BLOCKS_PER_SYNC) {
+			Self::trace_block(client, backend, overrides, number)?;
+		}
+
+		Ok(())
+	}
+
+	fn trace_block(
+		client: &C,
+		backend: &mathchain_db::Backend<B>,
+		overrides: &OverrideHandle<B>,
+		number: u64,
+	) -> std::result::Result<(), String> {
+		let hash = client.hash(number.unique_saturated_into())
+			.map_err(|err| format!("{:?}", err))?
+			.ok_or_else(|| format!("block #{} not found", number))?;
+		let id = BlockId::Hash(hash);
+
+		let ethereum_block_hash = overrides.current_block(client, &id)?
+			.map(|block| block.header.hash())
+			.unwrap_or_default();
+		let statuses = overrides.current_transaction_statuses(client, &id)?.unwrap_or_default();
+
+		let traces = if statuses.is_empty() {
+			Vec::new()
+		} else {
+			replay_block(client, hash, |api, parent_id, extrinsics| {
+				api.trace_block(parent_id, extrinsics, TraceType::CallList)
+					.map_err(|err| internal_err(format!("call runtime failed: {:?}", err)))?
+					.map_err(|err| internal_err(format!("tracing failed: {:?}", err)))
+			}).map_err(|err| err.message)?
+		};
+
+		let transactions = statuses.into_iter()
+			.zip(traces.into_iter())
+			.map(|(status, trace)| TransactionTraces {
+				transaction_hash: status.transaction_hash,
+				transaction_position: status.transaction_index,
+				calls: match trace {
+					TransactionTrace::CallList(calls) => calls,
+					TransactionTrace::Raw { .. } => Vec::new(),
+				},
+			})
+			.collect();
+
+		backend.traces().write_block_traces(number, BlockTraces {
+			block_hash: hash,
+			ethereum_block_hash,
+			transactions,
+		})
+	}
+}
@@ -0,0 +1,206 @@
+// This file is part of Mathchain.
+
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Geth compatible `txpool_*` RPC.
+//!
+//! Pool extrinsics are opaque to the node, so the Ethereum transactions are
+//! picked out of them by the runtime and grouped here by sender and nonce.
+//! Ready transactions are reported as pending, future ones as queued.
+
+use std::{sync::Arc, collections::BTreeMap, marker::PhantomData};
+use ethereum::{Transaction as EthereumTransaction, TransactionAction, TransactionMessage};
+use jsonrpc_core::Result;
+use jsonrpc_derive::rpc;
+use serde::Serialize;
+use sha3::{Digest, Keccak256};
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_core::{H160, H256, U256};
+use sp_runtime::{generic::BlockId, traits::Block as BlockT};
+use sp_transaction_pool::{TransactionPool, InPoolTransaction};
+use fc_rpc_core::types::Bytes;
+use mathchain_rpc_primitives::txpool::{TxPoolResponse, TxPoolRuntimeApi};
+
+use crate::internal_err;
+
+/// Transactions grouped by sender, then by decimal nonce.
+pub type TransactionMap<T> = BTreeMap<H160, BTreeMap<String, T>>;
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct TxPoolResult<T: Serialize> {
+	pub pending: T,
+	pub queued: T,
+}
+
+/// A pool transaction, as returned by `txpool_content`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PoolTransaction {
+	/// Always `null`, the transaction is not in a block yet.
+	pub block_hash: Option<H256>,
+	/// Always `null`, the transaction is not in a block yet.
+	pub block_number: Option<U256>,
+	pub from: H160,
+	pub gas: U256,
+	pub gas_price: U256,
+	pub hash: H256,
+	pub input: Bytes,
+	pub nonce: U256,
+	pub to: Option<H160>,
+	/// Always `null`, the transaction is not in a block yet.
+	pub transaction_index: Option<U256>,
+	pub value: U256,
+	pub v: U256,
+	pub r: U256,
+	pub s: U256,
+}
+
+#[rpc(server)]
+pub trait TxPoolApi {
+	/// Returns the full content of the pending and queued transactions.
+	#[rpc(name = "txpool_content")]
+	fn content(&self) -> Result<TxPoolResult<TransactionMap<PoolTransaction>>>;
+
+	/// Returns a one line summary of the pending and queued transactions.
+	#[rpc(name = "txpool_inspect")]
+	fn inspect(&self) -> Result<TxPoolResult<TransactionMap<String>>>;
+
+	/// Returns the number of pending and queued transactions.
+	#[rpc(name = "txpool_status")]
+	fn status(&self) -> Result<TxPoolResult<U256>>;
+}
+
+pub struct TxPool<B: BlockT, C, P> {
+	client: Arc<C>,
+	pool: Arc<P>,
+	_marker: PhantomData<B>,
+}
+
+impl<B: BlockT, C, P> TxPool<B, C, P> {
+	pub fn new(client: Arc<C>, pool: Arc<P>) -> Self {
+		Self { client, pool, _marker: PhantomData }
+	}
+}
+
+impl<B, C, P> TxPool<B, C, P> where
+	B: BlockT + Send + Sync + 'static,
+	C: ProvideRuntimeApi<B> + HeaderBackend<B> + Send + Sync + 'static,
+	C::Api: TxPoolRuntimeApi<B>,
+	P: TransactionPool<Block=B> + Send + Sync + 'static,
+{
+	/// Ethereum transactions of the pool, as seen from the best block.
+	fn transactions(&self) -> Result<TxPoolResponse> {
+		let xts_ready: Vec<B::Extrinsic> = self.pool.ready()
+			.map(|in_pool_tx| in_pool_tx.data().clone())
+			.collect();
+		let xts_future: Vec<B::Extrinsic> = self.pool.futures()
+			.iter()
+			.map(|in_pool_tx| in_pool_tx.data().clone())
+			.collect();
+
+		let best_block = BlockId::Hash(self.client.info().best_hash);
+		self.client.runtime_api()
+			.extrinsic_filter(&best_block, xts_ready, xts_future)
+			.map_err(|err| internal_err(format!("fetch runtime extrinsic filter failed: {:?}", err)))
+	}
+
+	fn group<T, F>(&self, f: F) -> Result<TxPoolResult<TransactionMap<T>>> where
+		T: Serialize,
+		F: Fn(H160, &EthereumTransaction) -> T,
+	{
+		let group = |transactions: Vec<EthereumTransaction>| -> Result<TransactionMap<T>> {
+			let mut map = TransactionMap::new();
+			for transaction in transactions {
+				let from = recover_signer(&transaction)
+					.ok_or_else(|| internal_err("invalid transaction signature"))?;
+				map.entry(from)
+					.or_insert_with(BTreeMap::new)
+					.insert(transaction.nonce.to_string(), f(from, &transaction));
+			}
+			Ok(map)
+		};
+
+		let TxPoolResponse { ready, future } = self.transactions()?;
+		Ok(TxPoolResult {
+			pending: group(ready)?,
+			queued: group(future)?,
+		})
+	}
+}
+
+impl<B, C, P> TxPoolApi for TxPool<B, C, P> where
+	B: BlockT + Send + Sync + 'static,
+	C: ProvideRuntimeApi<B> + HeaderBackend<B> + Send + Sync + 'static,
+	C::Api: TxPoolRuntimeApi<B>,
+	P: TransactionPool<Block=B> + Send + Sync + 'static,
+{
+	fn content(&self) -> Result<TxPoolResult<TransactionMap<PoolTransaction>>> {
+		self.group(|from, transaction| PoolTransaction {
+			block_hash: None,
+			block_number: None,
+			from,
+			gas: transaction.gas_limit,
+			gas_price: transaction.gas_price,
+			hash: H256::from_slice(Keccak256::digest(&rlp::encode(transaction)).as_slice()),
+			input: Bytes(transaction.input.clone()),
+			nonce: transaction.nonce,
+			to: match transaction.action {
+				TransactionAction::Call(to) => Some(to),
+				TransactionAction::Create => None,
+			},
+			transaction_index: None,
+			value: transaction.value,
+			v: U256::from(transaction.signature.v()),
+			r: U256::from(transaction.signature.r().as_bytes()),
+			s: U256::from(transaction.signature.s().as_bytes()),
+		})
+	}
+
+	fn inspect(&self) -> Result<TxPoolResult<TransactionMap<String>>> {
+		self.group(|_, transaction| {
+			let to = match transaction.action {
+				TransactionAction::Call(to) => format!("{:?}", to),
+				TransactionAction::Create => "contract creation".into(),
+			};
+			format!(
+				"{}: {} wei + {} gas × {} wei",
+				to, transaction.value, transaction.gas_limit, transaction.gas_price,
+			)
+		})
+	}
+
+	fn status(&self) -> Result<TxPoolResult<U256>> {
+		let TxPoolResponse { ready, future } = self.transactions()?;
+		Ok(TxPoolResult {
+			pending: U256::from(ready.len()),
+			queued: U256::from(future.len()),
+		})
+	}
+}
+
+/// Sender of a signed Ethereum transaction.
+fn recover_signer(transaction: &EthereumTransaction) -> Option<H160> {
+	let mut sig = [0u8; 65];
+	let mut msg = [0u8; 32];
+	sig[0..32].copy_from_slice(&transaction.signature.r()[..]);
+	sig[32..64].copy_from_slice(&transaction.signature.s()[..]);
+	sig[64] = transaction.signature.standard_v();
+	msg.copy_from_slice(&TransactionMessage::from(transaction.clone()).hash()[..]);
+
+	let pubkey = sp_io::crypto::secp256k1_ecdsa_recover(&sig, &msg).ok()?;
+	Some(H160::from(H256::from_slice(Keccak256::digest(&pubkey).as_slice())))
+}
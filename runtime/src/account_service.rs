@@ -0,0 +1,265 @@
+//! Address resolution of the account service.
+//!
+//! `pallet_account_service` binds a nickname, an Ethereum address and addresses
+//! of other chains to an account. An Ethereum address which is not bound falls
+//! back to the account `evm:` ++ address, so every H160 has an account.
+//!
+//! Extrinsics name accounts by an [`Address`], which extends the variants of
+//! `sp_runtime::MultiAddress` with the nicknames and addresses of the account
+//! service.
+//!
+//! When an address is recovered through `pallet_evm_recovery`, its binding moves
+//! to the rescuer.
+//!
+//! The nickname is shown next to the `pallet_identity` display name. Registrars
+//! judge the display name, not the nickname, so they are only reported as
+//! vouching for the account when both are the same.
+
+use sp_std::prelude::*;
+use codec::{Encode, Decode};
+use sp_core::{H160, crypto::AccountId32};
+use sp_runtime::{DispatchResult, RuntimeDebug};
+use frame_support::{traits::Get, weights::Weight};
+use mathchain_rpc_primitives::account_service::{Identity, LinkedAddress};
+use pallet_account_service::AccountServiceEnum;
+
+use crate::{AccountService, Origin, Runtime};
+
+const FALLBACK_PREFIX: &[u8; 4] = b"evm:";
+
+/// An account, by any of its addresses.
+///
+/// The first variants encode as those of `sp_runtime::MultiAddress`, so that
+/// clients keep signing for the same addresses.
+#[derive(Clone, Eq, PartialEq, Encode, Decode, RuntimeDebug)]
+pub enum Address<AccountId, AccountIndex> {
+	/// It's an account ID (pubkey).
+	Id(AccountId),
+	/// It's an account index.
+	Index(#[codec(compact)] AccountIndex),
+	/// It's some arbitrary raw bytes. No account has one.
+	Raw(Vec<u8>),
+	/// It's a 32 byte representation.
+	Address32([u8; 32]),
+	/// It's a 20 byte representation, an Ethereum address.
+	Address20([u8; 20]),
+	/// A nickname or an address of another chain bound through the account service.
+	Linked(AccountServiceEnum),
+}
+
+#[cfg(feature = "std")]
+impl<AccountId, AccountIndex> std::fmt::Display for Address<AccountId, AccountIndex> where
+	AccountId: std::fmt::Debug,
+	AccountIndex: std::fmt::Debug,
+{
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		use sp_core::hexdisplay::HexDisplay;
+		match self {
+			Address::Raw(inner) => write!(f, "Address::Raw({})", HexDisplay::from(inner)),
+			Address::Address32(inner) => write!(f, "Address::Address32({})", HexDisplay::from(inner)),
+			Address::Address20(inner) => write!(f, "Address::Address20({})", HexDisplay::from(inner)),
+			ref other => write!(f, "{:?}", other),
+		}
+	}
+}
+
+impl<AccountId, AccountIndex> From<AccountId> for Address<AccountId, AccountIndex> {
+	fn from(account: AccountId) -> Self {
+		Address::Id(account)
+	}
+}
+
+impl<AccountId: Default, AccountIndex> Default for Address<AccountId, AccountIndex> {
+	fn default() -> Self {
+		Address::Id(Default::default())
+	}
+}
+
+/// Account an Ethereum address resolves to.
+pub fn ethereum_account(address: H160) -> AccountId32 {
+	AccountService::account_of(&AccountServiceEnum::Ethereum(address))
+		.unwrap_or_else(|| fallback_account(address))
+}
+
+/// Account of an Ethereum address which is not bound.
+pub fn fallback_account(address: H160) -> AccountId32 {
+	let mut data = [0u8; 32];
+	data[0..4].copy_from_slice(FALLBACK_PREFIX);
+	data[4..24].copy_from_slice(&address[..]);
+	AccountId32::new(data)
+}
+
+/// Ethereum address of a fallback account.
+pub fn fallback_address(account: &AccountId32) -> Option<H160> {
+	let data: &[u8; 32] = account.as_ref();
+	if &data[0..4] == FALLBACK_PREFIX && data[24..].iter().all(|b| *b == 0) {
+		Some(H160::from_slice(&data[4..24]))
+	} else {
+		None
+	}
+}
+
+/// Addresses bound to `account`, and the Ethereum address of a fallback account.
+pub fn linked_addresses(account: &AccountId32) -> Vec<LinkedAddress> {
+	let mut addresses: Vec<_> = AccountService::linked_addresses(account).into_iter()
+		.map(linked_address)
+		.collect();
+	if let Some(address) = fallback_address(account) {
+		addresses.push(LinkedAddress::Ethereum(address));
+	}
+	addresses
+}
+
+/// Account holding `nickname`.
+pub fn nickname_account(nickname: Vec<u8>) -> Option<AccountId32> {
+	AccountService::account_of(&AccountServiceEnum::Nickname(nickname))
+}
+
+/// Account `address` is bound to, an Ethereum address falling back to its `evm:` account.
+pub fn linked_account(address: AccountServiceEnum) -> Option<AccountId32> {
+	match address {
+		AccountServiceEnum::Ethereum(address) => Some(ethereum_account(address)),
+		address => AccountService::account_of(&address),
+	}
+}
+
+fn linked_address(address: AccountServiceEnum) -> LinkedAddress {
+	match address {
+		AccountServiceEnum::Nickname(nickname) => LinkedAddress::Nickname(nickname),
+		AccountServiceEnum::Ethereum(address) => LinkedAddress::Ethereum(address),
+		AccountServiceEnum::Bitcoin(hash) => LinkedAddress::Bitcoin(hash),
+		AccountServiceEnum::Tron(address) => LinkedAddress::Tron(address),
+		AccountServiceEnum::Solana(public) => LinkedAddress::Solana(public),
+		AccountServiceEnum::Polkadot(public) => LinkedAddress::Polkadot(public),
+	}
+}
+
+/// Account service address of a linked address.
+pub fn service_address(address: LinkedAddress) -> AccountServiceEnum {
+	match address {
+		LinkedAddress::Nickname(nickname) => AccountServiceEnum::Nickname(nickname),
+		LinkedAddress::Ethereum(address) => AccountServiceEnum::Ethereum(address),
+		LinkedAddress::Bitcoin(hash) => AccountServiceEnum::Bitcoin(hash),
+		LinkedAddress::Tron(address) => AccountServiceEnum::Tron(address),
+		LinkedAddress::Solana(public) => AccountServiceEnum::Solana(public),
+		LinkedAddress::Polkadot(public) => AccountServiceEnum::Polkadot(public),
+	}
+}
+
+/// Identity and nickname of `account`.
+pub fn identity(account: &AccountId32) -> Option<Identity> {
+	use pallet_identity::{Data, Judgement};
+
+	let registration = crate::Identity::identity(account);
+	let nickname = AccountService::linked_addresses(account).into_iter()
+		.find_map(|address| match address {
+			AccountServiceEnum::Nickname(nickname) => Some(nickname),
+			_ => None,
+		});
+	if registration.is_none() && nickname.is_none() {
+		return None;
+	}
+
+	let (display, judgements) = match registration {
+		Some(registration) => (
+			match registration.info.display {
+				Data::Raw(display) => Some(display),
+				_ => None,
+			},
+			registration.judgements,
+		),
+		None => (None, Vec::new()),
+	};
+	let verified_by = if display.is_some() && display == nickname {
+		judgements.into_iter()
+			.filter(|(_, judgement)| matches!(judgement, Judgement::Reasonable | Judgement::KnownGood))
+			.map(|(registrar, _)| registrar)
+			.collect()
+	} else {
+		Vec::new()
+	};
+	Some(Identity { display, nickname, verified_by })
+}
+
+/// Binds a recovered Ethereum address to its rescuer, in place of the account it was bound to.
+pub struct RelinkEthereum;
+
+impl pallet_evm_recovery::OnEthereumRecovered<AccountId32> for RelinkEthereum {
+	fn on_recovered(address: H160, rescuer: &AccountId32) -> DispatchResult {
+		let bound = match AccountService::account_of(&AccountServiceEnum::Ethereum(address)) {
+			Some(bound) if &bound != rescuer => bound,
+			_ => return Ok(()),
+		};
+		// Only the Ethereum address moves, the nickname and the other addresses stay
+		// with the account they were bound to.
+		AccountService::force_unbind(Origin::root(), bound, AccountServiceEnum::Ethereum(address))?;
+		AccountService::force_bind(Origin::root(), rescuer.clone(), AccountServiceEnum::Ethereum(address))
+	}
+
+	fn weight() -> Weight {
+		// `force_unbind` then `force_bind`.
+		80_000_000 + <Runtime as frame_system::Config>::DbWeight::get().reads_writes(6, 5)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use pallet_evm_recovery::OnEthereumRecovered;
+
+	fn new_test_ext() -> sp_io::TestExternalities {
+		frame_system::GenesisConfig::default().build_storage::<Runtime>().unwrap().into()
+	}
+
+	#[test]
+	fn accounts_are_named_by_their_index_or_ethereum_address() {
+		use sp_runtime::traits::StaticLookup;
+		type Lookup = crate::AccountIdLookup<AccountId32, crate::AccountIndex>;
+
+		new_test_ext().execute_with(|| {
+			let indexed = AccountId32::new([1; 32]);
+			let plain = AccountId32::new([2; 32]);
+			let address = H160::repeat_byte(3);
+			pallet_indices::Accounts::<Runtime>::insert(7, (indexed.clone(), 0, false));
+
+			assert_eq!(Lookup::unlookup(indexed.clone()), Address::Index(7));
+			assert_eq!(Lookup::unlookup(plain.clone()), Address::Id(plain.clone()));
+			assert_eq!(Lookup::unlookup(fallback_account(address)), Address::Address20(address.into()));
+			for account in vec![indexed, plain, fallback_account(address)] {
+				assert_eq!(Lookup::lookup(Lookup::unlookup(account.clone())), Ok(account));
+			}
+		});
+	}
+
+	#[test]
+	fn recovered_address_moves_without_the_other_bindings() {
+		new_test_ext().execute_with(|| {
+			let (bound, rescuer) = (AccountId32::new([1; 32]), AccountId32::new([2; 32]));
+			let address = H160::repeat_byte(1);
+			let nickname = AccountServiceEnum::Nickname(b"alice".to_vec());
+			let tron = AccountServiceEnum::Tron(H160::repeat_byte(2));
+			for linked in vec![nickname.clone(), AccountServiceEnum::Ethereum(address), tron.clone()] {
+				AccountService::force_bind(Origin::root(), bound.clone(), linked).unwrap();
+			}
+
+			RelinkEthereum::on_recovered(address, &rescuer).unwrap();
+
+			assert_eq!(ethereum_account(address), rescuer);
+			assert_eq!(AccountService::linked_addresses(&bound), vec![nickname, tron]);
+			assert_eq!(AccountService::linked_addresses(&rescuer), vec![AccountServiceEnum::Ethereum(address)]);
+		});
+	}
+
+	#[test]
+	fn unbound_address_is_left_unbound() {
+		new_test_ext().execute_with(|| {
+			let rescuer = AccountId32::new([2; 32]);
+			let address = H160::repeat_byte(1);
+			assert_eq!(ethereum_account(address), fallback_account(address));
+
+			RelinkEthereum::on_recovered(address, &rescuer).unwrap();
+
+			assert_eq!(ethereum_account(address), fallback_account(address));
+		});
+	}
+}
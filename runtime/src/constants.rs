@@ -4,4 +4,16 @@ pub mod currency {
 	pub const DOLLARS: Balance = MATHS / 100;               // 10_000_000_000_000_000
 	pub const CENTS: Balance = DOLLARS / 100;               // 100_000_000_000_000
 	pub const MILLICENTS: Balance = CENTS / 1_000;          // 100_000_000_000
-}
\ No newline at end of file
+
+	/// Deposit for `items` storage items taking `bytes` bytes.
+	pub const fn deposit(items: u32, bytes: u32) -> Balance {
+		items as Balance * 15 * CENTS + (bytes as Balance) * 6 * CENTS
+	}
+}
+
+pub mod staking {
+	use super::currency::MATHS;
+
+	/// Self bond of the validators, at genesis and when moved from `pallet_validator_set`.
+	pub const VALIDATOR_BOND: u128 = 1000 * MATHS;
+}
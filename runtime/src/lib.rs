@@ -8,10 +8,10 @@ include!(concat!(env!("OUT_DIR"), "/wasm_binary.rs"));
 
 use codec::{Codec, Encode, Decode};
 use sp_std::{self, prelude::*, marker::PhantomData, fmt::Debug};
-use sp_core::{crypto::KeyTypeId, OpaqueMetadata, U256, H160, H256};
+use sp_core::{crypto::KeyTypeId, sr25519, OpaqueMetadata, U256, H160, H256};
 use sp_runtime::{
 	ApplyExtrinsicResult, generic, create_runtime_str, impl_opaque_keys, MultiSignature,
-	transaction_validity::{TransactionValidity, TransactionSource},
+	transaction_validity::{TransactionValidity, TransactionSource, TransactionPriority},
 };
 use sp_runtime::traits::{
 	BlakeTwo256, Block as BlockT, Verify, IdentifyAccount, NumberFor, StaticLookup, LookupError,
@@ -21,18 +21,15 @@ use sp_api::impl_runtime_apis;
 use sp_consensus_aura::sr25519::AuthorityId as AuraId;
 use pallet_grandpa::{AuthorityId as GrandpaId, AuthorityList as GrandpaAuthorityList};
 use pallet_grandpa::fg_primitives;
+use pallet_im_online::sr25519::AuthorityId as ImOnlineId;
+use sp_authority_discovery::AuthorityId as AuthorityDiscoveryId;
 use sp_version::RuntimeVersion;
 #[cfg(feature = "std")]
 use sp_version::NativeVersion;
 use sp_core::crypto::Public;
 use sp_core::crypto::AccountId32;
-pub use pallet_validator_set;
 pub use secretstore_runtime_module::Call as SecretStoreCall;
 
-impl pallet_validator_set::Config for Runtime {
-	type Event = Event;
-}
-
 // A few exports that help ease life for downstream crates.
 #[cfg(any(feature = "std", test))]
 pub use sp_runtime::BuildStorage;
@@ -40,11 +37,17 @@ pub use pallet_timestamp::Call as TimestampCall;
 pub use pallet_balances::Call as BalancesCall;
 pub use sp_runtime::{Permill, Perbill};
 pub use pallet_recovery::Call as RecoveryCall;
+pub use pallet_staking::StakerStatus;
 use pallet_transaction_payment::CurrencyAdapter;
+use pallet_session::historical as pallet_session_historical;
+use sp_runtime::curve::PiecewiseLinear;
 
 pub use frame_support::{
-	construct_runtime, parameter_types, StorageValue,
-	traits::{KeyOwnerProofSystem, Randomness, FindAuthor},
+	construct_runtime, parameter_types, StorageValue, RuntimeDebug,
+	traits::{
+		KeyOwnerProofSystem, Randomness, FindAuthor, Filter, InstanceFilter, Currency, OnUnbalanced,
+		U128CurrencyToVote, Get,
+	},
 	weights::{
 		Weight, IdentityFee,
 		constants::{BlockExecutionWeight, ExtrinsicBaseWeight, RocksDbWeight, WEIGHT_PER_SECOND},
@@ -56,10 +59,17 @@ use pallet_evm::{
 	EnsureAddressTruncated, Runner,
 };
 use fp_rpc::{TransactionStatus};
+use fp_evm::{Context, ExitError, ExitSucceed, Precompile, PrecompileSet};
 
 pub mod constants;
 use constants::{currency::*};
 
+pub mod account_service;
+pub mod migrations;
+
+#[cfg(feature = "evm-tracing")]
+mod tracer;
+
 /// Import the template pallet.
 // pub use pallet_template;
 pub use pallet_account_service;
@@ -82,6 +92,12 @@ pub type AccountIndex = u32;
 /// Balance of an account.
 pub type Balance = u128;
 
+/// Identifier of an asset of `pallet_assets`.
+pub type AssetId = u32;
+
+/// Balance of an asset, in units of the asset.
+pub type AssetBalance = u128;
+
 /// Index of a transaction in the chain.
 pub type Index = u32;
 
@@ -111,6 +127,8 @@ pub mod opaque {
 		pub struct SessionKeys {
 			pub aura: Aura,
 			pub grandpa: Grandpa,
+			pub im_online: ImOnline,
+			pub authority_discovery: AuthorityDiscovery,
 		}
 	}
 }
@@ -119,10 +137,10 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
 	spec_name: create_runtime_str!("mathchain-galois"),
 	impl_name: create_runtime_str!("mathchain-galois"),
 	authoring_version: 2,
-	spec_version: 1,
+	spec_version: 3,
 	impl_version: 1,
 	apis: RUNTIME_API_VERSIONS,
-	transaction_version: 1,
+	transaction_version: 3,
 };
 
 pub const MILLISECS_PER_BLOCK: u64 = 6000;
@@ -156,36 +174,66 @@ parameter_types! {
 	pub const SS58Prefix: u8 = 40;
 }
 
-/// A lookup implementation returning the `AccountId` from a `MultiAddress`.
+/// A lookup implementation returning the `AccountId` from an [`account_service::Address`].
 pub struct AccountIdLookup<AccountId, AccountIndex>(PhantomData<(AccountId, AccountIndex)>);
 impl<AccountId, AccountIndex> StaticLookup for AccountIdLookup<AccountId, AccountIndex>
 where
-	AccountId: Codec + Clone + PartialEq + Debug + From<AccountId32> + core::cmp::PartialEq<AccountId32>,
-	AccountIndex: Codec + Clone + PartialEq + Debug,
-	sp_runtime::MultiAddress<AccountId, AccountIndex>: Codec,
+	AccountId: Codec + Clone + PartialEq + Debug + From<AccountId32> + Into<AccountId32>
+		+ core::cmp::PartialEq<AccountId32>,
+	AccountIndex: Codec + Clone + PartialEq + Debug + From<crate::AccountIndex> + Into<crate::AccountIndex>,
+	account_service::Address<AccountId, AccountIndex>: Codec,
 {
-	type Source = sp_runtime::MultiAddress<AccountId, AccountIndex>;
+	type Source = account_service::Address<AccountId, AccountIndex>;
 	type Target = AccountId;
 	fn lookup(x: Self::Source) -> Result<Self::Target, LookupError> {
 		match x {
-			sp_runtime::MultiAddress::Id(i) => Ok(i),
-			sp_runtime::MultiAddress::Address20(i) => {
-				let account = AccountService::from_ethereum(&AccountServiceEnum::Ethereum(i)).into();
-				Ok(if account == AccountId32::new([0u8; 32]) {
-					let mut data = [0u8; 32];
-					data[0..4].copy_from_slice(b"evm:");
-					data[4..24].copy_from_slice(&i[..]);
-					// let hash = H::hash(&data);
-					AccountId32::new(data).into()
-				} else {
-					account
-				})
-			},
-			_ => Err(LookupError),
+			account_service::Address::Id(i) => Ok(i),
+			account_service::Address::Index(i) => Indices::lookup_index(i.into())
+				.map(Into::into)
+				.ok_or(LookupError),
+			account_service::Address::Address20(i) =>
+				Ok(account_service::ethereum_account(H160::from(i)).into()),
+			account_service::Address::Address32(i) => Ok(AccountId32::new(i).into()),
+			account_service::Address::Raw(_) => Err(LookupError),
+			account_service::Address::Linked(address) => account_service::linked_account(address)
+				.map(Into::into)
+				.ok_or(LookupError),
 		}
 	}
 	fn unlookup(x: Self::Target) -> Self::Source {
-		sp_runtime::MultiAddress::Id(x)
+		let account: AccountId32 = x.clone().into();
+		// `pallet_indices` keeps no index by account, the indices are few enough to be
+		// searched.
+		let index = pallet_indices::Accounts::<Runtime>::iter()
+			.find(|(_, (owner, _, _))| owner == &account)
+			.map(|(index, _)| index);
+		if let Some(index) = index {
+			return account_service::Address::Index(index.into());
+		}
+		if let Some(address) = account_service::fallback_address(&account) {
+			// Only while the address is not bound to another account.
+			if account_service::ethereum_account(address) == account {
+				return account_service::Address::Address20(address.into());
+			}
+		}
+		account_service::Address::Id(x)
+	}
+}
+
+/// Calls allowed to be dispatched, by any origin.
+///
+/// Every pallet is listed, so that adding one is a deliberate choice.
+pub struct BaseFilter;
+impl Filter<Call> for BaseFilter {
+	fn filter(call: &Call) -> bool {
+		match call {
+			Call::System(_) | Call::Timestamp(_) | Call::VrfRandomness(_) |
+			Call::Grandpa(_) | Call::Indices(_) | Call::Balances(_) | Call::Sudo(_) | Call::Recovery(_) |
+			Call::EVMRecovery(_) | Call::AccountService(_) | Call::Identity(_) | Call::Utility(_) | Call::Multisig(_) |
+			Call::Proxy(_) | Call::Ethereum(_) | Call::EVM(_) | Call::Authorship(_) | Call::Staking(_) |
+			Call::Session(_) | Call::ImOnline(_) | Call::Assets(_) | Call::AssetFeePayment(_) |
+			Call::SecretStore(_) => true,
+		}
 	}
 }
 
@@ -193,7 +241,7 @@ where
 
 impl frame_system::Config for Runtime {
 	/// The basic call filter to use in dispatchable.
-	type BaseCallFilter = ();
+	type BaseCallFilter = BaseFilter;
 	/// Block & extrinsics weights: base values and limits.
 	type BlockWeights = BlockWeights;
 	/// The maximum length of a block (in bytes).
@@ -203,7 +251,7 @@ impl frame_system::Config for Runtime {
 	/// The aggregated dispatch type that is available for extrinsics.
 	type Call = Call;
 	/// The lookup mechanism to get account ID from whatever is passed in dispatchers.
-	type Lookup = AccountIdLookup<AccountId, ()>;
+	type Lookup = AccountIdLookup<AccountId, AccountIndex>;
 	/// The index type for storing how many extrinsics an account has signed.
 	type Index = Index;
 	/// The index type for blocks.
@@ -248,7 +296,7 @@ impl pallet_grandpa::Config for Runtime {
 	type Event = Event;
 	type Call = Call;
 
-	type KeyOwnerProofSystem = ();
+	type KeyOwnerProofSystem = Historical;
 
 	type KeyOwnerProof =
 		<Self::KeyOwnerProofSystem as KeyOwnerProofSystem<(KeyTypeId, GrandpaId)>>::Proof;
@@ -258,8 +306,171 @@ impl pallet_grandpa::Config for Runtime {
 		GrandpaId,
 	)>>::IdentificationTuple;
 
-	type HandleEquivocation = ();
+	type HandleEquivocation =
+		pallet_grandpa::EquivocationHandler<Self::KeyOwnerIdentification, Offences, ReportLongevity>;
+
+	type WeightInfo = ();
+}
+
+impl<C> frame_system::offchain::SendTransactionTypes<C> for Runtime where
+	Call: From<C>,
+{
+	type Extrinsic = UncheckedExtrinsic;
+	type OverarchingCall = Call;
+}
+
+parameter_types! {
+	pub const UncleGenerations: BlockNumber = 0;
+}
+
+impl pallet_authorship::Config for Runtime {
+	type FindAuthor = pallet_session::FindAccountFromAuthorIndex<Self, Aura>;
+	type UncleGenerations = UncleGenerations;
+	type FilterUncle = ();
+	type EventHandler = (Staking,);
+}
+
+pallet_staking_reward_curve::build! {
+	const REWARD_CURVE: PiecewiseLinear<'static> = curve!(
+		min_inflation: 0_025_000,
+		max_inflation: 0_100_000,
+		ideal_stake: 0_500_000,
+		falloff: 0_050_000,
+		max_piece_count: 40,
+		test_precision: 0_005_000,
+	);
+}
+
+parameter_types! {
+	pub const SessionPeriod: BlockNumber = 1 * HOURS;
+	pub const SessionOffset: BlockNumber = 0;
+	// Six sessions in an era (6 hours).
+	pub const SessionsPerEra: sp_staking::SessionIndex = 6;
+	// 28 eras for unbonding (7 days).
+	pub const BondingDuration: pallet_staking::EraIndex = 28;
+	// 27 eras in which slashes can be cancelled (slightly less than 7 days).
+	pub const SlashDeferDuration: pallet_staking::EraIndex = 27;
+	pub const RewardCurve: &'static PiecewiseLinear<'static> = &REWARD_CURVE;
+	pub const MaxNominatorRewardedPerValidator: u32 = 256;
+	pub const ReportLongevity: u64 =
+		BondingDuration::get() as u64 * SessionsPerEra::get() as u64 * SessionPeriod::get() as u64;
+}
+
+impl pallet_session::historical::Config for Runtime {
+	type FullIdentification = pallet_staking::Exposure<AccountId, Balance>;
+	type FullIdentificationOf = pallet_staking::ExposureOf<Runtime>;
+}
 
+impl frame_election_provider_support::onchain::Config for Runtime {
+	type AccountId = AccountId;
+	type BlockNumber = BlockNumber;
+	type BlockWeights = BlockWeights;
+	type Accuracy = Perbill;
+	type DataProvider = Staking;
+}
+
+impl pallet_staking::Config for Runtime {
+	const MAX_NOMINATIONS: u32 = 16;
+	type Currency = Balances;
+	type UnixTime = Timestamp;
+	type CurrencyToVote = U128CurrencyToVote;
+	// Without a treasury, the part of the inflation not paid to the validators is not minted.
+	type RewardRemainder = ();
+	type Event = Event;
+	type Slash = ();
+	type Reward = ();
+	type SessionsPerEra = SessionsPerEra;
+	type BondingDuration = BondingDuration;
+	type SlashDeferDuration = SlashDeferDuration;
+	type SlashCancelOrigin = frame_system::EnsureRoot<AccountId>;
+	type SessionInterface = Self;
+	type EraPayout = pallet_staking::ConvertCurve<RewardCurve>;
+	type NextNewSession = Session;
+	type MaxNominatorRewardedPerValidator = MaxNominatorRewardedPerValidator;
+	type ElectionProvider = frame_election_provider_support::onchain::OnChainSequentialPhragmen<Self>;
+	type WeightInfo = ();
+}
+
+parameter_types! {
+	pub const ImOnlineUnsignedPriority: TransactionPriority = TransactionPriority::max_value();
+}
+
+/// Public key no one holds the secret of, registered as the `key_type` session key of
+/// `validator` until it sets its own, at genesis or by `migrations::UpgradeSessionKeys`.
+pub fn placeholder_session_key(key_type: KeyTypeId, validator: &AccountId) -> sr25519::Public {
+	sr25519::Public::from_raw(sp_io::hashing::blake2_256(
+		&(b"mathchain:placeholder-session-key", key_type, validator).encode(),
+	))
+}
+
+/// Reports unresponsive validators to `Offences`, except the validators which still
+/// have their placeholder `im_online` key and could not send heartbeats.
+pub struct ReportUnlessPlaceholderKeys;
+
+type UnresponsivenessOffence = pallet_im_online::UnresponsivenessOffence<
+	pallet_session::historical::IdentificationTuple<Runtime>
+>;
+
+impl sp_staking::offence::ReportOffence<
+	AccountId,
+	pallet_session::historical::IdentificationTuple<Runtime>,
+	UnresponsivenessOffence,
+> for ReportUnlessPlaceholderKeys {
+	fn report_offence(
+		reporters: Vec<AccountId>,
+		mut offence: UnresponsivenessOffence,
+	) -> Result<(), sp_staking::offence::OffenceError> {
+		use sp_core::crypto::key_types::IM_ONLINE;
+
+		let keys = ImOnline::keys();
+		offence.offenders
+			.retain(|(validator, _)| !keys.contains(&placeholder_session_key(IM_ONLINE, validator).into()));
+		if offence.offenders.is_empty() {
+			return Ok(());
+		}
+		<Offences as sp_staking::offence::ReportOffence<_, _, _>>::report_offence(reporters, offence)
+	}
+
+	fn is_known_offence(
+		offenders: &[pallet_session::historical::IdentificationTuple<Runtime>],
+		time_slot: &sp_staking::SessionIndex,
+	) -> bool {
+		<Offences as sp_staking::offence::ReportOffence<_, _, UnresponsivenessOffence>>::is_known_offence(
+			offenders,
+			time_slot,
+		)
+	}
+}
+
+impl pallet_im_online::Config for Runtime {
+	type AuthorityId = ImOnlineId;
+	type Event = Event;
+	type NextSessionRotation = pallet_session::PeriodicSessions<SessionPeriod, SessionOffset>;
+	type ValidatorSet = Historical;
+	// Validators missing a whole session are chilled by staking, once every validator
+	// has its own `im_online` key.
+	type ReportUnresponsiveness = ReportUnlessPlaceholderKeys;
+	type UnsignedPriority = ImOnlineUnsignedPriority;
+	type WeightInfo = ();
+}
+
+impl pallet_authority_discovery::Config for Runtime {}
+
+impl pallet_offences::Config for Runtime {
+	type Event = Event;
+	type IdentificationTuple = pallet_session::historical::IdentificationTuple<Self>;
+	type OnOffenceHandler = Staking;
+}
+
+parameter_types! {
+	pub const IndexDeposit: Balance = 1 * DOLLARS;
+}
+
+impl pallet_indices::Config for Runtime {
+	type AccountIndex = AccountIndex;
+	type Currency = Balances;
+	type Deposit = IndexDeposit;
+	type Event = Event;
 	type WeightInfo = ();
 }
 
@@ -280,6 +491,153 @@ impl pallet_recovery::Config for Runtime {
 	type RecoveryDeposit = RecoveryDeposit;
 }
 
+impl pallet_evm_recovery::Config for Runtime {
+	type Event = Event;
+	type AddressMapping = <Runtime as pallet_evm::Config>::AddressMapping;
+	type OnRecovered = account_service::RelinkEthereum;
+	type WeightInfo = pallet_evm_recovery::weights::SubstrateWeight<Runtime>;
+}
+
+parameter_types! {
+	pub const BasicDeposit: Balance = deposit(1, 258);
+	pub const FieldDeposit: Balance = deposit(0, 66);
+	pub const SubAccountDeposit: Balance = deposit(1, 53);
+	pub const MaxSubAccounts: u32 = 100;
+	pub const MaxAdditionalFields: u32 = 100;
+	pub const MaxRegistrars: u32 = 20;
+}
+
+impl pallet_identity::Config for Runtime {
+	type Event = Event;
+	type Currency = Balances;
+	type BasicDeposit = BasicDeposit;
+	type FieldDeposit = FieldDeposit;
+	type SubAccountDeposit = SubAccountDeposit;
+	type MaxSubAccounts = MaxSubAccounts;
+	type MaxAdditionalFields = MaxAdditionalFields;
+	type MaxRegistrars = MaxRegistrars;
+	type Slashed = ();
+	type ForceOrigin = frame_system::EnsureRoot<AccountId>;
+	type RegistrarOrigin = frame_system::EnsureRoot<AccountId>;
+	type WeightInfo = ();
+}
+
+impl pallet_utility::Config for Runtime {
+	type Event = Event;
+	type Call = Call;
+	type WeightInfo = ();
+}
+
+parameter_types! {
+	// One storage item; key size is 32; value is size 4+4+16+32 bytes = 56 bytes.
+	pub const DepositBase: Balance = deposit(1, 88);
+	// Additional storage item size of 32 bytes.
+	pub const DepositFactor: Balance = deposit(0, 32);
+	pub const MaxSignatories: u16 = 100;
+}
+
+impl pallet_multisig::Config for Runtime {
+	type Event = Event;
+	type Call = Call;
+	type Currency = Balances;
+	type DepositBase = DepositBase;
+	type DepositFactor = DepositFactor;
+	type MaxSignatories = MaxSignatories;
+	type WeightInfo = ();
+}
+
+parameter_types! {
+	// One storage item; key size 32, value size 8.
+	pub const ProxyDepositBase: Balance = deposit(1, 8);
+	// Additional storage item size of 33 bytes.
+	pub const ProxyDepositFactor: Balance = deposit(0, 33);
+	pub const MaxProxies: u16 = 32;
+	pub const AnnouncementDepositBase: Balance = deposit(1, 8);
+	pub const AnnouncementDepositFactor: Balance = deposit(0, 66);
+	pub const MaxPending: u32 = 32;
+}
+
+/// The type used to represent the kinds of proxying allowed.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Encode, Decode, RuntimeDebug)]
+pub enum ProxyType {
+	/// Any call.
+	Any,
+	/// Any call which cannot move funds.
+	NonTransfer,
+	/// Staking, validation and session key management.
+	ValidatorSet,
+	/// Secret Store calls.
+	SecretStore,
+	/// Nickname and address bindings of the account service.
+	AccountService,
+}
+
+impl Default for ProxyType {
+	fn default() -> Self {
+		Self::Any
+	}
+}
+
+impl InstanceFilter<Call> for ProxyType {
+	fn filter(&self, c: &Call) -> bool {
+		match self {
+			ProxyType::Any => true,
+			ProxyType::NonTransfer => !matches!(c,
+				Call::Balances(..) |
+				Call::Indices(pallet_indices::Call::transfer(..)) |
+				Call::EVM(..) |
+				Call::Ethereum(..) |
+				Call::EVMRecovery(..) |
+				Call::Assets(..) |
+				// The rate the fee oracle sets is what fee payers are charged.
+				Call::AssetFeePayment(..) |
+				// `as_recovered` dispatches without the proxy filter.
+				Call::Recovery(..) |
+				// As does `sudo_as`, and `set_key` hands the key over.
+				Call::Sudo(..)
+			),
+			ProxyType::ValidatorSet => matches!(c,
+				Call::Staking(..) |
+				Call::Session(..) |
+				Call::Utility(..)
+			),
+			ProxyType::SecretStore => matches!(c,
+				Call::SecretStore(..) |
+				Call::Utility(..)
+			),
+			ProxyType::AccountService => matches!(c,
+				Call::AccountService(..) |
+				Call::Utility(..)
+			),
+		}
+	}
+
+	fn is_superset(&self, o: &Self) -> bool {
+		match (self, o) {
+			(x, y) if x == y => true,
+			(ProxyType::Any, _) => true,
+			(_, ProxyType::Any) => false,
+			(ProxyType::NonTransfer, _) => true,
+			_ => false,
+		}
+	}
+}
+
+impl pallet_proxy::Config for Runtime {
+	type Event = Event;
+	type Call = Call;
+	type Currency = Balances;
+	type ProxyType = ProxyType;
+	type ProxyDepositBase = ProxyDepositBase;
+	type ProxyDepositFactor = ProxyDepositFactor;
+	type MaxProxies = MaxProxies;
+	type WeightInfo = ();
+	type MaxPending = MaxPending;
+	type CallHasher = BlakeTwo256;
+	type AnnouncementDepositBase = AnnouncementDepositBase;
+	type AnnouncementDepositFactor = AnnouncementDepositFactor;
+}
+
 parameter_types! {
 	pub const MinimumPeriod: u64 = SLOT_DURATION / 2;
 }
@@ -319,13 +677,63 @@ parameter_types! {
 	pub const TransactionByteFee: Balance = 1;
 }
 
+type NegativeImbalance = <Balances as Currency<AccountId>>::NegativeImbalance;
+
+/// Pays the transaction fees and tips to the block author.
+pub struct ToAuthor;
+impl OnUnbalanced<NegativeImbalance> for ToAuthor {
+	fn on_nonzero_unbalanced(amount: NegativeImbalance) {
+		Balances::resolve_creating(&Authorship::author(), amount);
+	}
+}
+
 impl pallet_transaction_payment::Config for Runtime {
-	type OnChargeTransaction = CurrencyAdapter<Balances, ()>;
+	type OnChargeTransaction = CurrencyAdapter<Balances, ToAuthor>;
 	type TransactionByteFee = TransactionByteFee;
 	type WeightToFee = IdentityFee<Balance>;
 	type FeeMultiplierUpdate = ();
 }
 
+parameter_types! {
+	pub const AssetDeposit: Balance = 100 * MATHS;
+	pub const ApprovalDeposit: Balance = 1 * MATHS;
+	pub const AssetsStringLimit: u32 = 50;
+	pub const MetadataDepositBase: Balance = 10 * MATHS;
+	pub const MetadataDepositPerByte: Balance = 1 * MATHS;
+}
+
+impl pallet_assets::Config for Runtime {
+	type Event = Event;
+	type Balance = AssetBalance;
+	type AssetId = AssetId;
+	type Currency = Balances;
+	type ForceOrigin = frame_system::EnsureRoot<AccountId>;
+	type AssetDeposit = AssetDeposit;
+	type MetadataDepositBase = MetadataDepositBase;
+	type MetadataDepositPerByte = MetadataDepositPerByte;
+	type ApprovalDeposit = ApprovalDeposit;
+	type StringLimit = AssetsStringLimit;
+	type Freezer = ();
+	type Extra = ();
+	type WeightInfo = pallet_assets::weights::SubstrateWeight<Runtime>;
+}
+
+/// Receives the transaction fees paid in assets, as `ToAuthor` does for MATH.
+pub struct BlockAuthor;
+impl Get<AccountId> for BlockAuthor {
+	fn get() -> AccountId {
+		Authorship::author()
+	}
+}
+
+impl pallet_asset_fee_payment::Config for Runtime {
+	type Event = Event;
+	type Assets = Assets;
+	type FeeReceiver = BlockAuthor;
+	type SufficientAssets = pallet_asset_fee_payment::SufficientAssetsOf<Runtime>;
+	type WeightInfo = pallet_asset_fee_payment::weights::SubstrateWeight<Runtime>;
+}
+
 impl pallet_sudo::Config for Runtime {
 	type Event = Event;
 	type Call = Call;
@@ -381,16 +789,63 @@ impl pallet_evm::Config for Runtime {
 	type Currency = Balances;
 	type Event = Event;
 	type Runner = pallet_evm::runner::stack::Runner<Self>;
-	type Precompiles = (
-		pallet_evm_precompile_simple::ECRecover,
-		pallet_evm_precompile_simple::Sha256,
-		pallet_evm_precompile_simple::Ripemd160,
-		pallet_evm_precompile_simple::Identity,
-	);
+	type Precompiles = MathChainPrecompiles;
 	type ChainId = ChainId;
 	type OnChargeTransaction = ();
 }
 
+/// Address of `pallet_vrf_randomness::RandomnessPrecompile`.
+pub const RANDOMNESS_PRECOMPILE: H160 = H160([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 4, 0]);
+
+/// The Ethereum precompiles, and the MathChain ones from `0x0400`.
+pub struct MathChainPrecompiles;
+
+impl PrecompileSet for MathChainPrecompiles {
+	fn execute(
+		address: H160,
+		input: &[u8],
+		target_gas: Option<u64>,
+		context: &Context,
+	) -> Option<Result<(ExitSucceed, Vec<u8>, u64), ExitError>> {
+		if address == RANDOMNESS_PRECOMPILE {
+			return Some(pallet_vrf_randomness::RandomnessPrecompile::<Runtime>::execute(input, target_gas, context));
+		}
+		<(
+			pallet_evm_precompile_simple::ECRecover,
+			pallet_evm_precompile_simple::Sha256,
+			pallet_evm_precompile_simple::Ripemd160,
+			pallet_evm_precompile_simple::Identity,
+		) as PrecompileSet>::execute(address, input, target_gas, context)
+	}
+}
+
+impl pallet_vrf_randomness::Config for Runtime {
+	type FindSlot = AuraSlot;
+	type Authorities = AuraAuthorityKeys;
+}
+
+/// Aura keys of the authorities.
+pub struct AuraAuthorityKeys;
+impl Get<Vec<sr25519::Public>> for AuraAuthorityKeys {
+	fn get() -> Vec<sr25519::Public> {
+		Aura::authorities().iter()
+			.map(|authority_id| sr25519::Public::from_slice(&authority_id.to_raw_vec()))
+			.collect()
+	}
+}
+
+/// Aura slot of the block.
+pub struct AuraSlot;
+impl pallet_vrf_randomness::FindSlot for AuraSlot {
+	fn find_slot<'a, I>(digests: I) -> Option<u64> where
+		I: 'a + IntoIterator<Item=(ConsensusEngineId, &'a [u8])>
+	{
+		digests.into_iter()
+			.find(|(id, _)| *id == sp_consensus_aura::AURA_ENGINE_ID)
+			.and_then(|(_, mut data)| u64::decode(&mut data).ok())
+	}
+}
+
 pub struct EthereumFindAuthor<F>(PhantomData<F>);
 impl<F: FindAuthor<u32>> FindAuthor<H160> for EthereumFindAuthor<F>
 {
@@ -416,16 +871,20 @@ impl pallet_ethereum::Config for Runtime {
 	type BlockGasLimit = BlockGasLimit;
 }
 
+parameter_types! {
+	pub const DisabledValidatorsThreshold: Perbill = Perbill::from_percent(17);
+}
+
 impl pallet_session::Config for Runtime {
 	type SessionHandler = <opaque::SessionKeys as OpaqueKeys>::KeyTypeIdProviders;
-	type ShouldEndSession = ValidatorSet;
-	type SessionManager = ValidatorSet;
+	type ShouldEndSession = pallet_session::PeriodicSessions<SessionPeriod, SessionOffset>;
+	type SessionManager = pallet_session::historical::NoteHistoricalRoot<Self, Staking>;
 	type Event = Event;
 	type Keys = opaque::SessionKeys;
-	type NextSessionRotation = ValidatorSet;
+	type NextSessionRotation = pallet_session::PeriodicSessions<SessionPeriod, SessionOffset>;
 	type ValidatorId = <Self as frame_system::Config>::AccountId;
-	type ValidatorIdOf = pallet_validator_set::ValidatorOf<Self>;
-	type DisabledValidatorsThreshold = ();
+	type ValidatorIdOf = pallet_staking::StashOf<Self>;
+	type DisabledValidatorsThreshold = DisabledValidatorsThreshold;
 	type WeightInfo = ();
 }
 
@@ -441,23 +900,39 @@ construct_runtime!(
 		NodeBlock = opaque::Block,
 		UncheckedExtrinsic = UncheckedExtrinsic
 	{
-		System: frame_system::{Pallet, Call, Config, Storage, Event<T>},
-		RandomnessCollectiveFlip: pallet_randomness_collective_flip::{Pallet, Call, Storage},
-		Timestamp: pallet_timestamp::{Pallet, Call, Storage, Inherent},
-		Aura: pallet_aura::{Pallet, Config<T>},
-		Grandpa: pallet_grandpa::{Pallet, Call, Storage, Config, Event},
-		Balances: pallet_balances::{Pallet, Call, Storage, Config<T>, Event<T>},
-		TransactionPayment: pallet_transaction_payment::{Pallet, Storage},
-		Sudo: pallet_sudo::{Pallet, Call, Config<T>, Storage, Event<T>},
-		Recovery: pallet_recovery::{Pallet, Call, Storage, Event<T>},
-		AccountService: pallet_account_service::{Pallet, Call, Storage, Event<T>},
+		// Pallets keep their index across upgrades: new ones are appended, and the
+		// index of a removed one is not reused (1 and 12 held RandomnessCollectiveFlip
+		// and ValidatorSet). Staking is declared before Session for its genesis.
+		System: frame_system::{Pallet, Call, Config, Storage, Event<T>} = 0,
+		Timestamp: pallet_timestamp::{Pallet, Call, Storage, Inherent} = 2,
+		Aura: pallet_aura::{Pallet, Config<T>} = 3,
+		Grandpa: pallet_grandpa::{Pallet, Call, Storage, Config, Event, ValidateUnsigned} = 4,
+		Authorship: pallet_authorship::{Pallet, Call, Storage, Inherent} = 15,
+		VrfRandomness: pallet_vrf_randomness::{Pallet, Call, Storage, Inherent} = 16,
+		Indices: pallet_indices::{Pallet, Call, Storage, Event<T>} = 17,
+		Balances: pallet_balances::{Pallet, Call, Storage, Config<T>, Event<T>} = 5,
+		TransactionPayment: pallet_transaction_payment::{Pallet, Storage} = 6,
+		Assets: pallet_assets::{Pallet, Call, Storage, Event<T>} = 18,
+		AssetFeePayment: pallet_asset_fee_payment::{Pallet, Call, Storage, Event<T>} = 19,
+		Sudo: pallet_sudo::{Pallet, Call, Config<T>, Storage, Event<T>} = 7,
+		Recovery: pallet_recovery::{Pallet, Call, Storage, Event<T>} = 8,
+		EVMRecovery: pallet_evm_recovery::{Pallet, Call, Event<T>} = 20,
+		AccountService: pallet_account_service::{Pallet, Call, Storage, Event<T>} = 9,
+		Identity: pallet_identity::{Pallet, Call, Storage, Event<T>} = 21,
+		Utility: pallet_utility::{Pallet, Call, Event} = 22,
+		Multisig: pallet_multisig::{Pallet, Call, Storage, Event<T>} = 23,
+		Proxy: pallet_proxy::{Pallet, Call, Storage, Event<T>} = 24,
 		// Include the custom logic from the template pallet in the runtime.
 		// TemplatePallet: pallet_template::{Pallet, Call, Storage, Event<T>},
-		Ethereum: pallet_ethereum::{Pallet, Call, Storage, Event, Config, ValidateUnsigned},
-		EVM: pallet_evm::{Pallet, Config, Call, Storage, Event<T>},
-		ValidatorSet: pallet_validator_set::{Pallet, Call, Storage, Event<T>, Config<T>},
-		Session: pallet_session::{Pallet, Call, Storage, Event, Config<T>},
-		SecretStore: secretstore_runtime_module::{Pallet, Call, Event, Config<T>},
+		Ethereum: pallet_ethereum::{Pallet, Call, Storage, Event, Config, ValidateUnsigned} = 10,
+		EVM: pallet_evm::{Pallet, Config, Call, Storage, Event<T>} = 11,
+		Staking: pallet_staking::{Pallet, Call, Config<T>, Storage, Event<T>} = 25,
+		Offences: pallet_offences::{Pallet, Storage, Event} = 26,
+		Historical: pallet_session_historical::{Pallet} = 27,
+		Session: pallet_session::{Pallet, Call, Storage, Event, Config<T>} = 13,
+		ImOnline: pallet_im_online::{Pallet, Call, Storage, Event<T>, ValidateUnsigned, Config<T>} = 28,
+		AuthorityDiscovery: pallet_authority_discovery::{Pallet, Config} = 29,
+		SecretStore: secretstore_runtime_module::{Pallet, Call, Event, Config<T>} = 14,
 	}
 );
 
@@ -478,7 +953,7 @@ impl fp_rpc::ConvertTransaction<opaque::UncheckedExtrinsic> for TransactionConve
 }
 
 /// The address format for describing accounts.
-pub type Address = sp_runtime::MultiAddress<AccountId, ()>;
+pub type Address = account_service::Address<AccountId, AccountIndex>;
 // pub type Address = AccountId;
 /// Block header type as expected by this runtime.
 pub type Header = generic::Header<BlockNumber, BlakeTwo256>;
@@ -496,8 +971,20 @@ pub type SignedExtra = (
 	frame_system::CheckEra<Runtime>,
 	frame_system::CheckNonce<Runtime>,
 	frame_system::CheckWeight<Runtime>,
-	pallet_transaction_payment::ChargeTransactionPayment<Runtime>
+	pallet_asset_fee_payment::ChargeAssetTxPayment<Runtime>
 );
+
+/// Asset the fees of an extrinsic are paid in, `None` for MATH.
+pub trait FeeAsset {
+	fn fee_asset(&self) -> Option<AssetId>;
+}
+
+impl FeeAsset for SignedExtra {
+	fn fee_asset(&self) -> Option<AssetId> {
+		let (_, _, _, _, _, _, charge_fee) = self;
+		charge_fee.asset
+	}
+}
 /// Unchecked extrinsic type as expected by this runtime.
 pub type UncheckedExtrinsic = generic::UncheckedExtrinsic<Address, Call, Signature, SignedExtra>;
 /// Extrinsic type that has already been checked.
@@ -509,6 +996,7 @@ pub type Executive = frame_executive::Executive<
 	frame_system::ChainContext<Runtime>,
 	Runtime,
 	AllPallets,
+	migrations::Migrations,
 >;
 
 impl_runtime_apis! {
@@ -553,7 +1041,7 @@ impl_runtime_apis! {
 		}
 
 		fn random_seed() -> <Block as BlockT>::Hash {
-			RandomnessCollectiveFlip::random_seed().0
+			VrfRandomness::random_seed().0
 		}
 	}
 
@@ -582,6 +1070,18 @@ impl_runtime_apis! {
 		}
 	}
 
+	impl pallet_vrf_randomness::VrfRandomnessApi<Block> for Runtime {
+		fn current_randomness() -> [u8; 32] {
+			VrfRandomness::current_randomness()
+		}
+	}
+
+	impl sp_authority_discovery::AuthorityDiscoveryApi<Block> for Runtime {
+		fn authorities() -> Vec<AuthorityDiscoveryId> {
+			AuthorityDiscovery::authorities()
+		}
+	}
+
 	impl sp_session::SessionKeys<Block> for Runtime {
 		fn generate_session_keys(seed: Option<Vec<u8>>) -> Vec<u8> {
 			opaque::SessionKeys::generate(seed)
@@ -599,23 +1099,27 @@ impl_runtime_apis! {
 		}
 
 		fn submit_report_equivocation_unsigned_extrinsic(
-			_equivocation_proof: fg_primitives::EquivocationProof<
+			equivocation_proof: fg_primitives::EquivocationProof<
 				<Block as BlockT>::Hash,
 				NumberFor<Block>,
 			>,
-			_key_owner_proof: fg_primitives::OpaqueKeyOwnershipProof,
+			key_owner_proof: fg_primitives::OpaqueKeyOwnershipProof,
 		) -> Option<()> {
-			None
+			let key_owner_proof = key_owner_proof.decode()?;
+
+			Grandpa::submit_unsigned_equivocation_report(
+				equivocation_proof,
+				key_owner_proof,
+			)
 		}
 
 		fn generate_key_ownership_proof(
 			_set_id: fg_primitives::SetId,
-			_authority_id: GrandpaId,
+			authority_id: GrandpaId,
 		) -> Option<fg_primitives::OpaqueKeyOwnershipProof> {
-			// NOTE: this is the only implementation possible since we've
-			// defined our key owner proof type as a bottom type (i.e. a type
-			// with no values).
-			None
+			Historical::prove((fg_primitives::KEY_TYPE, authority_id))
+				.map(|p| p.encode())
+				.map(fg_primitives::OpaqueKeyOwnershipProof::new)
 		}
 	}
 	
@@ -640,6 +1144,27 @@ impl_runtime_apis! {
 		}
 	}
 
+	impl mathchain_rpc_primitives::asset_fee_payment::AssetFeePaymentRuntimeApi<Block, AssetId, AssetBalance> for Runtime {
+		fn query_fee_details(
+			uxt: <Block as BlockT>::Extrinsic,
+			len: u32,
+		) -> Option<mathchain_rpc_primitives::asset_fee_payment::AssetFeeDetails<AssetId, AssetBalance>> {
+			use mathchain_rpc_primitives::asset_fee_payment::{AssetFeeDetails, AssetInclusionFee};
+
+			let asset = uxt.signature.as_ref().and_then(|(_, _, extra)| extra.fee_asset())?;
+			let details = AssetFeePayment::quote_fee_details(asset, TransactionPayment::query_fee_details(uxt, len))?;
+			Some(AssetFeeDetails {
+				asset,
+				inclusion_fee: details.inclusion_fee.map(|fee| AssetInclusionFee {
+					base_fee: fee.base_fee,
+					len_fee: fee.len_fee,
+					adjusted_weight_fee: fee.adjusted_weight_fee,
+				}),
+				tip: details.tip,
+			})
+		}
+	}
+
 	impl fp_rpc::EthereumRuntimeRPCApi<Block> for Runtime {
 		fn chain_id() -> u64 {
 			<Runtime as pallet_evm::Config>::ChainId::get()
@@ -750,6 +1275,147 @@ impl_runtime_apis! {
 		}
 	}
 	
+	impl mathchain_rpc_primitives::debug::DebugRuntimeApi<Block> for Runtime {
+		fn trace_transaction(
+			extrinsics: Vec<<Block as BlockT>::Extrinsic>,
+			transaction: &pallet_ethereum::Transaction,
+			trace_type: mathchain_rpc_primitives::debug::TraceType,
+		) -> Result<mathchain_rpc_primitives::debug::TransactionTrace, sp_runtime::DispatchError> {
+			#[cfg(feature = "evm-tracing")]
+			{
+				for ext in extrinsics.into_iter() {
+					match &ext.function {
+						Call::Ethereum(pallet_ethereum::Call::transact(t)) if t == transaction => {
+							let (tracer, _) = tracer::Tracer::new(trace_type)
+								.trace(|| Executive::apply_extrinsic(ext));
+							return Ok(tracer.into_trace());
+						},
+						_ => { let _ = Executive::apply_extrinsic(ext); },
+					};
+				}
+
+				Err(sp_runtime::DispatchError::Other(
+					"Failed to find Ethereum transaction among the extrinsics."
+				))
+			}
+			#[cfg(not(feature = "evm-tracing"))]
+			{
+				let _ = (extrinsics, transaction, trace_type);
+				Err(sp_runtime::DispatchError::Other(
+					"Missing `evm-tracing` compile time feature flag."
+				))
+			}
+		}
+
+		fn trace_block(
+			extrinsics: Vec<<Block as BlockT>::Extrinsic>,
+			trace_type: mathchain_rpc_primitives::debug::TraceType,
+		) -> Result<Vec<mathchain_rpc_primitives::debug::TransactionTrace>, sp_runtime::DispatchError> {
+			#[cfg(feature = "evm-tracing")]
+			{
+				let mut traces = Vec::new();
+				for ext in extrinsics.into_iter() {
+					match &ext.function {
+						Call::Ethereum(pallet_ethereum::Call::transact(_)) => {
+							let (tracer, _) = tracer::Tracer::new(trace_type.clone())
+								.trace(|| Executive::apply_extrinsic(ext));
+							traces.push(tracer.into_trace());
+						},
+						_ => { let _ = Executive::apply_extrinsic(ext); },
+					};
+				}
+
+				Ok(traces)
+			}
+			#[cfg(not(feature = "evm-tracing"))]
+			{
+				let _ = (extrinsics, trace_type);
+				Err(sp_runtime::DispatchError::Other(
+					"Missing `evm-tracing` compile time feature flag."
+				))
+			}
+		}
+	}
+
+	impl mathchain_rpc_primitives::account_service::AccountServiceRuntimeApi<Block, AccountId> for Runtime {
+		fn ethereum_account(address: H160) -> AccountId {
+			account_service::ethereum_account(address)
+		}
+
+		fn linked_addresses(
+			account: AccountId,
+		) -> Vec<mathchain_rpc_primitives::account_service::LinkedAddress> {
+			account_service::linked_addresses(&account)
+		}
+
+		fn nickname_account(nickname: Vec<u8>) -> Option<AccountId> {
+			account_service::nickname_account(nickname)
+		}
+
+		fn linked_account(
+			address: mathchain_rpc_primitives::account_service::LinkedAddress,
+		) -> Option<AccountId> {
+			account_service::linked_account(account_service::service_address(address))
+		}
+
+		fn identity(
+			account: AccountId,
+		) -> Option<mathchain_rpc_primitives::account_service::Identity> {
+			account_service::identity(&account)
+		}
+	}
+
+	impl mathchain_rpc_primitives::recovery::RecoveryRuntimeApi<Block, AccountId, Balance, BlockNumber> for Runtime {
+		fn recovery_status(
+			account: AccountId,
+		) -> mathchain_rpc_primitives::recovery::RecoveryStatus<AccountId, Balance, BlockNumber> {
+			use frame_support::storage::IterableStorageDoubleMap;
+			use mathchain_rpc_primitives::recovery::{ActiveRecovery, RecoveryConfig, RecoveryStatus};
+
+			let config = Recovery::recovery_config(&account);
+			let delay_period = config.as_ref().map(|config| config.delay_period).unwrap_or_default();
+			let active_recoveries = pallet_recovery::ActiveRecoveries::<Runtime>::iter_prefix(&account)
+				.map(|(rescuer, recovery)| ActiveRecovery {
+					rescuer,
+					created: recovery.created,
+					delay_elapses_at: recovery.created.saturating_add(delay_period),
+					vouched: recovery.friends,
+					deposit: recovery.deposit,
+				})
+				.collect();
+
+			RecoveryStatus {
+				config: config.map(|config| RecoveryConfig {
+					friends: config.friends,
+					threshold: config.threshold,
+					delay_period: config.delay_period,
+					deposit: config.deposit,
+				}),
+				active_recoveries,
+				recovered: Recovery::proxy(&account),
+			}
+		}
+	}
+
+	impl mathchain_rpc_primitives::txpool::TxPoolRuntimeApi<Block> for Runtime {
+		fn extrinsic_filter(
+			xts_ready: Vec<<Block as BlockT>::Extrinsic>,
+			xts_future: Vec<<Block as BlockT>::Extrinsic>,
+		) -> mathchain_rpc_primitives::txpool::TxPoolResponse {
+			let transactions = |xts: Vec<<Block as BlockT>::Extrinsic>| xts.into_iter()
+				.filter_map(|xt| match xt.function {
+					Call::Ethereum(pallet_ethereum::Call::transact(t)) => Some(t),
+					_ => None,
+				})
+				.collect::<Vec<pallet_ethereum::Transaction>>();
+
+			mathchain_rpc_primitives::txpool::TxPoolResponse {
+				ready: transactions(xts_ready),
+				future: transactions(xts_future),
+			}
+		}
+	}
+
 	impl secretstore_runtime_primitives::acl_storage::SecretStoreAclApi<Block> for Runtime {
 		fn check(
 			requester: secretstore_runtime_primitives::EntityId,
@@ -829,4 +1495,142 @@ impl_runtime_apis! {
 			SecretStore::is_document_key_shadow_retrieval_response_required(key_server, key_id, requester)
 		}
 	}
+
+	#[cfg(feature = "runtime-benchmarks")]
+	impl frame_benchmarking::Benchmark<Block> for Runtime {
+		fn dispatch_benchmark(
+			config: frame_benchmarking::BenchmarkConfig
+		) -> Result<Vec<frame_benchmarking::BenchmarkBatch>, sp_runtime::RuntimeString> {
+			use frame_benchmarking::{Benchmarking, BenchmarkBatch, add_benchmark, TrackedStorageKey};
+
+			use frame_system_benchmarking::Pallet as SystemBench;
+			impl frame_system_benchmarking::Config for Runtime {}
+
+			let whitelist: Vec<TrackedStorageKey> = vec![
+				// Block Number
+				hex_literal::hex!("26aa394eea5630e07c48ae0c9558cef702a5c1b19ab7a04f536c519aca4983ac").to_vec().into(),
+				// Total Issuance
+				hex_literal::hex!("c2261276cc9d1f8598ea4b6a74b15c2f57c875e4cff74148e4628f264b974c80").to_vec().into(),
+				// Execution Phase
+				hex_literal::hex!("26aa394eea5630e07c48ae0c9558cef7ff553b5a9862a516939d82b3d3d8661a").to_vec().into(),
+				// Event Count
+				hex_literal::hex!("26aa394eea5630e07c48ae0c9558cef70a98fdbe9ce6c55837576c60c7af3850").to_vec().into(),
+				// System Events
+				hex_literal::hex!("26aa394eea5630e07c48ae0c9558cef780d41e5e16056765bc8461851072c9d7").to_vec().into(),
+			];
+
+			let mut batches = Vec::<BenchmarkBatch>::new();
+			let params = (&config, &whitelist);
+
+			add_benchmark!(params, batches, frame_system, SystemBench::<Runtime>);
+			add_benchmark!(params, batches, pallet_timestamp, Timestamp);
+			add_benchmark!(params, batches, pallet_evm_recovery, EVMRecovery);
+			add_benchmark!(params, batches, pallet_asset_fee_payment, AssetFeePayment);
+
+			if batches.is_empty() { return Err("Benchmark not found for this pallet.".into()) }
+			Ok(batches)
+		}
+	}
+
+	#[cfg(feature = "try-runtime")]
+	impl frame_try_runtime::TryRuntime<Block> for Runtime {
+		fn on_runtime_upgrade() -> Result<(Weight, Weight), sp_runtime::RuntimeString> {
+			let weight = Executive::try_runtime_upgrade()?;
+			// As `Executive::initialize_block` does after an upgrade, so that the blocks
+			// executed afterwards do not run the migrations again.
+			frame_system::LastRuntimeUpgrade::<Runtime>::put(
+				frame_system::LastRuntimeUpgradeInfo::from(VERSION),
+			);
+			let weight = weight.saturating_add(RocksDbWeight::get().writes(1));
+			Ok((weight, BlockWeights::get().max_block))
+		}
+	}
+
+	#[cfg(feature = "try-runtime")]
+	impl migrations::TryExecuteBlock<Block> for Runtime {
+		fn execute_block_no_check(block: Block) -> migrations::ExecutedBlock {
+			let (header, extrinsics) = block.deconstruct();
+			Executive::initialize_block(&header);
+			let (mut invalid, mut dispatch_errors) = (0, 0);
+			for xt in extrinsics {
+				match Executive::apply_extrinsic(xt) {
+					Ok(Ok(())) => {},
+					// The call failed, as it may have on the chain too.
+					Ok(Err(_)) => dispatch_errors += 1,
+					Err(_) => invalid += 1,
+				}
+			}
+			let weight = System::block_weight().total();
+			Executive::finalize_block();
+			migrations::ExecutedBlock { weight, invalid, dispatch_errors }
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use frame_support::{assert_ok, storage::unhashed};
+	use sp_core::crypto::key_types::IM_ONLINE;
+	use sp_io::hashing::twox_128;
+	use sp_staking::offence::ReportOffence;
+
+	#[test]
+	fn validators_with_placeholder_keys_are_not_reported() {
+		let mut ext: sp_io::TestExternalities = frame_system::GenesisConfig::default()
+			.build_storage::<Runtime>().unwrap().into();
+		ext.execute_with(|| {
+			let (online, offline, placeholder) =
+				(AccountId::new([1; 32]), AccountId::new([2; 32]), AccountId::new([3; 32]));
+			let validators = vec![online.clone(), offline.clone(), placeholder.clone()];
+			let keys: Vec<ImOnlineId> = vec![
+				sr25519::Public::from_raw([1; 32]).into(),
+				sr25519::Public::from_raw([2; 32]).into(),
+				placeholder_session_key(IM_ONLINE, &placeholder).into(),
+			];
+			unhashed::put(&[twox_128(b"Session"), twox_128(b"Validators")].concat(), &validators);
+			unhashed::put(&[twox_128(b"ImOnline"), twox_128(b"Keys")].concat(), &keys);
+			assert_eq!(ImOnline::keys(), keys);
+
+			let offender = |validator: &AccountId| (validator.clone(), Default::default());
+			assert_ok!(ReportUnlessPlaceholderKeys::report_offence(vec![], UnresponsivenessOffence {
+				session_index: 0,
+				validator_set_count: 3,
+				offenders: vec![offender(&offline), offender(&placeholder)],
+			}));
+
+			assert!(ReportUnlessPlaceholderKeys::is_known_offence(&[offender(&offline)], &0));
+			assert!(!ReportUnlessPlaceholderKeys::is_known_offence(&[offender(&placeholder)], &0));
+			assert!(!ReportUnlessPlaceholderKeys::is_known_offence(&[offender(&online)], &0));
+		});
+	}
+
+	#[test]
+	fn randomness_precompile_is_at_0x0400() {
+		let mut ext: sp_io::TestExternalities = frame_system::GenesisConfig::default()
+			.build_storage::<Runtime>().unwrap().into();
+		ext.execute_with(|| {
+			let context = Context {
+				address: RANDOMNESS_PRECOMPILE,
+				caller: H160::repeat_byte(1),
+				apparent_value: U256::zero(),
+			};
+			let subject = b"subject";
+			let (random, block) = VrfRandomness::random(subject);
+			let mut number = [0u8; 32];
+			U256::from(block).to_big_endian(&mut number);
+			let expected = [random.as_bytes(), &number[..]].concat();
+
+			assert_eq!(RANDOMNESS_PRECOMPILE, H160::from_low_u64_be(0x0400));
+			assert_eq!(
+				MathChainPrecompiles::execute(RANDOMNESS_PRECOMPILE, subject, None, &context),
+				Some(Ok((ExitSucceed::Returned, expected, 2_106))),
+			);
+			assert_eq!(
+				MathChainPrecompiles::execute(RANDOMNESS_PRECOMPILE, subject, Some(2_000), &context),
+				Some(Err(ExitError::OutOfGas)),
+			);
+			assert_eq!(MathChainPrecompiles::execute(H160::from_low_u64_be(0x0401), subject, None, &context), None);
+		});
+	}
 }
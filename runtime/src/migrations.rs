@@ -0,0 +1,509 @@
+//! Runtime upgrades.
+//!
+//! A `Migration` changes the storage of a pallet to the layout of a version of
+//! the pallet. `Versioned` runs it through `Executive` only while the
+//! `PalletVersion` FRAME stores for the pallet is lower than that version. Once
+//! the migrations ran, the pallets store their own version, no lower than the
+//! version of their migrations, so a migration runs once, on the upgrade of its
+//! pallet, even if it stays in `Migrations` for several releases.
+//!
+//! The storage of the removed pallets, the session keys and the Ethereum storage
+//! schema are not versioned by a pallet of this runtime. Their migrations run
+//! while the storage they migrate is still there.
+//!
+//! With the `try-runtime` feature, `pre_upgrade` and `post_upgrade` check the
+//! storage before and after the migrations.
+//!
+//! To change the storage of a pallet, raise the version of the pallet and add a
+//! `Migration` to its version to `Migrations`.
+
+use sp_std::{prelude::*, marker::PhantomData};
+use codec::DecodeAll;
+use sp_core::crypto::key_types::{AUTHORITY_DISCOVERY, IM_ONLINE};
+use sp_io::hashing::twox_128;
+use sp_runtime::impl_opaque_keys;
+use frame_support::{
+	traits::{Currency, Get, GetPalletVersion, OnRuntimeUpgrade, PalletVersion},
+	storage::{unhashed, migration::{get_storage_value, take_storage_value}},
+	weights::Weight,
+};
+use pallet_ethereum::{EthereumStorageSchema, PALLET_ETHEREUM_SCHEMA};
+use pallet_staking::{RewardDestination, ValidatorPrefs};
+
+use crate::constants::staking::VALIDATOR_BOND;
+use crate::{
+	AccountId, AccountService, Balances, BlockWeights, Origin, Runtime, Staking, Aura, Grandpa,
+	opaque::SessionKeys, placeholder_session_key,
+};
+
+/// Migrations run by `Executive` on a runtime upgrade, in order.
+pub type Migrations = (
+	ValidatorSetToStaking,
+	UpgradeSessionKeys,
+	RemoveCollectiveFlip,
+	Versioned<AccountServiceV3>,
+	EthereumSchemaV1,
+	CheckBalances,
+);
+
+/// A storage migration of a pallet.
+pub trait Migration {
+	/// Pallet whose storage the migration changes.
+	type Pallet: GetPalletVersion;
+
+	/// Version of the pallet with the migrated layout, no higher than the version
+	/// of the pallet in this runtime.
+	fn version() -> PalletVersion;
+
+	/// Migrates the storage.
+	fn migrate() -> Weight;
+
+	/// Checks the storage before the migration.
+	#[cfg(feature = "try-runtime")]
+	fn pre_upgrade() -> Result<(), &'static str> {
+		Ok(())
+	}
+
+	/// Checks the storage after the migration.
+	#[cfg(feature = "try-runtime")]
+	fn post_upgrade() -> Result<(), &'static str> {
+		Ok(())
+	}
+}
+
+/// Outcome of a block executed by `TryExecuteBlock`.
+#[cfg(feature = "try-runtime")]
+#[derive(Clone, Eq, PartialEq, codec::Encode, codec::Decode, sp_runtime::RuntimeDebug)]
+pub struct ExecutedBlock {
+	/// Weight of the block.
+	pub weight: Weight,
+	/// Extrinsics which could not be applied, invalid on top of the migrated state.
+	pub invalid: u32,
+	/// Extrinsics which were applied, but whose call returned an error.
+	pub dispatch_errors: u32,
+}
+
+#[cfg(feature = "try-runtime")]
+sp_api::decl_runtime_apis! {
+	/// Execution of blocks on top of migrated state, for `try-runtime`.
+	pub trait TryExecuteBlock {
+		/// Executes `block` without checking its state root.
+		fn execute_block_no_check(block: Block) -> ExecutedBlock;
+	}
+}
+
+/// Whether the stored version of the pallet of `M` is lower than the version of `M`.
+///
+/// A pallet without stored version has no storage to migrate yet.
+fn needs_migration<M: Migration>() -> bool {
+	M::Pallet::storage_version().map_or(false, |stored| stored < M::version())
+}
+
+/// Runs `M` while the stored version of its pallet is lower than `M::version()`.
+pub struct Versioned<M>(PhantomData<M>);
+
+impl<M: Migration> OnRuntimeUpgrade for Versioned<M> {
+	fn on_runtime_upgrade() -> Weight {
+		let db_weight = <Runtime as frame_system::Config>::DbWeight::get();
+		if !needs_migration::<M>() {
+			return db_weight.reads(1);
+		}
+		M::migrate().saturating_add(db_weight.reads(1))
+	}
+
+	#[cfg(feature = "try-runtime")]
+	fn pre_upgrade() -> Result<(), &'static str> {
+		if M::version() > M::Pallet::current_version() {
+			return Err("The migration is to a version above the version of its pallet");
+		}
+		if !needs_migration::<M>() {
+			return Ok(());
+		}
+		M::pre_upgrade()
+	}
+
+	/// Runs once the pallets stored their version.
+	#[cfg(feature = "try-runtime")]
+	fn post_upgrade() -> Result<(), &'static str> {
+		if needs_migration::<M>() {
+			return Err("The stored version of the pallet is still lower than the version of the migration");
+		}
+		M::post_upgrade()
+	}
+}
+
+/// Storage prefix of the removed `pallet_validator_set`, which stored
+/// `Validators: Vec<AccountId>` and `Flag: bool`.
+const VALIDATOR_SET: &[u8] = b"ValidatorSet";
+
+/// Seeds `pallet_staking` with the validators of the removed `pallet_validator_set`.
+///
+/// Each validator bonds `VALIDATOR_BOND`, as at genesis, as its own stash and
+/// controller and declares itself a validator. A validator with less free balance
+/// is left out. The validators become invulnerables, and the first era starts
+/// with the next session.
+pub struct ValidatorSetToStaking;
+
+impl OnRuntimeUpgrade for ValidatorSetToStaking {
+	fn on_runtime_upgrade() -> Weight {
+		let validators = match take_storage_value::<Vec<AccountId>>(VALIDATOR_SET, b"Validators", &[]) {
+			Some(validators) => validators,
+			None => return <Runtime as frame_system::Config>::DbWeight::get().reads(1),
+		};
+		let _ = take_storage_value::<bool>(VALIDATOR_SET, b"Flag", &[]);
+		// The pallet version FRAME kept for the pallet.
+		remove_prefix_counted(&twox_128(VALIDATOR_SET));
+
+		let bonded = validators.into_iter()
+			.filter(|validator| bond_and_validate(validator.clone()))
+			.collect::<Vec<_>>();
+		let _ = Staking::set_validator_count(Origin::root(), bonded.len() as u32);
+		let _ = Staking::set_invulnerables(Origin::root(), bonded);
+
+		BlockWeights::get().max_block
+	}
+
+	#[cfg(feature = "try-runtime")]
+	fn post_upgrade() -> Result<(), &'static str> {
+		if has_prefix(&twox_128(VALIDATOR_SET)) {
+			return Err("The storage of pallet_validator_set was not removed");
+		}
+		if Staking::validator_count() == 0 {
+			return Err("No validator could bond the validator bond");
+		}
+		Ok(())
+	}
+}
+
+/// Bonds `VALIDATOR_BOND` of `validator` and declares it a validator.
+fn bond_and_validate(validator: AccountId) -> bool {
+	if Balances::free_balance(&validator) < VALIDATOR_BOND {
+		return false;
+	}
+
+	Staking::bond(
+		Origin::signed(validator.clone()),
+		validator.clone().into(),
+		VALIDATOR_BOND,
+		RewardDestination::Staked,
+	).is_ok() && Staking::validate(Origin::signed(validator), ValidatorPrefs::default()).is_ok()
+}
+
+impl_opaque_keys! {
+	/// Session keys before `im_online` and `authority_discovery`.
+	pub struct OldSessionKeys {
+		pub aura: Aura,
+		pub grandpa: Grandpa,
+	}
+}
+
+/// Adds the `im_online` and `authority_discovery` keys to the session keys of the
+/// validators, as placeholders no one holds the secret of.
+///
+/// Validators set their own keys with `session.set_keys` after the upgrade, and
+/// until they have, `ReportUnlessPlaceholderKeys` does not report them as
+/// unresponsive.
+pub struct UpgradeSessionKeys;
+
+impl UpgradeSessionKeys {
+	/// Whether the queued keys are still `OldSessionKeys`.
+	fn has_old_keys() -> bool {
+		let key = [twox_128(b"Session"), twox_128(b"QueuedKeys")].concat();
+		unhashed::get_raw(&key)
+			.map_or(false, |raw| Vec::<(AccountId, OldSessionKeys)>::decode_all(&raw).is_ok())
+	}
+}
+
+impl OnRuntimeUpgrade for UpgradeSessionKeys {
+	fn on_runtime_upgrade() -> Weight {
+		if !Self::has_old_keys() {
+			return <Runtime as frame_system::Config>::DbWeight::get().reads(1);
+		}
+
+		pallet_session::Module::<Runtime>::upgrade_keys::<OldSessionKeys, _>(|validator, old| {
+			SessionKeys {
+				im_online: placeholder_session_key(IM_ONLINE, &validator).into(),
+				authority_discovery: placeholder_session_key(AUTHORITY_DISCOVERY, &validator).into(),
+				aura: old.aura,
+				grandpa: old.grandpa,
+			}
+		});
+
+		BlockWeights::get().max_block
+	}
+
+	#[cfg(feature = "try-runtime")]
+	fn post_upgrade() -> Result<(), &'static str> {
+		if Self::has_old_keys() || pallet_session::Module::<Runtime>::queued_keys().is_empty() {
+			return Err("The queued session keys were not upgraded");
+		}
+		Ok(())
+	}
+}
+
+/// Removes the storage of `pallet_randomness_collective_flip`, replaced by
+/// `pallet_vrf_randomness`: its random material and its pallet version.
+pub struct RemoveCollectiveFlip;
+
+/// Storage prefix of the removed `pallet_randomness_collective_flip`.
+const COLLECTIVE_FLIP: &[u8] = b"RandomnessCollectiveFlip";
+
+impl OnRuntimeUpgrade for RemoveCollectiveFlip {
+	fn on_runtime_upgrade() -> Weight {
+		let removed = remove_prefix_counted(&twox_128(COLLECTIVE_FLIP));
+
+		<Runtime as frame_system::Config>::DbWeight::get().reads_writes(removed + 1, removed)
+	}
+
+	#[cfg(feature = "try-runtime")]
+	fn post_upgrade() -> Result<(), &'static str> {
+		if has_prefix(&twox_128(COLLECTIVE_FLIP)) {
+			return Err("The storage of pallet_randomness_collective_flip was not removed");
+		}
+		Ok(())
+	}
+}
+
+/// Removes the bindings `pallet-account-service` 2.0.0 left behind, before the
+/// version 3.0.0 of the pallet lists the addresses of an account out of them.
+pub struct AccountServiceV3;
+
+impl Migration for AccountServiceV3 {
+	type Pallet = AccountService;
+
+	fn version() -> PalletVersion {
+		PalletVersion::new(3, 0, 0)
+	}
+
+	fn migrate() -> Weight {
+		AccountService::migrate_to_v3()
+	}
+
+	#[cfg(feature = "try-runtime")]
+	fn post_upgrade() -> Result<(), &'static str> {
+		check_bindings()
+	}
+}
+
+/// Whether every binding of the account service is listed by its account.
+#[cfg(any(test, feature = "try-runtime"))]
+fn check_bindings() -> Result<(), &'static str> {
+	use frame_support::storage::IterableStorageMap;
+
+	let listed = pallet_account_service::FromNickname::<Runtime>::iter()
+		.all(|(address, account)| AccountService::linked_addresses(&account).contains(&address));
+	if !listed {
+		return Err("An address is bound to an account which does not list it");
+	}
+	Ok(())
+}
+
+/// Records the storage schema of `pallet_ethereum` on a chain which has none, so
+/// that the client reads its Ethereum blocks with the V1 override.
+///
+/// `pallet_ethereum` only writes the schema at genesis, and this runtime stores
+/// its blocks, receipts and statuses in the V1 layout.
+pub struct EthereumSchemaV1;
+
+impl OnRuntimeUpgrade for EthereumSchemaV1 {
+	fn on_runtime_upgrade() -> Weight {
+		let db_weight = <Runtime as frame_system::Config>::DbWeight::get();
+		if ethereum_schema() == Some(EthereumStorageSchema::V1) {
+			return db_weight.reads(1);
+		}
+		unhashed::put(PALLET_ETHEREUM_SCHEMA, &EthereumStorageSchema::V1);
+		db_weight.reads_writes(1, 1)
+	}
+
+	#[cfg(feature = "try-runtime")]
+	fn post_upgrade() -> Result<(), &'static str> {
+		if ethereum_schema() != Some(EthereumStorageSchema::V1) {
+			return Err("The Ethereum storage schema is not V1");
+		}
+		Ok(())
+	}
+}
+
+fn ethereum_schema() -> Option<EthereumStorageSchema> {
+	unhashed::get(PALLET_ETHEREUM_SCHEMA)
+}
+
+/// Checks the storage of the fork of `pallet_balances`, which this runtime does
+/// not migrate.
+///
+/// The fork stores the layout of the version it is at, a fork at another version
+/// needs a `Migration` in `Migrations` first. The total issuance has to stay the
+/// sum of the balances through the other migrations.
+pub struct CheckBalances;
+
+impl OnRuntimeUpgrade for CheckBalances {
+	fn on_runtime_upgrade() -> Weight {
+		0
+	}
+
+	#[cfg(feature = "try-runtime")]
+	fn pre_upgrade() -> Result<(), &'static str> {
+		if Balances::storage_version().map_or(false, |stored| stored != Balances::current_version()) {
+			return Err("pallet_balances is at another version than its storage, add its migration");
+		}
+		check_total_issuance()
+	}
+
+	#[cfg(feature = "try-runtime")]
+	fn post_upgrade() -> Result<(), &'static str> {
+		check_total_issuance()
+	}
+}
+
+/// Whether the total issuance is the sum of the free and reserved balances.
+#[cfg(any(test, feature = "try-runtime"))]
+fn check_total_issuance() -> Result<(), &'static str> {
+	let total = frame_system::Account::<Runtime>::iter()
+		.fold(0u128, |total, (_, account)| {
+			total.saturating_add(account.data.free).saturating_add(account.data.reserved)
+		});
+	if total != Balances::total_issuance() {
+		return Err("The total issuance is not the sum of the balances");
+	}
+	Ok(())
+}
+
+/// Whether a key starts with `prefix`.
+#[cfg(any(test, feature = "try-runtime"))]
+fn has_prefix(prefix: &[u8]) -> bool {
+	unhashed::exists(prefix)
+		|| sp_io::storage::next_key(prefix).map_or(false, |key| key.starts_with(prefix))
+}
+
+/// Removes the keys starting with `prefix`, `prefix` included, and returns how many
+/// there were.
+fn remove_prefix_counted(prefix: &[u8]) -> u64 {
+	let mut removed = 0;
+	if unhashed::exists(prefix) {
+		unhashed::kill(prefix);
+		removed += 1;
+	}
+	while let Some(key) = sp_io::storage::next_key(prefix).filter(|key| key.starts_with(prefix)) {
+		unhashed::kill(&key);
+		removed += 1;
+	}
+	removed
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use frame_support::traits::PALLET_VERSION_STORAGE_KEY_POSTFIX;
+	use sp_core::{bytes::from_hex, storage::Storage};
+	use crate::Executive;
+
+	/// The genesis state of Galois, which still has `pallet_validator_set`, the old
+	/// session keys, `pallet_randomness_collective_flip` and version 2.0.0 of
+	/// `pallet-account-service`.
+	fn galois_ext() -> sp_io::TestExternalities {
+		let spec: serde_json::Value =
+			serde_json::from_str(include_str!("../../node/res/galois.json")).unwrap();
+		let top = spec["genesis"]["raw"]["top"].as_object().unwrap().iter()
+			.map(|(key, value)| (from_hex(key).unwrap(), from_hex(value.as_str().unwrap()).unwrap()))
+			.collect();
+
+		sp_io::TestExternalities::new(Storage { top, children_default: Default::default() })
+	}
+
+	fn pallet_version_key(pallet: &[u8]) -> Vec<u8> {
+		[twox_128(pallet), twox_128(PALLET_VERSION_STORAGE_KEY_POSTFIX)].concat()
+	}
+
+	/// Checks the storage once migrated, as the `post_upgrade` of the migrations.
+	fn assert_migrated() {
+		assert!(!has_prefix(&twox_128(VALIDATOR_SET)));
+		assert!(Staking::validator_count() > 0);
+		assert!(!UpgradeSessionKeys::has_old_keys());
+		assert!(!has_prefix(&twox_128(COLLECTIVE_FLIP)));
+		assert_eq!(AccountService::storage_version(), Some(AccountService::current_version()));
+		assert_eq!(check_bindings(), Ok(()));
+		assert_eq!(ethereum_schema(), Some(EthereumStorageSchema::V1));
+		assert_eq!(check_total_issuance(), Ok(()));
+	}
+
+	#[test]
+	fn migrations_are_to_versions_of_the_runtime() {
+		galois_ext().execute_with(|| {
+			assert!(AccountServiceV3::version() <= AccountService::current_version());
+			assert_eq!(Balances::storage_version(), Some(Balances::current_version()));
+		});
+	}
+
+	#[test]
+	fn migrates_galois() {
+		galois_ext().execute_with(|| {
+			let random_material = [twox_128(COLLECTIVE_FLIP), twox_128(b"RandomMaterial")].concat();
+			unhashed::put_raw(&random_material, &[4, 1, 2, 3]);
+			let validators = get_storage_value::<Vec<AccountId>>(VALIDATOR_SET, b"Validators", &[])
+				.unwrap();
+			assert_eq!(validators.len(), 3);
+			assert!(UpgradeSessionKeys::has_old_keys());
+			assert_eq!(AccountService::storage_version(), Some(PalletVersion::new(2, 0, 0)));
+			assert!(needs_migration::<AccountServiceV3>());
+			unhashed::kill(PALLET_ETHEREUM_SCHEMA);
+
+			Executive::execute_on_runtime_upgrade();
+
+			assert_migrated();
+			assert!(!needs_migration::<AccountServiceV3>());
+			assert_eq!(unhashed::get_raw(&pallet_version_key(VALIDATOR_SET)), None);
+			assert_eq!(Staking::validator_count(), 3);
+			assert_eq!(Staking::invulnerables(), validators);
+			for validator in &validators {
+				assert_eq!(Staking::ledger(validator).unwrap().active, VALIDATOR_BOND);
+			}
+
+			let queued_keys = pallet_session::Module::<Runtime>::queued_keys();
+			assert_eq!(queued_keys.len(), 3);
+			for (validator, keys) in queued_keys {
+				assert_eq!(keys.im_online, placeholder_session_key(IM_ONLINE, &validator).into());
+				assert_eq!(
+					keys.authority_discovery,
+					placeholder_session_key(AUTHORITY_DISCOVERY, &validator).into(),
+				);
+			}
+		});
+	}
+
+	#[test]
+	fn migrates_once() {
+		galois_ext().execute_with(|| {
+			Executive::execute_on_runtime_upgrade();
+			let root = sp_io::storage::root();
+
+			Executive::execute_on_runtime_upgrade();
+
+			assert_eq!(sp_io::storage::root(), root);
+		});
+	}
+
+	/// Runs the migrations on a state snapshot of Galois, taken with the
+	/// `remote-externalities` of `try-runtime`, at the path in `GALOIS_SNAPSHOT`.
+	///
+	/// `cargo test -p mathchain-runtime migrates_galois_snapshot -- --ignored`
+	#[test]
+	#[ignore]
+	fn migrates_galois_snapshot() {
+		use remote_externalities::{Builder, Mode, OfflineConfig, SnapshotConfig};
+
+		let snapshot = std::env::var_os("GALOIS_SNAPSHOT").expect("GALOIS_SNAPSHOT is the path of a snapshot");
+		let config = OfflineConfig { state_snapshot: SnapshotConfig::new(std::path::PathBuf::from(snapshot)) };
+		let mut ext = futures::executor::block_on(Builder::new().mode(Mode::Offline(config)).build()).unwrap();
+
+		ext.execute_with(|| {
+			assert_eq!(check_total_issuance(), Ok(()));
+
+			Executive::execute_on_runtime_upgrade();
+			assert_migrated();
+			let root = sp_io::storage::root();
+
+			Executive::execute_on_runtime_upgrade();
+			assert_eq!(sp_io::storage::root(), root);
+		});
+	}
+}
@@ -0,0 +1,574 @@
+//! EVM tracer used by the `DebugRuntimeApi`.
+//!
+//! Only compiled with the `evm-tracing` feature. It listens to the events emitted
+//! by the `evm`, `evm-runtime` and `evm-gasometer` crates while an extrinsic is
+//! applied and turns them into a `TransactionTrace`.
+
+use sp_std::{prelude::*, rc::Rc, cell::RefCell, collections::btree_map::BTreeMap};
+use sp_core::{H160, H256, U256};
+use evm::{ExitReason, ExitError, ExitFatal, Opcode, tracing::{Event as EvmEvent, EventListener as EvmListener}};
+use evm_runtime::tracing::{Event as RuntimeEvent, EventListener as RuntimeListener};
+use evm_gasometer::tracing::{Event as GasometerEvent, EventListener as GasometerListener, Snapshot};
+use mathchain_rpc_primitives::debug::{
+	TraceType, TransactionTrace, RawStepLog, Call, CallType, CallResult,
+};
+
+/// Call being executed, completed on `Exit`.
+struct PendingCall {
+	/// Position of the call in entering order.
+	order: usize,
+	call: Call,
+	subcalls: u32,
+	/// Gas limit of the gasometer of the frame, known from its first snapshot.
+	gas_limit: Option<u64>,
+	/// Remaining gas reported by the last snapshot of the frame.
+	remaining_gas: u64,
+}
+
+pub struct Tracer {
+	trace_type: TraceType,
+	/// Remaining gas reported by the last gasometer event.
+	remaining_gas: u64,
+	/// Gas limit of the traced transaction.
+	gas_limit: u64,
+	depth: usize,
+	/// Step being executed, completed on `StepResult`.
+	step: Option<RawStepLog>,
+	step_logs: Vec<RawStepLog>,
+	/// Storage touched by each call frame, if storage tracing is enabled.
+	storage: Vec<BTreeMap<H256, H256>>,
+	return_value: Vec<u8>,
+	calls_stack: Vec<PendingCall>,
+	calls: Vec<(usize, Call)>,
+	entered: usize,
+	/// Opcode of the last step, which made the call being entered.
+	last_opcode: Option<Opcode>,
+}
+
+impl Tracer {
+	pub fn new(trace_type: TraceType) -> Self {
+		Self {
+			trace_type,
+			remaining_gas: 0,
+			gas_limit: 0,
+			depth: 0,
+			step: None,
+			step_logs: Vec::new(),
+			storage: Vec::new(),
+			return_value: Vec::new(),
+			calls_stack: Vec::new(),
+			calls: Vec::new(),
+			entered: 0,
+			last_opcode: None,
+		}
+	}
+
+	/// Run `f` with this tracer listening to EVM events.
+	pub fn trace<R, F: FnOnce() -> R>(self, f: F) -> (Self, R) {
+		let wrapped = Rc::new(RefCell::new(self));
+
+		let mut evm = ListenerProxy(Rc::clone(&wrapped));
+		let mut runtime = ListenerProxy(Rc::clone(&wrapped));
+		let mut gasometer = ListenerProxy(Rc::clone(&wrapped));
+
+		let f = || evm_runtime::tracing::using(&mut runtime, f);
+		let f = || evm_gasometer::tracing::using(&mut gasometer, f);
+		let result = evm::tracing::using(&mut evm, f);
+
+		drop((evm, runtime, gasometer));
+		let tracer = Rc::try_unwrap(wrapped)
+			.map_err(|_| ())
+			.expect("all listener proxies were dropped; qed")
+			.into_inner();
+
+		(tracer, result)
+	}
+
+	pub fn into_trace(self) -> TransactionTrace {
+		match self.trace_type {
+			TraceType::Raw { .. } => TransactionTrace::Raw {
+				gas: U256::from(self.gas_limit.saturating_sub(self.remaining_gas)),
+				return_value: self.return_value,
+				step_logs: self.step_logs,
+			},
+			TraceType::CallList => {
+				let mut calls = self.calls;
+				// Calls are completed innermost first, report them in entering order.
+				calls.sort_by_key(|(order, _)| *order);
+				TransactionTrace::CallList(calls.into_iter().map(|(_, call)| call).collect())
+			},
+		}
+	}
+
+	fn record_snapshot(&mut self, snapshot: Option<Snapshot>) {
+		let snapshot = match snapshot {
+			Some(snapshot) => snapshot,
+			None => return,
+		};
+		let remaining_gas = snapshot.gas_limit
+			.saturating_sub(snapshot.used_gas)
+			.saturating_sub(snapshot.memory_gas);
+		self.remaining_gas = remaining_gas;
+
+		// A snapshot comes from the gasometer of the frame being executed, or from the
+		// gasometer of its parent, which takes back the gas left when the frame exits.
+		// The gasometers are told apart by their gas limit, a frame without snapshot
+		// yet takes the first one which is not of a frame below it.
+		let frame = self.calls_stack.iter()
+			.rposition(|frame| frame.gas_limit == Some(snapshot.gas_limit))
+			.or_else(|| self.calls_stack.len().checked_sub(1)
+				.filter(|top| self.calls_stack[*top].gas_limit.is_none()));
+		if let Some(frame) = frame {
+			let frame = &mut self.calls_stack[frame];
+			frame.gas_limit = Some(snapshot.gas_limit);
+			frame.remaining_gas = remaining_gas;
+		}
+	}
+
+	fn record_cost(&mut self, cost: u64, snapshot: Option<Snapshot>) {
+		if let Some(step) = self.step.as_mut() {
+			step.gas_cost = U256::from(cost);
+		}
+		self.record_snapshot(snapshot);
+	}
+
+	/// Trace address of the next call entered from the current frame.
+	fn next_trace_address(&mut self) -> (usize, Vec<u32>) {
+		let order = self.entered;
+		self.entered += 1;
+
+		match self.calls_stack.last_mut() {
+			Some(parent) => {
+				let mut trace_address = parent.call.trace_address.clone();
+				trace_address.push(parent.subcalls);
+				parent.subcalls += 1;
+				(order, trace_address)
+			},
+			None => (order, Vec::new()),
+		}
+	}
+
+	fn enter_call(&mut self, call_type: CallType, from: H160, to: H160, value: U256, gas: u64, input: &[u8]) {
+		let (order, trace_address) = self.next_trace_address();
+		self.calls_stack.push(PendingCall {
+			order,
+			call: Call {
+				call_type,
+				from,
+				to,
+				value,
+				gas: U256::from(gas),
+				gas_used: U256::zero(),
+				input: input.to_vec(),
+				result: CallResult::Output(Vec::new()),
+				trace_address,
+			},
+			subcalls: 0,
+			gas_limit: None,
+			remaining_gas: gas,
+		});
+		self.depth += 1;
+		self.storage.push(BTreeMap::new());
+	}
+
+	fn exit_call(&mut self, reason: &ExitReason, return_value: &[u8]) {
+		self.depth = self.depth.saturating_sub(1);
+		self.storage.pop();
+		if self.depth == 0 {
+			self.return_value = return_value.to_vec();
+		}
+
+		if let Some(PendingCall { order, mut call, gas_limit, remaining_gas, .. }) = self.calls_stack.pop() {
+			let gas_limit = gas_limit.map(U256::from).unwrap_or(call.gas);
+			call.gas_used = gas_limit.saturating_sub(U256::from(remaining_gas));
+			call.result = match reason {
+				ExitReason::Succeed(_) => CallResult::Output(return_value.to_vec()),
+				ExitReason::Revert(_) => CallResult::Error(b"execution reverted".to_vec()),
+				ExitReason::Error(err) => CallResult::Error(exit_error_message(err).to_vec()),
+				ExitReason::Fatal(ExitFatal::CallErrorAsFatal(err)) =>
+					CallResult::Error(exit_error_message(err).to_vec()),
+				ExitReason::Fatal(_) => CallResult::Error(b"fatal error".to_vec()),
+			};
+			self.calls.push((order, call));
+		}
+	}
+
+	/// A self-destruct does not open a call frame, it is recorded as completed right away.
+	fn record_selfdestruct(&mut self, address: H160, target: H160, balance: U256) {
+		let (order, trace_address) = self.next_trace_address();
+		self.calls.push((order, Call {
+			call_type: CallType::SelfDestruct,
+			from: address,
+			to: target,
+			value: balance,
+			gas: U256::zero(),
+			gas_used: U256::zero(),
+			input: Vec::new(),
+			result: CallResult::Output(Vec::new()),
+			trace_address,
+		}));
+	}
+}
+
+fn exit_error_message(err: &ExitError) -> &'static [u8] {
+	match err {
+		ExitError::StackUnderflow => b"stack underflow",
+		ExitError::StackOverflow => b"stack overflow",
+		ExitError::InvalidJump => b"invalid jump",
+		ExitError::InvalidRange => b"invalid range",
+		ExitError::DesignatedInvalid => b"invalid opcode",
+		ExitError::CallTooDeep => b"call too deep",
+		ExitError::CreateCollision => b"contract address collision",
+		ExitError::CreateContractLimit => b"contract size limit exceeded",
+		ExitError::OutOfOffset => b"out of offset",
+		ExitError::OutOfGas => b"out of gas",
+		ExitError::OutOfFund => b"insufficient balance for transfer",
+		ExitError::PCUnderflow => b"pc underflow",
+		ExitError::CreateEmpty => b"create empty",
+		ExitError::Other(_) => b"execution error",
+	}
+}
+
+/// Type of a call frame entered by `opcode`.
+///
+/// `DELEGATECALL` and `CALLCODE` both run the code of another address in the
+/// context of the caller, only the opcode tells them apart.
+fn call_type(opcode: Option<Opcode>, is_static: bool) -> CallType {
+	match opcode.map(|opcode| opcode.0) {
+		Some(0xf2) => CallType::CallCode,
+		Some(0xf4) => CallType::DelegateCall,
+		Some(0xfa) => CallType::StaticCall,
+		_ if is_static => CallType::StaticCall,
+		_ => CallType::Call,
+	}
+}
+
+/// Forwards the events of the three EVM crates to a shared `Tracer`.
+struct ListenerProxy(Rc<RefCell<Tracer>>);
+
+impl EvmListener for ListenerProxy {
+	fn event(&mut self, event: EvmEvent) {
+		let mut tracer = self.0.borrow_mut();
+		match event {
+			EvmEvent::TransactCall { caller, address, value, data, gas_limit } => {
+				tracer.gas_limit = gas_limit;
+				tracer.remaining_gas = gas_limit;
+				tracer.enter_call(CallType::Call, caller, address, value, gas_limit, data);
+			},
+			EvmEvent::TransactCreate { caller, value, init_code, gas_limit, address }
+			| EvmEvent::TransactCreate2 { caller, value, init_code, gas_limit, address, .. } => {
+				tracer.gas_limit = gas_limit;
+				tracer.remaining_gas = gas_limit;
+				tracer.enter_call(CallType::Create, caller, address, value, gas_limit, init_code);
+			},
+			EvmEvent::Call { code_address, transfer, input, target_gas, is_static, context } => {
+				let call_type = call_type(tracer.last_opcode.take(), is_static);
+				let value = transfer.as_ref().map(|t| t.value).unwrap_or(context.apparent_value);
+				let gas = target_gas.unwrap_or(tracer.remaining_gas);
+				tracer.enter_call(call_type, context.caller, code_address, value, gas, input);
+			},
+			EvmEvent::Create { caller, address, value, init_code, target_gas, .. } => {
+				let gas = target_gas.unwrap_or(tracer.remaining_gas);
+				tracer.enter_call(CallType::Create, caller, address, value, gas, init_code);
+			},
+			EvmEvent::Suicide { address, target, balance } => {
+				tracer.record_selfdestruct(address, target, balance);
+			},
+			EvmEvent::Exit { reason, return_value } => {
+				tracer.exit_call(reason, return_value);
+			},
+		}
+	}
+}
+
+impl RuntimeListener for ListenerProxy {
+	fn event(&mut self, event: RuntimeEvent) {
+		let mut tracer = self.0.borrow_mut();
+		// Call frames are classified by the opcode which entered them, in every trace type.
+		if let RuntimeEvent::Step { opcode, .. } = &event {
+			tracer.last_opcode = Some(*opcode);
+		}
+		let (disable_storage, disable_memory, disable_stack) = match tracer.trace_type {
+			TraceType::Raw { disable_storage, disable_memory, disable_stack } =>
+				(disable_storage, disable_memory, disable_stack),
+			TraceType::CallList => return,
+		};
+
+		match event {
+			RuntimeEvent::Step { context: _, opcode, position, stack, memory } => {
+				let step = RawStepLog {
+					depth: U256::from(tracer.depth),
+					gas: U256::from(tracer.remaining_gas),
+					gas_cost: U256::zero(),
+					memory: if disable_memory {
+						None
+					} else {
+						Some(memory.data().chunks(32).map(|chunk| {
+							let mut word = [0u8; 32];
+							word[..chunk.len()].copy_from_slice(chunk);
+							H256::from(word)
+						}).collect())
+					},
+					op: opcode_name(opcode).to_vec(),
+					pc: U256::from(*position.as_ref().unwrap_or(&0)),
+					stack: if disable_stack { None } else { Some(stack.data().clone()) },
+					storage: if disable_storage { None } else { tracer.storage.last().cloned() },
+				};
+				tracer.step = Some(step);
+			},
+			RuntimeEvent::StepResult { .. } => {
+				if let Some(step) = tracer.step.take() {
+					tracer.step_logs.push(step);
+				}
+			},
+			RuntimeEvent::SLoad { index, value, .. } | RuntimeEvent::SStore { index, value, .. } => {
+				if !disable_storage {
+					if let Some(storage) = tracer.storage.last_mut() {
+						storage.insert(index, value);
+					}
+				}
+			},
+		}
+	}
+}
+
+impl GasometerListener for ListenerProxy {
+	fn event(&mut self, event: GasometerEvent) {
+		let mut tracer = self.0.borrow_mut();
+		match event {
+			GasometerEvent::RecordCost { cost, snapshot } => tracer.record_cost(cost, snapshot),
+			GasometerEvent::RecordDynamicCost { gas_cost, memory_gas, snapshot, .. } => {
+				let memory_cost = snapshot.map(|s| memory_gas.saturating_sub(s.memory_gas)).unwrap_or(0);
+				tracer.record_cost(gas_cost + memory_cost, snapshot)
+			},
+			GasometerEvent::RecordRefund { snapshot, .. }
+			| GasometerEvent::RecordStipend { snapshot, .. }
+			| GasometerEvent::RecordTransaction { snapshot, .. } => tracer.record_snapshot(snapshot),
+		}
+	}
+}
+
+/// Mnemonic of an opcode, as displayed by the Geth struct logger.
+fn opcode_name(opcode: Opcode) -> &'static [u8] {
+	match opcode.0 {
+		0x00 => b"STOP",
+		0x01 => b"ADD",
+		0x02 => b"MUL",
+		0x03 => b"SUB",
+		0x04 => b"DIV",
+		0x05 => b"SDIV",
+		0x06 => b"MOD",
+		0x07 => b"SMOD",
+		0x08 => b"ADDMOD",
+		0x09 => b"MULMOD",
+		0x0a => b"EXP",
+		0x0b => b"SIGNEXTEND",
+		0x10 => b"LT",
+		0x11 => b"GT",
+		0x12 => b"SLT",
+		0x13 => b"SGT",
+		0x14 => b"EQ",
+		0x15 => b"ISZERO",
+		0x16 => b"AND",
+		0x17 => b"OR",
+		0x18 => b"XOR",
+		0x19 => b"NOT",
+		0x1a => b"BYTE",
+		0x1b => b"SHL",
+		0x1c => b"SHR",
+		0x1d => b"SAR",
+		0x20 => b"SHA3",
+		0x30 => b"ADDRESS",
+		0x31 => b"BALANCE",
+		0x32 => b"ORIGIN",
+		0x33 => b"CALLER",
+		0x34 => b"CALLVALUE",
+		0x35 => b"CALLDATALOAD",
+		0x36 => b"CALLDATASIZE",
+		0x37 => b"CALLDATACOPY",
+		0x38 => b"CODESIZE",
+		0x39 => b"CODECOPY",
+		0x3a => b"GASPRICE",
+		0x3b => b"EXTCODESIZE",
+		0x3c => b"EXTCODECOPY",
+		0x3d => b"RETURNDATASIZE",
+		0x3e => b"RETURNDATACOPY",
+		0x3f => b"EXTCODEHASH",
+		0x40 => b"BLOCKHASH",
+		0x41 => b"COINBASE",
+		0x42 => b"TIMESTAMP",
+		0x43 => b"NUMBER",
+		0x44 => b"DIFFICULTY",
+		0x45 => b"GASLIMIT",
+		0x46 => b"CHAINID",
+		0x47 => b"SELFBALANCE",
+		0x50 => b"POP",
+		0x51 => b"MLOAD",
+		0x52 => b"MSTORE",
+		0x53 => b"MSTORE8",
+		0x54 => b"SLOAD",
+		0x55 => b"SSTORE",
+		0x56 => b"JUMP",
+		0x57 => b"JUMPI",
+		0x58 => b"PC",
+		0x59 => b"MSIZE",
+		0x5a => b"GAS",
+		0x5b => b"JUMPDEST",
+		0x60 => b"PUSH1",
+		0x61 => b"PUSH2",
+		0x62 => b"PUSH3",
+		0x63 => b"PUSH4",
+		0x64 => b"PUSH5",
+		0x65 => b"PUSH6",
+		0x66 => b"PUSH7",
+		0x67 => b"PUSH8",
+		0x68 => b"PUSH9",
+		0x69 => b"PUSH10",
+		0x6a => b"PUSH11",
+		0x6b => b"PUSH12",
+		0x6c => b"PUSH13",
+		0x6d => b"PUSH14",
+		0x6e => b"PUSH15",
+		0x6f => b"PUSH16",
+		0x70 => b"PUSH17",
+		0x71 => b"PUSH18",
+		0x72 => b"PUSH19",
+		0x73 => b"PUSH20",
+		0x74 => b"PUSH21",
+		0x75 => b"PUSH22",
+		0x76 => b"PUSH23",
+		0x77 => b"PUSH24",
+		0x78 => b"PUSH25",
+		0x79 => b"PUSH26",
+		0x7a => b"PUSH27",
+		0x7b => b"PUSH28",
+		0x7c => b"PUSH29",
+		0x7d => b"PUSH30",
+		0x7e => b"PUSH31",
+		0x7f => b"PUSH32",
+		0x80 => b"DUP1",
+		0x81 => b"DUP2",
+		0x82 => b"DUP3",
+		0x83 => b"DUP4",
+		0x84 => b"DUP5",
+		0x85 => b"DUP6",
+		0x86 => b"DUP7",
+		0x87 => b"DUP8",
+		0x88 => b"DUP9",
+		0x89 => b"DUP10",
+		0x8a => b"DUP11",
+		0x8b => b"DUP12",
+		0x8c => b"DUP13",
+		0x8d => b"DUP14",
+		0x8e => b"DUP15",
+		0x8f => b"DUP16",
+		0x90 => b"SWAP1",
+		0x91 => b"SWAP2",
+		0x92 => b"SWAP3",
+		0x93 => b"SWAP4",
+		0x94 => b"SWAP5",
+		0x95 => b"SWAP6",
+		0x96 => b"SWAP7",
+		0x97 => b"SWAP8",
+		0x98 => b"SWAP9",
+		0x99 => b"SWAP10",
+		0x9a => b"SWAP11",
+		0x9b => b"SWAP12",
+		0x9c => b"SWAP13",
+		0x9d => b"SWAP14",
+		0x9e => b"SWAP15",
+		0x9f => b"SWAP16",
+		0xa0 => b"LOG0",
+		0xa1 => b"LOG1",
+		0xa2 => b"LOG2",
+		0xa3 => b"LOG3",
+		0xa4 => b"LOG4",
+		0xf0 => b"CREATE",
+		0xf1 => b"CALL",
+		0xf2 => b"CALLCODE",
+		0xf3 => b"RETURN",
+		0xf4 => b"DELEGATECALL",
+		0xf5 => b"CREATE2",
+		0xfa => b"STATICCALL",
+		0xfd => b"REVERT",
+		0xfe => b"INVALID",
+		0xff => b"SELFDESTRUCT",
+		_ => b"UNKNOWN",
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn call_type_follows_the_opcode() {
+		assert_eq!(call_type(Some(Opcode(0xf1)), false), CallType::Call);
+		assert_eq!(call_type(Some(Opcode(0xf2)), false), CallType::CallCode);
+		assert_eq!(call_type(Some(Opcode(0xf4)), false), CallType::DelegateCall);
+		assert_eq!(call_type(Some(Opcode(0xfa)), true), CallType::StaticCall);
+	}
+
+	#[test]
+	fn call_in_static_context_is_static() {
+		assert_eq!(call_type(Some(Opcode(0xf1)), true), CallType::StaticCall);
+		assert_eq!(call_type(None, true), CallType::StaticCall);
+		assert_eq!(call_type(None, false), CallType::Call);
+	}
+
+	#[test]
+	fn subcalls_get_nested_trace_addresses() {
+		let mut tracer = Tracer::new(TraceType::CallList);
+		let (a, b) = (H160::repeat_byte(1), H160::repeat_byte(2));
+		tracer.enter_call(CallType::Call, a, b, U256::zero(), 100, &[]);
+		tracer.enter_call(CallType::CallCode, b, a, U256::zero(), 50, &[]);
+		tracer.exit_call(&ExitReason::Succeed(evm::ExitSucceed::Stopped), &[]);
+		tracer.enter_call(CallType::DelegateCall, b, a, U256::zero(), 50, &[]);
+		tracer.exit_call(&ExitReason::Succeed(evm::ExitSucceed::Stopped), &[]);
+		tracer.exit_call(&ExitReason::Succeed(evm::ExitSucceed::Stopped), &[]);
+
+		let calls = match tracer.into_trace() {
+			TransactionTrace::CallList(calls) => calls,
+			_ => panic!("call list expected"),
+		};
+		let addresses = calls.iter().map(|call| call.trace_address.clone()).collect::<Vec<_>>();
+		assert_eq!(addresses, vec![vec![], vec![0], vec![1]]);
+		assert_eq!(calls[1].call_type, CallType::CallCode);
+	}
+
+	fn snapshot(gas_limit: u64, used_gas: u64) -> Option<Snapshot> {
+		Some(Snapshot { gas_limit, memory_gas: 0, used_gas, refunded_gas: 0 })
+	}
+
+	#[test]
+	fn nested_calls_use_the_gas_of_their_own_frame() {
+		let mut tracer = Tracer::new(TraceType::CallList);
+		let (a, b) = (H160::repeat_byte(1), H160::repeat_byte(2));
+		let stopped = ExitReason::Succeed(evm::ExitSucceed::Stopped);
+
+		tracer.enter_call(CallType::Call, a, b, U256::zero(), 100_000, &[]);
+		tracer.record_snapshot(snapshot(100_000, 21_100));
+
+		tracer.enter_call(CallType::Call, b, a, U256::zero(), 50_000, &[]);
+		tracer.record_snapshot(snapshot(50_000, 2_000));
+		tracer.record_snapshot(snapshot(50_000, 5_000));
+		// The outer frame takes back the 45_000 gas left by the inner one.
+		tracer.record_snapshot(snapshot(100_000, 26_100));
+		tracer.exit_call(&stopped, &[]);
+
+		// A call into an account without code reports no gas of its own.
+		tracer.enter_call(CallType::Call, b, a, U256::zero(), 10_000, &[]);
+		tracer.record_snapshot(snapshot(100_000, 27_000));
+		tracer.exit_call(&stopped, &[]);
+
+		tracer.record_snapshot(snapshot(100_000, 30_000));
+		tracer.exit_call(&stopped, &[]);
+
+		let calls = match tracer.into_trace() {
+			TransactionTrace::CallList(calls) => calls,
+			_ => panic!("call list expected"),
+		};
+		let gas_used = calls.iter().map(|call| call.gas_used).collect::<Vec<_>>();
+		assert_eq!(gas_used, vec![U256::from(30_000), U256::from(5_000), U256::zero()]);
+	}
+}